@@ -0,0 +1,143 @@
+//! Reads program metadata in the format of the community CHIP-8 Archive (`programs.json`).
+//!
+//! The metadata file is a JSON object that maps the file name of each ROM (without extension) to an entry describing it, e.g.:
+//! ```json
+//! {
+//!   "snake": {
+//!     "title": "Snake",
+//!     "authors": ["Someone"],
+//!     "release": "2021-10-01",
+//!     "platform": "chip8",
+//!     "desc": "Eat the apples.",
+//!     "options": { "tickrate": 20, "shiftQuirks": false, "loadStoreQuirks": false },
+//!     "keys": { "up": "5", "down": "8" }
+//!   }
+//! }
+//! ```
+//! When a `programs.json` is present in the same directory as a ROM, its entry is displayed and its platform and quirk settings are applied
+//! automatically.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde_json::Value;
+use crate::octo::apply_octo_options;
+use crate::quirks::Quirks;
+use crate::system::Variant;
+
+/// The name of the metadata file that is searched for next to a ROM.
+const METADATA_FILE: &str = "programs.json";
+
+/// The metadata of a single program in the CHIP-8 Archive format.
+pub struct ArchiveEntry {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub release: Option<String>,
+    /// The platform the program was written for, e.g. `chip8`, `schip` or `xochip`.
+    pub platform: Option<String>,
+    pub description: Option<String>,
+    /// The number of instructions executed per 60 Hz frame, if specified.
+    pub tickrate: Option<u32>,
+    /// Hints describing what the keys are used for, as `(key, description)` pairs.
+    pub key_hints: Vec<(String, String)>,
+    options: Value,
+}
+
+impl ArchiveEntry {
+
+    /// Looks up the entry for the ROM at the given path in the `programs.json` file in the same directory.
+    ///
+    /// Returns `Ok(None)` if there is no metadata file, or if it does not contain an entry for the ROM. Entries are matched by the ROM's file name
+    /// without extension.
+    ///
+    /// # Example
+    /// ```ignore
+    /// if let Some(entry) = ArchiveEntry::find("roms/snake.ch8")? {
+    ///     println!("{}", entry);
+    /// }
+    /// ```
+    pub fn find<P>(rom: P) -> io::Result<Option<ArchiveEntry>>
+        where P: AsRef<Path>, {
+            let rom = rom.as_ref();
+            let metadata = rom.parent().unwrap_or(Path::new(".")).join(METADATA_FILE);
+            let stem = match rom.file_stem() {
+                Some(stem) => stem.to_string_lossy().into_owned(),
+                None => return Ok(None),
+            };
+            if !metadata.is_file() {
+                return Ok(None);
+            }
+            let json: Value = serde_json::from_slice(&fs::read(metadata)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Ok(json.get(&stem).map(|entry| ArchiveEntry::from_json(&stem, entry)))
+    }
+
+    /// Creates an entry from its JSON representation. Missing fields are left empty, the title defaults to the given name.
+    fn from_json(name: &str, entry: &Value) -> ArchiveEntry {
+        let text = |field: &str| entry[field].as_str().map(|s| s.to_string());
+        let key_hints = match &entry["keys"] {
+            Value::Object(keys) => keys.iter().map(|(key, hint)| (key.clone(), hint.as_str().map(|h| h.to_string()).unwrap_or_else(|| hint.to_string()))).collect(),
+            _ => Vec::new(),
+        };
+        ArchiveEntry {
+            title: text("title").unwrap_or_else(|| name.to_string()),
+            authors: entry["authors"].as_array().map(|a| a.iter().filter_map(|x| x.as_str()).map(|x| x.to_string()).collect()).unwrap_or_default(),
+            release: text("release"),
+            platform: text("platform"),
+            description: text("desc"),
+            tickrate: entry["options"]["tickrate"].as_u64().map(|t| t as u32),
+            key_hints,
+            options: entry["options"].clone(),
+        }
+    }
+
+    /// Applies the quirk settings of the entry to the given quirks. Settings the entry does not specify are left unchanged.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.quirks = entry.quirks(sys.quirks);
+    /// ```
+    pub fn quirks(&self, quirks: Quirks) -> Quirks {
+        apply_octo_options(quirks, &self.options)
+    }
+
+    /// Gets the variant of the CHIP-8 the entry's platform refers to, or `None` if the entry has no platform or an unknown one.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut config = Config::new();
+    /// if let Some(variant) = entry.variant() {
+    ///     config.variant = variant;
+    /// }
+    /// ```
+    pub fn variant(&self) -> Option<Variant> {
+        self.platform.as_deref().and_then(|platform| platform.parse().ok())
+    }
+}
+
+impl std::fmt::Display for ArchiveEntry {
+
+    /// Formats the `ArchiveEntry` as a list of `key: value` lines, omitting fields that are not set.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Title:    {}", self.title)?;
+        if !self.authors.is_empty() {
+            write!(f, "\nAuthors:  {}", self.authors.join(", "))?;
+        }
+        if let Some(release) = &self.release {
+            write!(f, "\nRelease:  {}", release)?;
+        }
+        if let Some(platform) = &self.platform {
+            write!(f, "\nPlatform: {}", platform)?;
+        }
+        if let Some(tickrate) = self.tickrate {
+            write!(f, "\nTickrate: {} instructions per frame", tickrate)?;
+        }
+        if let Some(description) = &self.description {
+            write!(f, "\n{}", description)?;
+        }
+        for (key, hint) in &self.key_hints {
+            write!(f, "\n  {:<8} {}", key, hint)?;
+        }
+        Ok(())
+    }
+}
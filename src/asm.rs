@@ -0,0 +1,304 @@
+//! Provides an assembler that translates mnemonics, as produced by the `disasm` module, back into bytecode.
+//!
+//! Each line contains a single instruction in the notation of Cowgod's CHIP-8 technical reference (e.g. `LD V1, 0x20` or `DRW V0, V1, 5`), or one
+//! of the data directives `DW` (a 16-bit word) and `DB` (one or more bytes). Numbers may be given in hexadecimal with a `0x` prefix, or in decimal.
+//!
+//! Whole source files are assembled with `assemble(...)`, which additionally supports labels: a line may start with `name:`, and `name` can then be
+//! used instead of an address or a number in any operand, before or after its definition.
+//!
+//! ```text
+//! ; Draws the digit 0 and waits forever.
+//!         LD I, sprite
+//!         DRW V0, V1, 5
+//! loop:   JP loop
+//! sprite: DB 0xF0, 0x90, 0x90, 0x90, 0xF0
+//! ```
+
+use std::collections::HashMap;
+use crate::disasm;
+use crate::program::{Program, PROGRAM_START};
+use crate::utils::big_endian_8_2;
+
+/// Describes why a line could not be assembled.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub message: String,
+}
+
+impl std::fmt::Display for AsmError {
+
+    /// Formats the `AsmError` as its message.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// Creates an `AsmError` with the given message.
+fn error<T>(message: String) -> Result<T, AsmError> {
+    Err(AsmError { message })
+}
+
+/// A single parsed operand of an instruction.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Operand {
+    Register(u8),
+    Number(u16),
+    I,
+    IndirectI,
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    BigFont,
+    Bcd,
+    Rpl,
+    Long,
+    Pitch,
+}
+
+/// Parses a number in hexadecimal (with a `0x` prefix) or decimal notation.
+fn parse_number(text: &str) -> Option<u16> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Parses a single operand, resolving the names of the given labels to their addresses.
+fn parse_operand(text: &str, labels: &HashMap<String, u16>) -> Result<Operand, AsmError> {
+    let operand = match text.to_uppercase().as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DelayTimer,
+        "ST" => Operand::SoundTimer,
+        "K" => Operand::Key,
+        "F" => Operand::Font,
+        "HF" => Operand::BigFont,
+        "B" => Operand::Bcd,
+        "R" => Operand::Rpl,
+        "LONG" => Operand::Long,
+        "PITCH" => Operand::Pitch,
+        register if register.len() == 2 && register.starts_with('V') => match u8::from_str_radix(&register[1..], 16) {
+            Ok(idx) => Operand::Register(idx),
+            Err(_) => return error(format!("invalid register '{}'", text)),
+        },
+        _ => match parse_number(text).or_else(|| labels.get(text).copied()) {
+            Some(value) => Operand::Number(value),
+            None if is_label(text) => return error(format!("undefined label '{}'", text)),
+            None => return error(format!("invalid operand '{}'", text)),
+        },
+    };
+    Ok(operand)
+}
+
+/// Assembles a single line into its bytes.
+///
+/// Empty lines and everything after a `;` are ignored and result in no bytes.
+///
+/// # Example
+/// ```
+/// # use chip8::asm::assemble_line;
+/// assert_eq!(assemble_line("DRW V0, V1, 5"), Ok(vec![0xD0, 0x15]));
+/// assert_eq!(assemble_line("DB 0xF0, 0x90"), Ok(vec![0xF0, 0x90]));
+/// ```
+pub fn assemble_line(line: &str) -> Result<Vec<u8>, AsmError> {
+    assemble_with_labels(line.split(';').next().unwrap_or("").trim(), &HashMap::new())
+}
+
+/// Assembles a source file with labels into the bytes of a program starting at `PROGRAM_START`.
+///
+/// The source is assembled in two passes: the first one collects the addresses of all labels, the second one encodes the instructions with the
+/// labels resolved. Errors are reported with the number of the line they occurred in (starting at 1).
+///
+/// # Example
+/// ```
+/// # use chip8::asm::assemble;
+/// assert_eq!(assemble("start: CLS\n       JP start"), Ok(vec![0x00, 0xE0, 0x12, 0x00]));
+/// ```
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut lines = Vec::new();
+    let mut address = PROGRAM_START as usize;
+    for (idx, line) in source.lines().enumerate() {
+        let at_line = |e: AsmError| AsmError { message: format!("line {}: {}", idx + 1, e.message) };
+        let mut line = line.split(';').next().unwrap_or("").trim();
+        while let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) || parse_operand(label, &HashMap::new()).is_ok() {
+                return error(format!("invalid label '{}'", label)).map_err(at_line);
+            }
+            if labels.insert(label.to_string(), address as u16).is_some() {
+                return error(format!("duplicate label '{}'", label)).map_err(at_line);
+            }
+            line = rest.trim();
+        }
+        address += instruction_size(line);
+        lines.push((idx, line));
+    }
+
+    let mut bytes = Vec::new();
+    for (idx, line) in lines {
+        let at_line = |e: AsmError| AsmError { message: format!("line {}: {}", idx + 1, e.message) };
+        bytes.extend(assemble_with_labels(line, &labels).map_err(at_line)?);
+    }
+    Ok(bytes)
+}
+
+/// Checks whether the given text is a valid label name: a letter or `_`, followed by letters, digits and `_`.
+fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Determines the number of bytes a line (without comments and labels) assembles into, without resolving its operands.
+fn instruction_size(line: &str) -> usize {
+    let (mnemonic, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    match mnemonic.to_uppercase().as_str() {
+        "" => 0,
+        "DB" => rest.split(',').filter(|o| !o.trim().is_empty()).count(),
+        _ => 2,
+    }
+}
+
+/// Assembles a single line (without comments and labels) into its bytes, resolving the names of the given labels in its operands.
+fn assemble_with_labels(line: &str, labels: &HashMap<String, u16>) -> Result<Vec<u8>, AsmError> {
+    if line.is_empty() {
+        return Ok(Vec::new());
+    }
+    let (mnemonic, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let operands = rest.split(',').map(|o| o.trim()).filter(|o| !o.is_empty()).map(|o| parse_operand(o, labels))
+        .collect::<Result<Vec<Operand>, AsmError>>()?;
+    let mnemonic = mnemonic.to_uppercase();
+
+    if mnemonic == "DB" {
+        return operands.iter().map(|operand| match operand {
+            Operand::Number(value) if *value <= 0xFF => Ok(*value as u8),
+            _ => error("DB expects bytes".to_string()),
+        }).collect();
+    }
+    encode(&mnemonic, &operands).map(|opcode| opcode.to_be_bytes().to_vec())
+}
+
+/// Encodes an instruction, given its mnemonic and operands, into its opcode.
+fn encode(mnemonic: &str, operands: &[Operand]) -> Result<u16, AsmError> {
+    use Operand::*;
+
+    let address = |a: u16| if a <= 0xFFF { Ok(a) } else { error(format!("address 0x{:X} is out of range", a)) };
+    let byte = |b: u16| if b <= 0xFF { Ok(b) } else { error(format!("value 0x{:X} does not fit into a byte", b)) };
+    let x = |r: u8| (r as u16) << 8;
+    let y = |r: u8| (r as u16) << 4;
+
+    let opcode = match (mnemonic, operands) {
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SCD", [Number(n)]) if *n <= 0xF => 0x00C0 | n,
+        ("SCU", [Number(n)]) if *n <= 0xF => 0x00D0 | n,
+        ("SCR", []) => 0x00FB,
+        ("SCL", []) => 0x00FC,
+        ("EXIT", []) => 0x00FD,
+        ("LOW", []) => 0x00FE,
+        ("HIGH", []) => 0x00FF,
+        ("SYS", [Number(a)]) => address(*a)?,
+        ("JP", [Number(a)]) => 0x1000 | address(*a)?,
+        ("JP", [Register(0), Number(a)]) => 0xB000 | address(*a)?,
+        ("CALL", [Number(a)]) => 0x2000 | address(*a)?,
+        ("SE", [Register(vx), Number(b)]) => 0x3000 | x(*vx) | byte(*b)?,
+        ("SNE", [Register(vx), Number(b)]) => 0x4000 | x(*vx) | byte(*b)?,
+        ("SE", [Register(vx), Register(vy)]) => 0x5000 | x(*vx) | y(*vy),
+        ("SAVE", [Register(vx), Register(vy)]) => 0x5002 | x(*vx) | y(*vy),
+        ("LOAD", [Register(vx), Register(vy)]) => 0x5003 | x(*vx) | y(*vy),
+        ("LD", [Register(vx), Number(b)]) => 0x6000 | x(*vx) | byte(*b)?,
+        ("ADD", [Register(vx), Number(b)]) => 0x7000 | x(*vx) | byte(*b)?,
+        ("LD", [Register(vx), Register(vy)]) => 0x8000 | x(*vx) | y(*vy),
+        ("OR", [Register(vx), Register(vy)]) => 0x8001 | x(*vx) | y(*vy),
+        ("AND", [Register(vx), Register(vy)]) => 0x8002 | x(*vx) | y(*vy),
+        ("XOR", [Register(vx), Register(vy)]) => 0x8003 | x(*vx) | y(*vy),
+        ("ADD", [Register(vx), Register(vy)]) => 0x8004 | x(*vx) | y(*vy),
+        ("SUB", [Register(vx), Register(vy)]) => 0x8005 | x(*vx) | y(*vy),
+        ("SHR", [Register(vx)]) => 0x8006 | x(*vx),
+        ("SHR", [Register(vx), Register(vy)]) => 0x8006 | x(*vx) | y(*vy),
+        ("SUBN", [Register(vx), Register(vy)]) => 0x8007 | x(*vx) | y(*vy),
+        ("SHL", [Register(vx)]) => 0x800E | x(*vx),
+        ("SHL", [Register(vx), Register(vy)]) => 0x800E | x(*vx) | y(*vy),
+        ("SNE", [Register(vx), Register(vy)]) => 0x9000 | x(*vx) | y(*vy),
+        ("LD", [I, Number(a)]) => 0xA000 | address(*a)?,
+        ("RND", [Register(vx), Number(b)]) => 0xC000 | x(*vx) | byte(*b)?,
+        ("DRW", [Register(vx), Register(vy), Number(n)]) if *n <= 0xF => 0xD000 | x(*vx) | y(*vy) | n,
+        ("SKP", [Register(vx)]) => 0xE09E | x(*vx),
+        ("SKNP", [Register(vx)]) => 0xE0A1 | x(*vx),
+        ("LD", [I, Long]) => 0xF000,
+        ("PLANE", [Number(n)]) if *n <= 0xF => 0xF001 | n << 8,
+        ("AUDIO", []) => 0xF002,
+        ("LD", [Register(vx), DelayTimer]) => 0xF007 | x(*vx),
+        ("LD", [Register(vx), Key]) => 0xF00A | x(*vx),
+        ("LD", [DelayTimer, Register(vx)]) => 0xF015 | x(*vx),
+        ("LD", [SoundTimer, Register(vx)]) => 0xF018 | x(*vx),
+        ("ADD", [I, Register(vx)]) => 0xF01E | x(*vx),
+        ("LD", [Font, Register(vx)]) => 0xF029 | x(*vx),
+        ("LD", [BigFont, Register(vx)]) => 0xF030 | x(*vx),
+        ("LD", [Pitch, Register(vx)]) => 0xF03A | x(*vx),
+        ("LD", [Bcd, Register(vx)]) => 0xF033 | x(*vx),
+        ("LD", [IndirectI, Register(vx)]) => 0xF055 | x(*vx),
+        ("LD", [Register(vx), IndirectI]) => 0xF065 | x(*vx),
+        ("LD", [Rpl, Register(vx)]) => 0xF075 | x(*vx),
+        ("LD", [Register(vx), Rpl]) => 0xF085 | x(*vx),
+        ("DW", [Number(word)]) => *word,
+        _ => return error(format!("invalid instruction '{}' with {} operand(s)", mnemonic, operands.len())),
+    };
+    Ok(opcode)
+}
+
+/// The result of disassembling and reassembling a program with `roundtrip(...)`.
+pub struct RoundTrip {
+    /// The number of words that were disassembled into instructions.
+    pub instructions: usize,
+    /// The address ranges (start inclusive, end exclusive) of words that could not be decoded and were treated as data (`DW`/`DB`).
+    pub data_regions: Vec<(u16, u16)>,
+    /// The addresses of all words whose reassembled bytes differ from the original ones.
+    pub mismatches: Vec<u16>,
+}
+
+/// Disassembles a program word by word, reassembles the resulting mnemonics and compares the result to the original bytes.
+///
+/// This validates the disassembler and the assembler against each other: for every decodable instruction, reassembling its mnemonic has to
+/// produce the original opcode.
+///
+/// # Example
+/// ```
+/// # use chip8::asm::roundtrip;
+/// # use chip8::program::Program;
+/// let result = roundtrip(&Program::from_hex_str("00E0 A22A FFFF")?);
+/// assert!(result.mismatches.is_empty());
+/// assert_eq!(result.data_regions, vec![(0x204, 0x206)]);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn roundtrip(program: &Program) -> RoundTrip {
+    let mut result = RoundTrip { instructions: 0, data_regions: Vec::new(), mismatches: Vec::new() };
+    for (i, word) in program.instructions.chunks(2).enumerate() {
+        let address = PROGRAM_START + 2 * i as u16;
+        let line = match word {
+            [high, low] => match disasm::mnemonic(big_endian_8_2(*high, *low).into()) {
+                Some(mnemonic) => {
+                    result.instructions += 1;
+                    mnemonic
+                },
+                None => format!("DW 0x{:0>4X}", big_endian_8_2(*high, *low)),
+            },
+            _ => format!("DB 0x{:0>2X}", word[0]),
+        };
+        let is_data = line.starts_with("DW") || line.starts_with("DB");
+        if is_data {
+            let end = address + word.len() as u16;
+            match result.data_regions.last_mut() {
+                Some(region) if region.1 == address => region.1 = end,
+                _ => result.data_regions.push((address, end)),
+            }
+        }
+        if assemble_line(&line).as_deref() != Ok(word) {
+            result.mismatches.push(address);
+        }
+    }
+    result
+}
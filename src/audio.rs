@@ -0,0 +1,179 @@
+//! Contains the `Audio` trait, through which `System::run_with(...)` plays the tone of the sound timer, and its implementations.
+
+#[cfg(feature = "audio")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "audio")]
+use std::sync::mpsc;
+#[cfg(feature = "audio")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "audio")]
+use std::thread;
+#[cfg(feature = "audio")]
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+/// The frequency of the tone played while the sound timer is active, in Hz.
+pub const TONE_FREQUENCY: f32 = 440.0;
+
+/// The pitch of the XO-CHIP audio pattern until it is changed with `FX3A`, which plays 4000 bits per second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// Gets the rate at which the bits of the XO-CHIP audio pattern are played at the given pitch, in bits per second.
+///
+/// # Example
+/// ```
+/// # use chip8::audio::{pattern_rate, DEFAULT_PITCH};
+/// assert_eq!(pattern_rate(DEFAULT_PITCH), 4000.0);
+/// ```
+pub fn pattern_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+/// An audio output for the tone of the CHIP-8, which sounds whenever the sound timer is non-zero.
+pub trait Audio: Send {
+
+    /// Starts or stops the tone. Called by the main loop whenever the state of the sound timer changes.
+    fn set_playing(&mut self, playing: bool);
+
+    /// Replaces the tone with the 128-bit audio pattern of the XO-CHIP, played at the given pitch (see `pattern_rate(...)`), or restores the tone
+    /// for `None`. Called whenever the pattern or the pitch changes. Outputs that only play the tone can ignore it.
+    fn set_pattern(&mut self, _pattern: Option<[u8; 16]>, _pitch: u8) {}
+}
+
+/// An `Audio` output that stays silent, e.g. for headless runs, tests or when muted with `--mute`.
+///
+/// # Example
+/// ```ignore
+/// sys.set_audio(Box::new(Silent));
+/// ```
+pub struct Silent;
+
+impl Audio for Silent {
+
+    fn set_playing(&mut self, _playing: bool) {}
+}
+
+/// An `Audio` output that plays a square wave of `TONE_FREQUENCY` (or the audio pattern of the XO-CHIP) on the default output device of the host,
+/// using `cpal`. Requires the `audio` feature.
+///
+/// The stream is owned by a worker thread (since streams cannot be moved between threads on all platforms), which keeps it open until the `Beeper`
+/// is dropped.
+///
+/// # Example
+/// ```ignore
+/// match Beeper::new() {
+///     Ok(beeper) => sys.set_audio(Box::new(beeper)),
+///     Err(e) => log::warn!("No sound: {}", e),
+/// }
+/// ```
+#[cfg(feature = "audio")]
+pub struct Beeper {
+    playing: Arc<AtomicBool>,
+    pattern: Arc<Mutex<Option<([u8; 16], u8)>>>,
+    stop: mpsc::Sender<()>,
+}
+
+#[cfg(feature = "audio")]
+impl Beeper {
+
+    /// Opens the default output device and starts a silent stream on it.
+    ///
+    /// # Errors
+    /// Returns an error if there is no output device or the stream cannot be started.
+    pub fn new() -> Result<Beeper, String> {
+        let playing = Arc::new(AtomicBool::new(false));
+        let pattern = Arc::new(Mutex::new(None));
+        let (stop, stopped) = mpsc::channel();
+        let (started, result) = mpsc::channel();
+        let (tone, stream_pattern) = (Arc::clone(&playing), Arc::clone(&pattern));
+        thread::spawn(move || {
+            match open_stream(tone, stream_pattern) {
+                Ok(stream) => {
+                    let _res = started.send(Ok(()));
+                    let _res = stopped.recv();
+                    drop(stream);
+                },
+                Err(e) => {
+                    let _res = started.send(Err(e));
+                },
+            }
+        });
+        result.recv().map_err(|e| e.to_string())??;
+        Ok(Beeper { playing, pattern, stop })
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Audio for Beeper {
+
+    fn set_playing(&mut self, playing: bool) {
+        self.playing.store(playing, Ordering::Relaxed);
+    }
+
+    fn set_pattern(&mut self, pattern: Option<[u8; 16]>, pitch: u8) {
+        if let Ok(mut current) = self.pattern.lock() {
+            *current = pattern.map(|pattern| (pattern, pitch));
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Drop for Beeper {
+
+    /// Closes the stream.
+    fn drop(&mut self) {
+        let _res = self.stop.send(());
+    }
+}
+
+/// Opens a stream on the default output device that plays the tone (or the pattern, if one is set) while `playing` is set.
+#[cfg(feature = "audio")]
+fn open_stream(playing: Arc<AtomicBool>, pattern: Arc<Mutex<Option<([u8; 16], u8)>>>) -> Result<cpal::Stream, String> {
+    let device = cpal::default_host().default_output_device().ok_or("no audio output device found")?;
+    let supported = device.default_output_config().map_err(|e| e.to_string())?;
+    let format = supported.sample_format();
+    let config: cpal::StreamConfig = supported.into();
+    let stream = match format {
+        cpal::SampleFormat::F32 => build_stream::<f32>(&device, config, playing, pattern),
+        cpal::SampleFormat::I16 => build_stream::<i16>(&device, config, playing, pattern),
+        cpal::SampleFormat::U16 => build_stream::<u16>(&device, config, playing, pattern),
+        other => return Err(format!("unsupported sample format {}", other)),
+    }.map_err(|e| e.to_string())?;
+    stream.play().map_err(|e| e.to_string())?;
+    Ok(stream)
+}
+
+/// Builds a stream with samples of type `T` that plays a square wave (or the bits of the pattern, if one is set) while `playing` is set, and
+/// silence otherwise. The pattern is only read if it is not locked, so the callback never blocks.
+#[cfg(feature = "audio")]
+fn build_stream<T>(device: &cpal::Device, config: cpal::StreamConfig, playing: Arc<AtomicBool>, pattern: Arc<Mutex<Option<([u8; 16], u8)>>>)
+    -> Result<cpal::Stream, cpal::Error>
+    where T: cpal::SizedSample + cpal::FromSample<f32>, {
+        let channels = config.channels as usize;
+        let sample_rate = config.sample_rate as f32;
+        let period = sample_rate / TONE_FREQUENCY;
+        let mut position = 0.0;
+        let mut current = None;
+        let mut bit = 0.0;
+        device.build_output_stream(config, move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let on = playing.load(Ordering::Relaxed);
+            if let Ok(pattern) = pattern.try_lock() {
+                current = *pattern;
+            }
+            for frame in data.chunks_mut(channels) {
+                let amplitude = match current {
+                    _ if !on => 0.0,
+                    Some((bytes, pitch)) => {
+                        let index = bit as usize % 128;
+                        bit = (bit + pattern_rate(pitch) / sample_rate) % 128.0;
+                        if bytes[index / 8] & (0x80 >> (index % 8)) != 0 { 0.2 } else { -0.2 }
+                    },
+                    None => {
+                        let amplitude = if position < period / 2.0 { 0.2 } else { -0.2 };
+                        position = (position + 1.0) % period;
+                        amplitude
+                    },
+                };
+                frame.fill(T::from_sample(amplitude));
+            }
+        }, |e| log::error!("Audio stream failed: {}", e), None)
+}
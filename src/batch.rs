@@ -0,0 +1,180 @@
+//! Runs a whole directory of ROMs headlessly and records the outcome of each of them, e.g. to validate the interpreter against a large collection.
+
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use serde_json::json;
+use crate::disasm;
+use crate::program::{Instruction, Program};
+use crate::system::{System, DEFAULT_LOOP_FREQUENCY};
+use crate::utils::big_endian_8_2;
+
+/// The number of instructions executed between two ticks of the timers, matching the default `loop_frequency` of 700 Hz.
+const INSTRUCTIONS_PER_TICK: u64 = DEFAULT_LOOP_FREQUENCY as u64 / 60;
+
+/// The way a headless run of a ROM ended.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The program reached an idle loop (a jump to itself), which is how most programs end.
+    Finished { pc: u16 },
+    /// The program reached the opcode `0000`, which halts the system.
+    Halted { pc: u16 },
+    /// The program reached an opcode that cannot be decoded.
+    IllegalOpcode { pc: u16, opcode: u16 },
+    /// The interpreter failed while executing an instruction, e.g. on a return with an empty stack.
+    Crashed { pc: u16, message: String },
+    /// The program was still running after the maximum number of cycles.
+    Timeout,
+    /// The ROM could not be loaded.
+    LoadFailed(String),
+}
+
+impl Outcome {
+
+    /// Gets the name of the outcome, as used in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Finished { .. } => "finished",
+            Outcome::Halted { .. } => "halted",
+            Outcome::IllegalOpcode { .. } => "illegal_opcode",
+            Outcome::Crashed { .. } => "crashed",
+            Outcome::Timeout => "timeout",
+            Outcome::LoadFailed(_) => "load_failed",
+        }
+    }
+
+    /// Gets the address at which the run ended, if there is one.
+    pub fn pc(&self) -> Option<u16> {
+        match self {
+            Outcome::Finished { pc } | Outcome::Halted { pc } | Outcome::IllegalOpcode { pc, .. } | Outcome::Crashed { pc, .. } => Some(*pc),
+            Outcome::Timeout | Outcome::LoadFailed(_) => None,
+        }
+    }
+
+    /// Gets additional details about the outcome, such as the illegal opcode or the error message.
+    pub fn details(&self) -> String {
+        match self {
+            Outcome::IllegalOpcode { opcode, .. } => format!("{:0>4X}", opcode),
+            Outcome::Crashed { message, .. } | Outcome::LoadFailed(message) => message.clone(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// The result of running a single ROM.
+pub struct BatchResult {
+    pub path: PathBuf,
+    pub outcome: Outcome,
+    /// The number of instructions executed before the run ended.
+    pub cycles: u64,
+}
+
+/// Runs a program headlessly for at most `max_cycles` instructions, with no keys pressed, and returns its outcome and the number of executed cycles.
+///
+/// # Example
+/// ```
+/// # use chip8::batch::{run_program, Outcome};
+/// # use chip8::program::Program;
+/// let (outcome, cycles) = run_program(Program::from_hex_str("00E0 1202")?, 1000);
+/// assert_eq!(outcome, Outcome::Finished { pc: 0x202 });
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn run_program(program: Program, max_cycles: u64) -> (Outcome, u64) {
+    let mut sys = System::new();
+    if let Err(e) = sys.load(program) {
+        return (Outcome::LoadFailed(e.to_string()), 0);
+    }
+
+    for cycle in 0..max_cycles {
+        let pc = sys.pc;
+        let opcode = big_endian_8_2(sys.memory.get(pc), sys.memory.get(pc + 1));
+        match Instruction::from(opcode) {
+            Instruction(0, 0, 0, 0) => return (Outcome::Halted { pc }, cycle),
+            Instruction(1, n1, n2, n3) if (n1 as u16) << 8 | (n2 as u16) << 4 | n3 as u16 == pc => return (Outcome::Finished { pc }, cycle),
+            instruction if disasm::mnemonic(instruction).is_none() => return (Outcome::IllegalOpcode { pc, opcode }, cycle),
+            _ => {},
+        }
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| sys.step())) {
+            let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown error".to_string());
+            return (Outcome::Crashed { pc, message }, cycle);
+        }
+        if let Some(e) = sys.error() {
+            return (Outcome::Crashed { pc, message: e.to_string() }, cycle);
+        }
+        if (cycle + 1).is_multiple_of(INSTRUCTIONS_PER_TICK) {
+            sys.tick_timers();
+        }
+    }
+    (Outcome::Timeout, max_cycles)
+}
+
+/// Runs every file in the given directory (sorted by name, not recursing into subdirectories) with `run_program(...)`.
+///
+/// Failed instructions (see `System::error()`) and panics of the interpreter are reported as `Outcome::Crashed`; the default panic message is
+/// suppressed while the ROMs are running.
+///
+/// # Errors
+/// Returns an error if the directory cannot be read.
+///
+/// # Example
+/// ```ignore
+/// let results = run_directory("roms", 100_000)?;
+/// print!("{}", to_csv(&results));
+/// ```
+pub fn run_directory<P>(directory: P, max_cycles: u64) -> io::Result<Vec<BatchResult>>
+    where P: AsRef<Path>, {
+        let mut paths: Vec<PathBuf> = fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        paths.sort();
+
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+        let results = paths.into_iter().map(|path| {
+            let (outcome, cycles) = match Program::load(&path) {
+                Ok(program) => run_program(program, max_cycles),
+                Err(e) => (Outcome::LoadFailed(e.to_string()), 0),
+            };
+            log::info!("{}: {} after {} cycles", path.display(), outcome.name(), cycles);
+            BatchResult { path, outcome, cycles }
+        }).collect();
+        panic::set_hook(default_hook);
+        Ok(results)
+}
+
+/// Formats the results as CSV with the columns `rom,outcome,cycles,pc,details`.
+///
+/// # Example
+/// ```ignore
+/// fs::write("report.csv", to_csv(&results))?;
+/// ```
+pub fn to_csv(results: &[BatchResult]) -> String {
+    let quote = |field: String| if field.contains([',', '"', '\n']) { format!("\"{}\"", field.replace('"', "\"\"")) } else { field };
+    let mut csv = String::from("rom,outcome,cycles,pc,details\n");
+    for result in results {
+        let pc = result.outcome.pc().map(|pc| format!("{:0>3X}", pc)).unwrap_or_default();
+        csv += &format!("{},{},{},{},{}\n", quote(result.path.display().to_string()), result.outcome.name(), result.cycles, pc, quote(result.outcome.details()));
+    }
+    csv
+}
+
+/// Formats the results as a JSON array with one object per ROM.
+///
+/// # Example
+/// ```ignore
+/// fs::write("report.json", to_json(&results))?;
+/// ```
+pub fn to_json(results: &[BatchResult]) -> String {
+    let entries: Vec<serde_json::Value> = results.iter().map(|result| json!({
+        "rom": result.path.display().to_string(),
+        "outcome": result.outcome.name(),
+        "cycles": result.cycles,
+        "pc": result.outcome.pc().map(|pc| format!("{:0>3X}", pc)),
+        "details": result.outcome.details(),
+    })).collect();
+    serde_json::to_string_pretty(&entries).unwrap_or_default() + "\n"
+}
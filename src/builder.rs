@@ -0,0 +1,204 @@
+//! Contains the `ProgramBuilder` struct, a typed way to generate programs from Rust code, e.g. for test ROMs and demos.
+
+use std::collections::HashMap;
+use crate::program::{Program, PROGRAM_START};
+use crate::system::DISPLAY_START;
+
+/// Describes why a `ProgramBuilder` could not build a program.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BuildError {
+    /// An instruction refers to a label that was never defined.
+    UndefinedLabel(String),
+    /// A label was defined more than once.
+    DuplicateLabel(String),
+    /// The program does not fit into the memory available for programs.
+    TooLarge(usize),
+}
+
+impl std::fmt::Display for BuildError {
+
+    /// Formats the `BuildError` as a human-readable message.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::UndefinedLabel(label) => write!(f, "undefined label '{}'", label),
+            BuildError::DuplicateLabel(label) => write!(f, "label '{}' is defined more than once", label),
+            BuildError::TooLarge(length) => write!(f, "program is too large ({} bytes)", length),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// An instruction whose address operand refers to a label that may not be defined yet.
+struct Reference {
+    offset: usize,
+    opcode: u16,
+    label: String,
+}
+
+/// Builds a `Program` instruction by instruction, resolving references to labels once the program is built.
+///
+/// Addresses are computed relative to `PROGRAM_START`, so the built program has to be loaded at that address.
+///
+/// # Example
+/// ```ignore
+/// let program = ProgramBuilder::new()
+///     .op(0x00E0)
+///     .load_i("smiley")
+///     .op(0xD015)
+///     .label("loop")
+///     .jump("loop")
+///     .label("smiley")
+///     .sprite(&[0x66, 0x66, 0x00, 0x81, 0x7E])
+///     .build()?;
+/// ```
+pub struct ProgramBuilder {
+    bytes: Vec<u8>,
+    labels: HashMap<String, usize>,
+    references: Vec<Reference>,
+    duplicates: Vec<String>,
+}
+
+impl ProgramBuilder {
+
+    /// Creates a new, empty `ProgramBuilder`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let builder = ProgramBuilder::new();
+    /// ```
+    pub fn new() -> ProgramBuilder {
+        ProgramBuilder { bytes: Vec::new(), labels: HashMap::new(), references: Vec::new(), duplicates: Vec::new() }
+    }
+
+    /// Gets the memory address at which the next instruction or data will be placed.
+    ///
+    /// The address is not limited to the 12-bit address space, so it can lie beyond the end of memory if too much was appended; `build()` reports
+    /// such programs as `BuildError::TooLarge`.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::builder::ProgramBuilder;
+    /// let mut builder = ProgramBuilder::new();
+    /// assert_eq!(builder.op(0x00E0).address(), 0x202);
+    /// ```
+    pub fn address(&self) -> usize {
+        PROGRAM_START as usize + self.bytes.len()
+    }
+
+    /// Appends a raw 16-bit opcode.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut builder = ProgramBuilder::new();
+    /// builder.op(0x00E0);
+    /// ```
+    pub fn op(&mut self, opcode: u16) -> &mut ProgramBuilder {
+        self.bytes.extend_from_slice(&opcode.to_be_bytes());
+        self
+    }
+
+    /// Defines a label at the current address, which can be referred to by `jump(...)`, `call(...)`, `load_i(...)` and `jump_v0(...)`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut builder = ProgramBuilder::new();
+    /// builder.label("loop").jump("loop");
+    /// ```
+    pub fn label(&mut self, name: &str) -> &mut ProgramBuilder {
+        let address = self.address();
+        if self.labels.insert(name.to_string(), address).is_some() {
+            self.duplicates.push(name.to_string());
+        }
+        self
+    }
+
+    /// Appends a `1NNN` instruction jumping to the given label.
+    pub fn jump(&mut self, label: &str) -> &mut ProgramBuilder {
+        self.reference(0x1000, label)
+    }
+
+    /// Appends a `2NNN` instruction calling the subroutine at the given label.
+    pub fn call(&mut self, label: &str) -> &mut ProgramBuilder {
+        self.reference(0x2000, label)
+    }
+
+    /// Appends an `ANNN` instruction setting `I` to the address of the given label.
+    pub fn load_i(&mut self, label: &str) -> &mut ProgramBuilder {
+        self.reference(0xA000, label)
+    }
+
+    /// Appends a `BNNN` instruction jumping to the address of the given label plus `V0`.
+    pub fn jump_v0(&mut self, label: &str) -> &mut ProgramBuilder {
+        self.reference(0xB000, label)
+    }
+
+    /// Appends sprite data. If the sprite has an odd number of rows, a padding byte is added, so following instructions stay aligned.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut builder = ProgramBuilder::new();
+    /// builder.label("zero").sprite(&[0xF0, 0x90, 0x90, 0x90, 0xF0]);
+    /// ```
+    pub fn sprite(&mut self, rows: &[u8]) -> &mut ProgramBuilder {
+        self.data(rows);
+        if !rows.len().is_multiple_of(2) {
+            self.bytes.push(0);
+        }
+        self
+    }
+
+    /// Appends raw bytes without any padding.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut builder = ProgramBuilder::new();
+    /// builder.data(b"HI");
+    /// ```
+    pub fn data(&mut self, bytes: &[u8]) -> &mut ProgramBuilder {
+        self.bytes.extend_from_slice(bytes);
+        self
+    }
+
+    /// Builds the program, resolving all label references.
+    ///
+    /// # Errors
+    /// Returns the first `BuildError` that occurred: a label that was defined twice or never, or a program that is too large to be loaded.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::builder::ProgramBuilder;
+    /// let program = ProgramBuilder::new().label("halt").jump("halt").build()?;
+    /// assert_eq!(program.instructions, vec![0x12, 0x00]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn build(&self) -> Result<Program, BuildError> {
+        if let Some(label) = self.duplicates.first() {
+            return Err(BuildError::DuplicateLabel(label.clone()));
+        }
+        if self.bytes.len() > (DISPLAY_START - PROGRAM_START) as usize {
+            return Err(BuildError::TooLarge(self.bytes.len()));
+        }
+
+        let mut bytes = self.bytes.clone();
+        for reference in &self.references {
+            let address = *self.labels.get(&reference.label).ok_or_else(|| BuildError::UndefinedLabel(reference.label.clone()))?;
+            let opcode = reference.opcode | address as u16;
+            bytes[reference.offset..reference.offset + 2].copy_from_slice(&opcode.to_be_bytes());
+        }
+        Ok(Program::from_bytes(bytes))
+    }
+
+    /// Appends an instruction with the given opcode prefix, whose address operand is filled in with the address of `label` when the program is built.
+    fn reference(&mut self, opcode: u16, label: &str) -> &mut ProgramBuilder {
+        self.references.push(Reference { offset: self.bytes.len(), opcode, label: label.to_string() });
+        self.op(opcode)
+    }
+}
+
+impl Default for ProgramBuilder {
+
+    fn default() -> ProgramBuilder {
+        ProgramBuilder::new()
+    }
+}
@@ -0,0 +1,230 @@
+//! Translates a line-based text protocol into `InputEvent`s, so a session can be controlled by a bot or by a chat ("Twitch plays" style).
+//!
+//! Each line contains one command. Keys are given as hexadecimal digits (`0`-`F`), durations as a number followed by `ms` or `s`:
+//! - `press <key> [duration]`: presses the key and releases it after the duration (default `DEFAULT_PRESS_TIME`).
+//! - `hold <key> <duration>`: the same as `press`, but the duration is required.
+//! - `release <key>`: releases the key immediately.
+//! - `faster`, `slower`, `next`, `save`, `load`, `reset`, `reload`, `pause`, `break`, `step`, `monitor`, `quit`: the corresponding control events.
+//!
+//! Commands are case-insensitive; empty lines and lines starting with `#` are ignored. Commands can be read from stdin or from TCP connections.
+
+use std::io::{self, BufRead, BufReader};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::input::InputEvent;
+
+/// The time a key stays pressed when `press` is used without a duration.
+const DEFAULT_PRESS_TIME: Duration = Duration::from_millis(100);
+
+/// The maximum duration of a key press, so a single command cannot block a key for a long time.
+const MAX_PRESS_TIME: Duration = Duration::from_secs(10);
+
+/// A parsed command of the line protocol.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Press the key and release it after the given duration.
+    Press { key: u8, duration: Duration },
+    /// Release the key.
+    Release { key: u8 },
+    /// Send a control event.
+    Control(InputEvent),
+}
+
+/// Parses a single line of the protocol. Returns `Ok(None)` for empty lines and comments.
+///
+/// # Example
+/// ```
+/// # use chip8::commands::{parse_command, Command};
+/// # use std::time::Duration;
+/// assert_eq!(parse_command("hold A 500ms"), Ok(Some(Command::Press { key: 0xA, duration: Duration::from_millis(500) })));
+/// ```
+pub fn parse_command(line: &str) -> Result<Option<Command>, String> {
+    let words: Vec<String> = line.split_whitespace().map(|w| w.to_lowercase()).collect();
+    let words: Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+    let command = match words.as_slice() {
+        [] => return Ok(None),
+        [first, ..] if first.starts_with('#') => return Ok(None),
+        ["press", key] => Command::Press { key: parse_key(key)?, duration: DEFAULT_PRESS_TIME },
+        ["press", key, duration] | ["hold", key, duration] => Command::Press { key: parse_key(key)?, duration: parse_duration(duration)? },
+        ["release", key] => Command::Release { key: parse_key(key)? },
+        ["faster"] => Command::Control(InputEvent::Faster),
+        ["slower"] => Command::Control(InputEvent::Slower),
+        ["next"] => Command::Control(InputEvent::NextProgram),
+        ["save"] => Command::Control(InputEvent::SaveState),
+        ["load"] => Command::Control(InputEvent::LoadState),
+        ["reset"] => Command::Control(InputEvent::Reset),
+        ["reload"] => Command::Control(InputEvent::Reload),
+        ["pause"] => Command::Control(InputEvent::TogglePause),
+        ["break"] => Command::Control(InputEvent::ToggleBreakpoint),
+        ["monitor"] => Command::Control(InputEvent::ToggleMonitor),
+        ["step"] => Command::Control(InputEvent::Step),
+        ["quit"] => Command::Control(InputEvent::Quit),
+        _ => return Err(format!("invalid command '{}'", line.trim())),
+    };
+    Ok(Some(command))
+}
+
+/// Parses a key given as a single hexadecimal digit.
+fn parse_key(key: &str) -> Result<u8, String> {
+    match u8::from_str_radix(key, 16) {
+        Ok(key) if key < 16 => Ok(key),
+        _ => Err(format!("invalid key '{}', expected 0-F", key)),
+    }
+}
+
+/// Parses a duration given in milliseconds (`500ms`) or seconds (`2s`), limited to `MAX_PRESS_TIME`.
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let parsed = if let Some(ms) = duration.strip_suffix("ms") {
+        ms.parse().ok().map(Duration::from_millis)
+    }
+    else if let Some(s) = duration.strip_suffix('s') {
+        s.parse().ok().and_then(|s: f64| Duration::try_from_secs_f64(s).ok())
+    }
+    else {
+        None
+    };
+    parsed.map(|d| d.min(MAX_PRESS_TIME)).ok_or_else(|| format!("invalid duration '{}', expected e.g. 500ms or 2s", duration))
+}
+
+/// A request to the release scheduler of a `CommandExecutor`.
+enum Schedule {
+    /// Release the key at the given time, replacing a pending release of the same key.
+    Release { key: u8, at: Instant },
+    /// Drop the pending release of the key.
+    Cancel { key: u8 },
+}
+
+/// Executes commands by sending the corresponding `InputEvent`s. The releases of pressed keys are scheduled on a single thread shared by all clones
+/// of the executor.
+///
+/// Each key has at most one pending release, so a release scheduled by an earlier `press` does not cut short a later one of the same key.
+#[derive(Clone)]
+pub struct CommandExecutor {
+    events: Sender<InputEvent>,
+    releases: Sender<Schedule>,
+}
+
+impl CommandExecutor {
+
+    /// Creates an executor that sends its events through the given sender, e.g. one obtained from `ChannelInput::sender()`, and starts its
+    /// release scheduler. The scheduler stops once all clones of the executor are dropped and the pending releases are sent.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let input = keyboard();
+    /// let executor = CommandExecutor::new(input.sender());
+    /// ```
+    pub fn new(events: Sender<InputEvent>) -> CommandExecutor {
+        let (releases, requests) = mpsc::channel();
+        let scheduler_events = events.clone();
+        thread::spawn(move || schedule_releases(requests, scheduler_events));
+        CommandExecutor { events, releases }
+    }
+
+    /// Executes a single command. Returns `false` if the receiving input source no longer exists.
+    ///
+    /// # Example
+    /// ```ignore
+    /// executor.execute(Command::Press { key: 0x5, duration: Duration::from_millis(200) });
+    /// ```
+    pub fn execute(&self, command: Command) -> bool {
+        match command {
+            Command::Press { key, duration } => {
+                self.events.send(InputEvent::Key { key, pressed: true }).is_ok()
+                    && self.releases.send(Schedule::Release { key, at: Instant::now() + duration }).is_ok()
+            },
+            Command::Release { key } => {
+                self.releases.send(Schedule::Cancel { key }).is_ok() && self.events.send(InputEvent::Key { key, pressed: false }).is_ok()
+            },
+            Command::Control(event) => self.events.send(event).is_ok(),
+        }
+    }
+
+    /// Reads commands line by line until the end of the input, executing each of them. Invalid commands are logged and skipped.
+    ///
+    /// # Example
+    /// ```ignore
+    /// executor.read_lines(io::stdin().lock());
+    /// ```
+    pub fn read_lines<R: BufRead>(&self, reader: R) {
+        for line in reader.lines() {
+            let Ok(line) = line else { break };
+            match parse_command(&line) {
+                Ok(Some(command)) => if !self.execute(command) {
+                    break;
+                },
+                Ok(None) => {},
+                Err(e) => log::warn!("{}", e),
+            }
+        }
+    }
+
+}
+
+/// Runs the release scheduler of a `CommandExecutor`: receives the scheduled releases and sends them as key events once they are due. Returns when
+/// the receiving input source no longer exists, or when no more requests can arrive and no releases are pending.
+fn schedule_releases(requests: Receiver<Schedule>, events: Sender<InputEvent>) {
+    let mut pending: [Option<Instant>; 16] = [None; 16];
+    let mut connected = true;
+    loop {
+        let now = Instant::now();
+        for (key, at) in pending.iter_mut().enumerate() {
+            if at.is_some_and(|at| at <= now) {
+                *at = None;
+                if events.send(InputEvent::Key { key: key as u8, pressed: false }).is_err() {
+                    return;
+                }
+            }
+        }
+        let next = pending.iter().flatten().min().map(|at| at.saturating_duration_since(now));
+        let request = match (next, connected) {
+            (None, false) => return,
+            (Some(timeout), false) => {
+                thread::sleep(timeout);
+                continue;
+            },
+            (Some(timeout), true) => requests.recv_timeout(timeout),
+            (None, true) => requests.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match request {
+            Ok(Schedule::Release { key, at }) => pending[key as usize] = Some(at),
+            Ok(Schedule::Cancel { key }) => pending[key as usize] = None,
+            Err(RecvTimeoutError::Timeout) => {},
+            Err(RecvTimeoutError::Disconnected) => connected = false,
+        }
+    }
+}
+
+/// Reads commands from stdin on a separate thread.
+///
+/// # Example
+/// ```ignore
+/// let input = keyboard();
+/// read_stdin(CommandExecutor::new(input.sender()));
+/// ```
+pub fn read_stdin(executor: CommandExecutor) {
+    thread::spawn(move || executor.read_lines(io::stdin().lock()));
+}
+
+/// Accepts TCP connections on the given port on a separate thread, and reads commands from each connected client.
+///
+/// # Errors
+/// Returns an error if the port cannot be bound.
+///
+/// # Example
+/// ```ignore
+/// let input = keyboard();
+/// listen(CommandExecutor::new(input.sender()), 2324)?;
+/// ```
+pub fn listen(executor: CommandExecutor, port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let executor = executor.clone();
+            thread::spawn(move || executor.read_lines(BufReader::new(stream)));
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,89 @@
+//! Runs the same program on two `System`s with different quirks side by side, sharing the input, to find out which configuration a ROM expects.
+
+use std::io::{self, Write};
+use crate::input::{InputEvent, InputSource};
+use crate::program::Program;
+use crate::quirks::Quirks;
+use crate::renderer::Renderer;
+use crate::scheduler::Scheduler;
+use crate::system::{Display, LoadError, System, DISPLAY_COLUMNS};
+
+/// The terminal row below the displays at which the quirks of each system are listed.
+const LABEL_ROW: u16 = 37;
+
+/// Runs the program on one `System` per quirk configuration, rendering the left system at the left edge of the terminal and the right one next to
+/// it. Key presses and speed changes from `input` are applied to both systems. The loop stops when both systems have halted or the input reports
+/// `InputEvent::Quit`.
+///
+/// Both systems execute the same number of instructions, so differences in their displays are only caused by the quirks. Returns both systems, e.g.
+/// to print their statistics.
+///
+/// # Errors
+/// Returns a `LoadError` if the program cannot be loaded.
+///
+/// # Example
+/// ```ignore
+/// let quirks = [Quirks::new(), Quirks::new().with_flags("shift_uses_vy")?];
+/// let [left, right] = run_comparison(&Program::load("test.ch8")?, quirks, &mut keyboard())?;
+/// ```
+pub fn run_comparison(program: &Program, quirks: [Quirks; 2], input: &mut dyn InputSource) -> Result<[System; 2], LoadError> {
+    let mut systems = [System::new(), System::new()];
+    for (sys, quirks) in systems.iter_mut().zip(quirks) {
+        sys.quirks = quirks;
+        sys.load(program.clone())?;
+    }
+    let mut displays = [Display::new(), Display::new().at_column(DISPLAY_COLUMNS + 2)];
+    for (idx, display) in displays.iter_mut().enumerate() {
+        display.begin();
+        let label = quirks[idx].to_string().split_whitespace().collect::<Vec<_>>().join(" ");
+        print!("{}[{};{}H{}", 27 as char, LABEL_ROW, idx as u16 * (DISPLAY_COLUMNS + 2) + 1, label);
+    }
+    let _res = io::stdout().flush();
+
+    let mut running = [true, true];
+    let mut scheduler = Scheduler::new(systems[0].loop_frequency());
+    while running.contains(&true) {
+        while let Some(event) = input.poll() {
+            match event {
+                InputEvent::Key { key, pressed } => for sys in &mut systems {
+                    let cycle = sys.cycles();
+                    sys.keyboard.set(key, pressed, cycle);
+                },
+                InputEvent::Faster => for sys in &mut systems {
+                    sys.set_loop_frequency(sys.loop_frequency().saturating_add((sys.loop_frequency() / 4).max(1)));
+                },
+                InputEvent::Slower => for sys in &mut systems {
+                    sys.set_loop_frequency(sys.loop_frequency() - sys.loop_frequency() / 5);
+                },
+                InputEvent::Quit => return Ok(systems),
+                InputEvent::ToggleDisplayOption(option) => for display in &mut displays {
+                    display.toggle(option);
+                },
+                InputEvent::NextProgram | InputEvent::Pan { .. } | InputEvent::TogglePause | InputEvent::ToggleBreakpoint | InputEvent::Step
+                | InputEvent::ToggleMonitor | InputEvent::ScrollMonitor(_) | InputEvent::ToggleEditor | InputEvent::HexDigit(_)
+                | InputEvent::SaveState | InputEvent::LoadState | InputEvent::Reload => {},
+                InputEvent::Reset => for sys in &mut systems {
+                    sys.reset();
+                },
+            }
+        }
+        if scheduler.frequency() != systems[0].loop_frequency() {
+            scheduler.set_frequency(systems[0].loop_frequency());
+        }
+
+        let instructions = scheduler.begin_frame();
+        for ((sys, display), running) in systems.iter_mut().zip(&mut displays).zip(&mut running) {
+            for _ in 0..instructions {
+                if !*running {
+                    break;
+                }
+                *running = sys.step();
+            }
+            sys.sync_timers();
+            Display::update(display, sys);
+            sys.memory.clear_dirty_rows();
+        }
+        scheduler.end_frame();
+    }
+    Ok(systems)
+}
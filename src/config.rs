@@ -0,0 +1,64 @@
+//! Contains the `Config` struct, which collects the settings of a `System` that are chosen before a program is run, e.g. from the command line.
+
+use crate::quirks::Quirks;
+use crate::system::{DisplayLayout, ErrorMode, KeyMode, TimerMode, Variant, WriteProtection, DEFAULT_KEYMAP, DEFAULT_LOOP_FREQUENCY, DEFAULT_TIMER_FREQUENCY};
+
+/// The settings with which a `System` is created by `System::with_config(...)`.
+///
+/// All settings can still be changed on the `System` afterwards, e.g. the `loop_frequency` with the `+`/`-` hotkeys.
+///
+/// # Example
+/// ```ignore
+/// let config = Config { loop_frequency: 1000, ..Config::new() };
+/// let sys = System::with_config(config);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// The number of instructions executed per second.
+    pub loop_frequency: u16,
+    /// The rate at which the delay and sound timers are decremented, in Hz.
+    pub timer_frequency: u16,
+    /// The behaviors of instructions that differ between interpreters.
+    pub quirks: Quirks,
+    /// How the timers are decremented while running.
+    pub timer_mode: TimerMode,
+    /// How `FX0A` observes the keyboard.
+    pub key_mode: KeyMode,
+    /// Maps each CHIP-8 key to the key that is pressed instead, see `System::keymap`.
+    pub keymap: [u8; 16],
+    /// The memory regions the program may not write to.
+    pub write_protection: WriteProtection,
+    /// What happens when an instruction fails.
+    pub error_mode: ErrorMode,
+    /// The variant of the CHIP-8 that is emulated.
+    pub variant: Variant,
+    /// The location and size of the display buffer of the CHIP-8. The SUPER-CHIP always uses `DisplayLayout::LORES` and `DisplayLayout::HIRES`.
+    pub display_layout: DisplayLayout,
+}
+
+impl Config {
+
+    /// Creates the default configuration: the original CHIP-8 at 700 instructions per second, 60 Hz timers ticked per emulated frame, the default
+    /// `Quirks`, queued key input without remapped keys, no write protection, halting on errors and the 64x32 display buffer at `DISPLAY_START`.
+    pub fn new() -> Config {
+        Config {
+            loop_frequency: DEFAULT_LOOP_FREQUENCY,
+            timer_frequency: DEFAULT_TIMER_FREQUENCY,
+            quirks: Quirks::new(),
+            timer_mode: TimerMode::Frames,
+            key_mode: KeyMode::Queue,
+            keymap: DEFAULT_KEYMAP,
+            write_protection: WriteProtection::default(),
+            error_mode: ErrorMode::Halt,
+            variant: Variant::Chip8,
+            display_layout: DisplayLayout::DEFAULT,
+        }
+    }
+}
+
+impl Default for Config {
+
+    fn default() -> Config {
+        Config::new()
+    }
+}
@@ -0,0 +1,277 @@
+//! A conformance test harness: runs well-known CHIP-8 test ROMs headlessly for a fixed number of instructions and compares the final display to
+//! golden screenshots, so regressions in `Instruction::execute(...)` are caught by `cargo test` (see `tests/conformance.rs`) and by
+//! `chip8 conformance`.
+//!
+//! The test ROMs are not distributed with the emulator: they are looked up by file name in `ROM_DIR` (e.g. `test/roms/BC_test.ch8`, see
+//! `test/roms/README.md` for where to get them). A case whose ROM is missing fails, unless the environment variable `SKIP_MISSING_ROMS_VAR` is
+//! set, which skips it instead. The generated test patterns (see the `patterns` module) need no files. The screenshots are stored in
+//! `EXPECTED_DIR` as `<name>.txt`, in the text format of `Frame`.
+//!
+//! Independently of the test ROMs, the harness runs `OPCODE_CHECKS`: tiny programs that exercise single instructions and check the values they
+//! leave in the registers, including the flags in `VF`.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::builder::ProgramBuilder;
+use crate::frames::Frame;
+use crate::patterns;
+use crate::program::Program;
+use crate::quirks::Quirks;
+use crate::system::{LoadError, System};
+
+/// The directory, relative to the root of the repository, in which the test ROMs are looked up.
+pub const ROM_DIR: &str = "test/roms";
+
+/// The directory, relative to the root of the repository, in which the golden screenshots are stored.
+pub const EXPECTED_DIR: &str = "test/expected";
+
+/// The environment variable that skips cases whose ROM is missing instead of failing them, if it is set to anything but `0`.
+pub const SKIP_MISSING_ROMS_VAR: &str = "CHIP8_SKIP_MISSING_ROMS";
+
+/// Checks whether cases whose ROM is missing are skipped, see `SKIP_MISSING_ROMS_VAR`.
+pub fn skip_missing_roms() -> bool {
+    env::var_os(SKIP_MISSING_ROMS_VAR).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Where the program of a `Case` comes from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// A ROM file in `ROM_DIR`.
+    Rom(&'static str),
+    /// One of the generated test patterns, see `patterns::generate(...)`.
+    Pattern(&'static str),
+}
+
+/// A single conformance test: a program that is run for a fixed number of instructions with the given quirks.
+#[derive(Copy, Clone, Debug)]
+pub struct Case {
+    /// The name of the case, which is also the file name of its screenshot.
+    pub name: &'static str,
+    pub source: Source,
+    /// The quirks the program is run with, as accepted by `Quirks::with_flags(...)`.
+    pub quirks: &'static str,
+    /// The number of instructions after which the display is captured, unless the program halts before.
+    pub cycles: u64,
+}
+
+/// The conformance tests, run in this order.
+pub const CASES: [Case; 6] = [
+    Case { name: "corax89_opcode_test", source: Source::Rom("test_opcode.ch8"), quirks: "default", cycles: 2_000 },
+    Case { name: "bc_test", source: Source::Rom("BC_test.ch8"), quirks: "default", cycles: 2_000 },
+    Case { name: "flags_test", source: Source::Rom("4-flags.ch8"), quirks: "chip8", cycles: 20_000 },
+    Case { name: "checkerboard", source: Source::Pattern("checkerboard"), quirks: "default", cycles: 1_000 },
+    Case { name: "font", source: Source::Pattern("font"), quirks: "default", cycles: 1_000 },
+    Case { name: "countdown", source: Source::Pattern("countdown"), quirks: "default", cycles: 20_000 },
+];
+
+/// A check of a few instructions, run with `Quirks::new()`, and of the values they leave in the registers.
+#[derive(Copy, Clone, Debug)]
+pub struct OpcodeCheck {
+    pub name: &'static str,
+    /// The opcodes of the program, which is loaded at `PROGRAM_START` and halts after the last one.
+    pub opcodes: &'static [u16],
+    /// The expected values of the registers as `(X, VX)` pairs.
+    pub registers: &'static [(u8, u8)],
+}
+
+/// The opcode checks, run in this order.
+pub const OPCODE_CHECKS: [OpcodeCheck; 24] = [
+    OpcodeCheck { name: "6XNN 7XNN", opcodes: &[0x6012, 0x7034], registers: &[(0x0, 0x46)] },
+    OpcodeCheck { name: "7XNN wraps", opcodes: &[0x6F05, 0x60FF, 0x7002], registers: &[(0x0, 0x01), (0xF, 0x05)] },
+    OpcodeCheck { name: "8XY0", opcodes: &[0x6107, 0x8010], registers: &[(0x0, 0x07), (0x1, 0x07)] },
+    OpcodeCheck { name: "8XY1 8XY2 8XY3", opcodes: &[0x600C, 0x610A, 0x8201, 0x8211, 0x8300, 0x8312, 0x8400, 0x8413],
+        registers: &[(0x2, 0x0E), (0x3, 0x08), (0x4, 0x06)] },
+    OpcodeCheck { name: "8XY4", opcodes: &[0x60FE, 0x6101, 0x8014], registers: &[(0x0, 0xFF), (0xF, 0x00)] },
+    OpcodeCheck { name: "8XY4 carry", opcodes: &[0x60FF, 0x6102, 0x8014], registers: &[(0x0, 0x01), (0xF, 0x01)] },
+    OpcodeCheck { name: "8XY4 VF result", opcodes: &[0x6FFF, 0x6101, 0x8F14], registers: &[(0xF, 0x01)] },
+    OpcodeCheck { name: "8XY5", opcodes: &[0x6005, 0x6103, 0x8015], registers: &[(0x0, 0x02), (0xF, 0x01)] },
+    OpcodeCheck { name: "8XY5 equal", opcodes: &[0x6005, 0x6105, 0x8015], registers: &[(0x0, 0x00), (0xF, 0x01)] },
+    OpcodeCheck { name: "8XY5 borrow", opcodes: &[0x6003, 0x6105, 0x8015], registers: &[(0x0, 0xFE), (0xF, 0x00)] },
+    OpcodeCheck { name: "8XY5 VF result", opcodes: &[0x6F03, 0x6105, 0x8F15], registers: &[(0xF, 0x00)] },
+    OpcodeCheck { name: "8XY7", opcodes: &[0x6003, 0x6105, 0x8017], registers: &[(0x0, 0x02), (0xF, 0x01)] },
+    OpcodeCheck { name: "8XY7 borrow", opcodes: &[0x6005, 0x6103, 0x8017], registers: &[(0x0, 0xFE), (0xF, 0x00)] },
+    OpcodeCheck { name: "8XY6", opcodes: &[0x6005, 0x8006], registers: &[(0x0, 0x02), (0xF, 0x01)] },
+    OpcodeCheck { name: "8XYE", opcodes: &[0x6081, 0x800E], registers: &[(0x0, 0x02), (0xF, 0x01)] },
+    OpcodeCheck { name: "8XY6 VF result", opcodes: &[0x6F02, 0x8FF6], registers: &[(0xF, 0x00)] },
+    OpcodeCheck { name: "3XNN 4XNN", opcodes: &[0x6005, 0x3005, 0x6101, 0x4005, 0x6201], registers: &[(0x1, 0x00), (0x2, 0x01)] },
+    OpcodeCheck { name: "5XY0 9XY0", opcodes: &[0x6005, 0x6105, 0x5010, 0x6201, 0x9010, 0x6301], registers: &[(0x2, 0x00), (0x3, 0x01)] },
+    OpcodeCheck { name: "1NNN", opcodes: &[0x1204, 0x6001, 0x6102], registers: &[(0x0, 0x00), (0x1, 0x02)] },
+    OpcodeCheck { name: "2NNN 00EE", opcodes: &[0x2206, 0x6102, 0x120A, 0x6001, 0x00EE], registers: &[(0x0, 0x01), (0x1, 0x02)] },
+    OpcodeCheck { name: "BNNN", opcodes: &[0x6002, 0x6202, 0xB206, 0x6A01, 0x6B01], registers: &[(0xA, 0x00), (0xB, 0x01)] },
+    OpcodeCheck { name: "FX33 FX55 FX65", opcodes: &[0x60FE, 0xA300, 0xF033, 0xF265, 0x6311, 0xA310, 0xF355, 0x6300, 0xA310, 0xF365],
+        registers: &[(0x0, 0x02), (0x1, 0x05), (0x2, 0x04), (0x3, 0x11)] },
+    OpcodeCheck { name: "FX15 FX07", opcodes: &[0x6030, 0xF015, 0xF107], registers: &[(0x1, 0x30)] },
+    OpcodeCheck { name: "DXYN collision", opcodes: &[0x6000, 0xF029, 0xD015, 0x8AF0, 0xD015], registers: &[(0xA, 0x00), (0xF, 0x01)] },
+];
+
+/// Runs an opcode check and describes the registers that do not have their expected values, if any.
+///
+/// # Example
+/// ```ignore
+/// for check in &OPCODE_CHECKS {
+///     if let Err(e) = check_opcodes(check) {
+///         println!("{}: {}", check.name, e);
+///     }
+/// }
+/// ```
+pub fn check_opcodes(check: &OpcodeCheck) -> Result<(), String> {
+    let mut builder = ProgramBuilder::new();
+    for opcode in check.opcodes {
+        builder.op(*opcode);
+    }
+    let program = builder.op(0x0000).build().map_err(|e| e.to_string())?;
+    let mut sys = System::new();
+    sys.load(program).map_err(|e| e.to_string())?;
+    for _ in 0..check.opcodes.len() * 2 {
+        if !sys.step() {
+            break;
+        }
+    }
+    let wrong: Vec<String> = check.registers.iter()
+        .filter(|(x, value)| sys.registers.get(*x) != *value)
+        .map(|(x, value)| format!("V{:X} = {:0>2X}, expected {:0>2X}", x, sys.registers.get(*x), value))
+        .collect();
+    if wrong.is_empty() {
+        Ok(())
+    }
+    else {
+        Err(wrong.join(", "))
+    }
+}
+
+/// The result of checking a `Case` against its screenshot.
+#[derive(Debug)]
+pub enum Verdict {
+    /// The display matches the screenshot.
+    Passed,
+    /// The display differs from the screenshot.
+    Mismatch { expected: Frame, actual: Frame },
+    /// The ROM of the case is not in `ROM_DIR`, so it could not be run.
+    MissingRom(PathBuf),
+    /// There is no screenshot for the case yet. It can be recorded with `bless(...)`.
+    MissingExpected(Frame),
+}
+
+impl Verdict {
+
+    /// Checks whether the case passed, or was skipped because its ROM is missing and `skip_missing_roms()` is set.
+    pub fn is_ok(&self) -> bool {
+        match self {
+            Verdict::Passed => true,
+            Verdict::MissingRom(_) => skip_missing_roms(),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Verdict {
+
+    /// Formats the verdict as a short summary, e.g. `passed` or `mismatch (12 pixels differ)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::Passed => write!(f, "passed"),
+            Verdict::Mismatch { expected, actual } => write!(f, "mismatch ({} pixels differ)", expected.differing_pixels(actual)),
+            Verdict::MissingRom(path) if skip_missing_roms() => write!(f, "skipped ({} not found)", path.display()),
+            Verdict::MissingRom(path) => write!(f, "{} not found (see test/roms/README.md, or set {}=1 to skip it)", path.display(),
+                SKIP_MISSING_ROMS_VAR),
+            Verdict::MissingExpected(_) => write!(f, "no screenshot"),
+        }
+    }
+}
+
+/// Runs a program headlessly for at most `cycles` instructions, ticking the timers for the emulated time, and captures the display.
+///
+/// # Errors
+/// Returns a `LoadError` if the program cannot be loaded.
+///
+/// # Example
+/// ```ignore
+/// let frame = run(Program::load("test/roms/BC_test.ch8")?, Quirks::new(), 2_000)?;
+/// println!("{}", frame);
+/// ```
+pub fn run(program: Program, quirks: Quirks, cycles: u64) -> Result<Frame, LoadError> {
+    let mut sys = System::new();
+    sys.quirks = quirks;
+    sys.load(program)?;
+    for _ in 0..cycles {
+        if !sys.step() {
+            break;
+        }
+        sys.sync_timers();
+    }
+    Ok(sys.framebuffer())
+}
+
+/// Loads the program of a case, or returns `None` if its ROM is not in the `ROM_DIR` below `root`.
+fn load(case: &Case, root: &Path) -> io::Result<Option<Program>> {
+    match case.source {
+        Source::Rom(file) => {
+            let path = root.join(ROM_DIR).join(file);
+            if !path.exists() {
+                return Ok(None);
+            }
+            Program::load(path).map(Some)
+        },
+        Source::Pattern(name) => Ok(patterns::generate(name)),
+    }
+}
+
+/// Runs a case and captures its display, or returns `None` if its ROM is missing.
+fn capture(case: &Case, root: &Path) -> io::Result<Option<Frame>> {
+    let Some(program) = load(case, root)? else { return Ok(None) };
+    let quirks = Quirks::new().with_flags(case.quirks).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    run(program, quirks, case.cycles).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// Gets the path of the screenshot of a case below `root`.
+pub fn expected_path(case: &Case, root: &Path) -> PathBuf {
+    root.join(EXPECTED_DIR).join(format!("{}.txt", case.name))
+}
+
+/// Runs a case and compares its display to the screenshot, with the `ROM_DIR` and `EXPECTED_DIR` relative to `root`.
+///
+/// # Errors
+/// Returns an error if the ROM or the screenshot cannot be read or parsed, or the ROM cannot be loaded.
+///
+/// # Example
+/// ```ignore
+/// for case in &CASES {
+///     println!("{}: {}", case.name, check(case, Path::new("."))?);
+/// }
+/// ```
+pub fn check(case: &Case, root: &Path) -> io::Result<Verdict> {
+    let Some(actual) = capture(case, root)? else {
+        if let Source::Rom(file) = case.source {
+            return Ok(Verdict::MissingRom(root.join(ROM_DIR).join(file)));
+        }
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("unknown test pattern in case '{}'", case.name)));
+    };
+    let path = expected_path(case, root);
+    if !path.exists() {
+        return Ok(Verdict::MissingExpected(actual));
+    }
+    let expected: Frame = fs::read_to_string(&path)?.parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+    if expected.digest() == actual.digest() {
+        Ok(Verdict::Passed)
+    }
+    else {
+        Ok(Verdict::Mismatch { expected, actual })
+    }
+}
+
+/// Runs a case and stores its display as the new screenshot, e.g. after adding a case or after verifying that a changed display is correct.
+/// Returns `false` if the ROM of the case is missing, so no screenshot was written.
+///
+/// # Errors
+/// Returns an error if the ROM cannot be read or loaded, or the screenshot cannot be written.
+pub fn bless(case: &Case, root: &Path) -> io::Result<bool> {
+    let Some(frame) = capture(case, root)? else { return Ok(false) };
+    let path = expected_path(case, root);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, frame.to_string())?;
+    Ok(true)
+}
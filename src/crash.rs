@@ -0,0 +1,75 @@
+//! Contains the `CrashReport` struct, a plain-text snapshot of a `System` written when the interpreter hits an unrecoverable error, so bug reports
+//! contain everything needed to reproduce the crash.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use crate::system::System;
+
+/// A view of a `System` that is formatted as a crash report, created by `CrashReport::new(...)`.
+///
+/// The report lists the error message, the SHA-1 hash of the loaded ROM, the quirks, the PC, `I` and `V` registers, the timers, the stack, the
+/// most recently executed PCs (oldest first, see `System::pc_history()`) and a hex dump of the whole memory.
+///
+/// # Example
+/// ```ignore
+/// let report = CrashReport::new(&sys, "unknown opcode 5AB1");
+/// report.save("crash.txt")?;
+/// ```
+pub struct CrashReport<'a> {
+    sys: &'a System,
+    message: &'a str,
+}
+
+impl CrashReport<'_> {
+
+    /// Creates a crash report of the given system, describing the error with the given message.
+    pub fn new<'a>(sys: &'a System, message: &'a str) -> CrashReport<'a> {
+        CrashReport { sys, message }
+    }
+
+    /// Writes the report to the given file path.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written.
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+        where P: AsRef<Path>, {
+            write!(File::create(path)?, "{}", self)
+    }
+}
+
+impl std::fmt::Display for CrashReport<'_> {
+
+    /// Formats the report as a list of `name: value` lines, followed by the PC history and the memory dump.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sys = self.sys;
+        writeln!(f, "CHIP-8 crash report")?;
+        writeln!(f, "Error:   {}", self.message)?;
+        match sys.loaded_program() {
+            Some(bytes) => writeln!(f, "ROM:     {} bytes, SHA-1 {}", bytes.len(), sha1_smol::Sha1::from(bytes).digest())?,
+            None => writeln!(f, "ROM:     none")?,
+        }
+        writeln!(f, "Cycles:  {}", sys.cycles())?;
+        writeln!(f)?;
+        writeln!(f, "Quirks:")?;
+        writeln!(f, "{}", sys.quirks)?;
+        writeln!(f)?;
+        writeln!(f, "PC:      0x{:0>3X}", sys.pc)?;
+        writeln!(f, "I:       0x{:0>3X}", sys.registers.i())?;
+        let v: Vec<String> = (0..16).map(|idx| format!("V{:X}={:0>2X}", idx, sys.registers.get(idx))).collect();
+        writeln!(f, "V:       {}", v.join(" "))?;
+        writeln!(f, "Timers:  delay {}, sound {}", sys.delay_timer.get(), sys.sound_timer.get())?;
+        let stack: Vec<String> = sys.stack.entries().iter().map(|address| format!("0x{:0>3X}", address)).collect();
+        writeln!(f, "Stack:   [{}]", stack.join(", "))?;
+        writeln!(f)?;
+        let history = sys.pc_history();
+        writeln!(f, "Last {} PCs:", history.len())?;
+        for row in history.chunks(16) {
+            let row: Vec<String> = row.iter().map(|pc| format!("{:0>3X}", pc)).collect();
+            writeln!(f, "{}", row.join(" "))?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Memory:")?;
+        write!(f, "{}", sys.memory.hexdump(0, sys.memory.size()))
+    }
+}
@@ -0,0 +1,288 @@
+//! Provides the `Debugger`, through which `System::run_with(...)` can be paused at breakpoints and stepped one instruction at a time.
+//!
+//! While the debugger is paused, the loop keeps polling the input, so it can be controlled with the `F8` (pause/continue), `F9` (toggle a breakpoint
+//! at the PC) and `F10` (single-step) hotkeys, and shows the `panel(...)` with the registers, the stack and the timers below the display.
+//!
+//! Watchpoints pause the execution after an instruction wrote to a watched address. Independently of the pause state, the `monitor(...)` shows the
+//! registers and the memory around `I` next to the display, updated live while the program runs. It is toggled with the `M` hotkey.
+//!
+//! The `Tab` hotkey turns the memory view of the monitor into a `HexEditor`: the arrow keys and `PageUp`/`PageDown` then move its cursor, and the
+//! hex digits typed on the keyboard overwrite the memory of the running system, until `Tab` is pressed again.
+
+use std::collections::BTreeSet;
+use std::ops::Range;
+use crate::disasm;
+use crate::hexedit::{EditorKey, HexEditor};
+use crate::program::Instruction;
+use crate::system::{Memory, System};
+
+/// The number of 16-byte rows of memory shown by the `monitor(...)`.
+const MONITOR_ROWS: i32 = 8;
+
+/// The keys that control the memory view of the `monitor(...)`, shown in its header.
+const MONITOR_KEYS: &str = "M: hide  PgUp/PgDn: scroll  Tab: edit";
+
+/// The keys that control the `HexEditor` of the `monitor(...)`, shown in its header while the memory is edited.
+const EDITOR_KEYS: &str = "Tab: stop editing  Arrows/PgUp/PgDn: move  0-F: type";
+
+/// The breakpoints and the execution state of an interactive debugging session.
+///
+/// # Example
+/// ```ignore
+/// let mut debugger = Debugger::new();
+/// debugger.add_breakpoint(0x20A);
+/// debugger.pause();
+/// sys.debugger = debugger;
+/// sys.run(&mut display);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    watchpoints: BTreeSet<u16>,
+    paused: bool,
+    pending_steps: u32,
+    resumed_at: Option<u16>,
+    changed: bool,
+    monitor_shown: bool,
+    monitor_scroll: i16,
+    editor: Option<HexEditor>,
+}
+
+impl Debugger {
+
+    /// Creates a new `Debugger` without breakpoints, which does not pause the execution.
+    pub fn new() -> Debugger {
+        Debugger::default()
+    }
+
+    /// Adds a breakpoint, which pauses the execution before the instruction at the given address is executed.
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+        self.changed = true;
+    }
+
+    /// Removes the breakpoint at the given address. Returns `false` if there was none.
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.changed = true;
+        self.breakpoints.remove(&address)
+    }
+
+    /// Adds a breakpoint at the given address, or removes it if there already is one.
+    pub fn toggle_breakpoint(&mut self, address: u16) {
+        if !self.remove_breakpoint(address) {
+            self.add_breakpoint(address);
+        }
+    }
+
+    /// Gets the addresses of all breakpoints, in ascending order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Adds a watchpoint, which pauses the execution after an instruction wrote to the given address.
+    pub fn add_watchpoint(&mut self, address: u16) {
+        self.watchpoints.insert(address);
+        self.changed = true;
+    }
+
+    /// Removes the watchpoint at the given address. Returns `false` if there was none.
+    pub fn remove_watchpoint(&mut self, address: u16) -> bool {
+        self.changed = true;
+        self.watchpoints.remove(&address)
+    }
+
+    /// Gets the addresses of all watchpoints, in ascending order.
+    pub fn watchpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.watchpoints.iter().copied()
+    }
+
+    /// Notes that the instruction at the given PC wrote to the given address. Called by `System::store(...)` for every write of the program.
+    ///
+    /// Pauses the execution if the address is watched, so the main loop stops before the next instruction.
+    pub fn notify_write(&mut self, address: u16, pc: u16) {
+        if self.watchpoints.is_empty() || !self.watchpoints.contains(&address) {
+            return;
+        }
+        log::info!("Watchpoint at 0x{:0>3X} written by the instruction at 0x{:0>3X}", address, pc);
+        if !self.paused {
+            self.paused = true;
+            self.pending_steps = 0;
+        }
+        self.changed = true;
+    }
+
+    /// Checks whether the execution is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses the execution before the next instruction.
+    pub fn pause(&mut self) {
+        if !self.paused {
+            self.paused = true;
+            self.pending_steps = 0;
+            self.changed = true;
+        }
+    }
+
+    /// Continues the execution at the given PC. A breakpoint at the PC is skipped once, so the execution does not stop right away.
+    pub fn resume(&mut self, pc: u16) {
+        if self.paused {
+            self.paused = false;
+            self.resumed_at = Some(pc);
+            self.changed = true;
+        }
+    }
+
+    /// Executes a single instruction while paused. Pauses the execution if it is running.
+    pub fn step(&mut self) {
+        if self.paused {
+            self.pending_steps += 1;
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Decides whether the instruction at the given PC should be executed. Called by the main loop before each instruction.
+    ///
+    /// Returns `false` while the execution is paused and no step was requested, and pauses the execution when a breakpoint is reached.
+    pub fn should_execute(&mut self, pc: u16) -> bool {
+        if self.paused {
+            if self.pending_steps == 0 {
+                return false;
+            }
+            self.pending_steps -= 1;
+            self.changed = true;
+            return true;
+        }
+        if self.resumed_at.take() != Some(pc) && self.breakpoints.contains(&pc) {
+            log::info!("Breakpoint at 0x{:0>3X}", pc);
+            self.paused = true;
+            self.changed = true;
+            return false;
+        }
+        true
+    }
+
+    /// Checks whether the `monitor(...)` is shown next to the display.
+    pub fn is_monitor_shown(&self) -> bool {
+        self.monitor_shown
+    }
+
+    /// Shows the `monitor(...)` next to the display, or hides it. Hiding it stops editing the memory.
+    pub fn toggle_monitor(&mut self) {
+        self.monitor_shown = !self.monitor_shown;
+        if !self.monitor_shown {
+            self.editor = None;
+        }
+    }
+
+    /// Checks whether the memory is edited in the `monitor(...)`.
+    pub fn is_editing(&self) -> bool {
+        self.editor.is_some()
+    }
+
+    /// Starts editing the memory in the `monitor(...)`, with the cursor of the `HexEditor` at the given address (usually `I`), or stops editing
+    /// it. Starting to edit shows the monitor if it is hidden.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::hexedit::EditorKey;
+    /// # use chip8::system::System;
+    /// let mut sys = System::new();
+    /// sys.debugger.toggle_editor(0x300, &sys.memory);
+    /// sys.debugger.edit(EditorKey::Digit(0x4), &mut sys.memory);
+    /// sys.debugger.edit(EditorKey::Digit(0x2), &mut sys.memory);
+    /// assert_eq!(sys.memory.get(0x300), 0x42);
+    /// ```
+    pub fn toggle_editor(&mut self, address: u16, memory: &Memory) {
+        if self.editor.take().is_none() {
+            let mut editor = HexEditor::new(MONITOR_ROWS as u16);
+            editor.goto(address, memory);
+            self.editor = Some(editor);
+            self.monitor_shown = true;
+        }
+    }
+
+    /// Passes a key press to the `HexEditor` of the `monitor(...)`, if the memory is edited. Returns `true` if the memory was changed.
+    pub fn edit(&mut self, key: EditorKey, memory: &mut Memory) -> bool {
+        match &mut self.editor {
+            Some(editor) => editor.handle_key(key, memory),
+            None => false,
+        }
+    }
+
+    /// Scrolls the memory view of the `monitor(...)` by the given number of rows (negative values scroll up). The view keeps following `I`.
+    pub fn scroll_monitor(&mut self, rows: i8) {
+        self.monitor_scroll = self.monitor_scroll.saturating_add(rows as i16);
+    }
+
+    /// Checks whether the state shown by the `panel(...)` may have changed since the last call, and resets the flag.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Formats the state of the system for the debugger panel: the PC with the next instruction, `I`, the registers `V0`-`VF`, the stack, the
+    /// timers and the breakpoints.
+    ///
+    /// # Example
+    /// ```ignore
+    /// display.show_panel(&sys.debugger.panel(&sys));
+    /// ```
+    pub fn panel(&self, sys: &System) -> String {
+        let opcode = u16::from_be_bytes([sys.memory.get(sys.pc), sys.memory.get(sys.pc.wrapping_add(1))]);
+        let instruction: Instruction = opcode.into();
+        let stack: Vec<String> = sys.stack.entries().iter().map(|address| format!("0x{:0>3X}", address)).collect();
+        let breakpoints: Vec<String> = self.breakpoints().map(|address| format!("0x{:0>3X}", address)).collect();
+        let mut lines = vec![
+            "PAUSED  F8: continue  F9: breakpoint  F10: step".to_string(),
+            format!("PC 0x{:0>3X}  {:0>4X}  {}", sys.pc, opcode, disasm::mnemonic(instruction).unwrap_or_else(|| "???".to_string())),
+            format!("I  0x{:0>3X}  DT {:0>2X}  ST {:0>2X}", sys.registers.i(), sys.delay_timer.get(), sys.sound_timer.get()),
+            format!("V0-V7  {}", registers(sys, 0..8)),
+            format!("V8-VF  {}", registers(sys, 8..16)),
+            format!("Stack  [{}]", stack.join(", ")),
+        ];
+        if !breakpoints.is_empty() {
+            lines.push(format!("Break  {}", breakpoints.join(", ")));
+        }
+        lines.join("\n")
+    }
+
+    /// Formats the live view of the system for the side panel: the PC, `I`, the stack depth, the timers, the registers `V0`-`VF`, the watchpoints
+    /// and a hex dump of the memory around `I`, which can be scrolled with `scroll_monitor(...)`. While the memory is edited, the `HexEditor` is
+    /// shown instead of the hex dump.
+    ///
+    /// # Example
+    /// ```ignore
+    /// display.show_side_panel(&sys.debugger.monitor(&sys));
+    /// ```
+    pub fn monitor(&self, sys: &System) -> String {
+        let i = sys.registers.i();
+        let state = if self.paused { "PAUSED" } else { "RUNNING" };
+        let mut lines = vec![
+            format!("MONITOR  {}", if self.editor.is_some() { EDITOR_KEYS } else { MONITOR_KEYS }),
+            format!("PC 0x{:0>3X}  I 0x{:0>3X}  SP {}", sys.pc, i, sys.stack.entries().len()),
+            format!("DT {:0>2X}  ST {:0>2X}  {}", sys.delay_timer.get(), sys.sound_timer.get(), state),
+            format!("V0-V7  {}", registers(sys, 0..8)),
+            format!("V8-VF  {}", registers(sys, 8..16)),
+        ];
+        if !self.watchpoints.is_empty() {
+            let watchpoints: Vec<String> = self.watchpoints().map(|address| format!("0x{:0>3X}", address)).collect();
+            lines.push(format!("Watch  {}", watchpoints.join(", ")));
+        }
+        lines.push(String::new());
+        if let Some(editor) = &self.editor {
+            lines.extend(editor.render(&sys.memory).lines().map(str::to_string));
+            return lines.join("\n");
+        }
+        let last_row = (sys.memory.size() as i32 / 16 - MONITOR_ROWS).max(0);
+        let row = (i as i32 / 16 - MONITOR_ROWS / 4 + self.monitor_scroll as i32).clamp(0, last_row);
+        lines.extend(sys.memory.hexdump((row * 16) as u16, (MONITOR_ROWS * 16) as usize).to_string().lines().map(str::to_string));
+        lines.join("\n")
+    }
+}
+
+/// Formats the given `V` registers as hexadecimal bytes, separated by spaces.
+fn registers(sys: &System, range: Range<u8>) -> String {
+    range.map(|idx| format!("{:0>2X}", sys.registers.get(idx))).collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,77 @@
+//! Provides a disassembler that translates `Instruction`s into human-readable mnemonics, such as `LD V1, 0x20` or `DRW V0, V1, 5`.
+//!
+//! The mnemonics follow the widespread notation of Cowgod's CHIP-8 technical reference. Immediate values and addresses are printed as hexadecimal
+//! numbers with a `0x` prefix, sprite heights as decimal numbers. The instructions of the SUPER-CHIP are included, e.g. `SCD 4` or `LD HF, V0`, as
+//! well as those of the XO-CHIP, e.g. `PLANE 3` or `LD I, LONG` (whose 16-bit address follows as the next word).
+
+use crate::program::Instruction;
+use crate::utils::{big_endian_4_2, big_endian_4_3};
+
+/// Translates an instruction into its mnemonic.
+///
+/// Returns `None` if the instruction cannot be decoded.
+///
+/// # Example
+/// ```
+/// # use chip8::disasm::mnemonic;
+/// # use chip8::program::Instruction;
+/// let instruction: Instruction = 0xD015u16.into();
+/// assert_eq!(mnemonic(instruction), Some("DRW V0, V1, 5".to_string()));
+/// ```
+pub fn mnemonic(instruction: Instruction) -> Option<String> {
+    let text = match instruction {
+        Instruction(0, 0, 0xE, 0) => "CLS".to_string(),
+        Instruction(0, 0, 0xE, 0xE) => "RET".to_string(),
+        Instruction(0, 0, 0xC, n) => format!("SCD {}", n),
+        Instruction(0, 0, 0xD, n) => format!("SCU {}", n),
+        Instruction(0, 0, 0xF, 0xB) => "SCR".to_string(),
+        Instruction(0, 0, 0xF, 0xC) => "SCL".to_string(),
+        Instruction(0, 0, 0xF, 0xD) => "EXIT".to_string(),
+        Instruction(0, 0, 0xF, 0xE) => "LOW".to_string(),
+        Instruction(0, 0, 0xF, 0xF) => "HIGH".to_string(),
+        Instruction(0, n1, n2, n3) => format!("SYS 0x{:0>3X}", big_endian_4_3(n1, n2, n3)),
+        Instruction(1, n1, n2, n3) => format!("JP 0x{:0>3X}", big_endian_4_3(n1, n2, n3)),
+        Instruction(2, n1, n2, n3) => format!("CALL 0x{:0>3X}", big_endian_4_3(n1, n2, n3)),
+        Instruction(3, x, n1, n2) => format!("SE V{:X}, 0x{:0>2X}", x, big_endian_4_2(n1, n2)),
+        Instruction(4, x, n1, n2) => format!("SNE V{:X}, 0x{:0>2X}", x, big_endian_4_2(n1, n2)),
+        Instruction(5, x, y, 0) => format!("SE V{:X}, V{:X}", x, y),
+        Instruction(5, x, y, 2) => format!("SAVE V{:X}, V{:X}", x, y),
+        Instruction(5, x, y, 3) => format!("LOAD V{:X}, V{:X}", x, y),
+        Instruction(6, x, n1, n2) => format!("LD V{:X}, 0x{:0>2X}", x, big_endian_4_2(n1, n2)),
+        Instruction(7, x, n1, n2) => format!("ADD V{:X}, 0x{:0>2X}", x, big_endian_4_2(n1, n2)),
+        Instruction(8, x, y, 0) => format!("LD V{:X}, V{:X}", x, y),
+        Instruction(8, x, y, 1) => format!("OR V{:X}, V{:X}", x, y),
+        Instruction(8, x, y, 2) => format!("AND V{:X}, V{:X}", x, y),
+        Instruction(8, x, y, 3) => format!("XOR V{:X}, V{:X}", x, y),
+        Instruction(8, x, y, 4) => format!("ADD V{:X}, V{:X}", x, y),
+        Instruction(8, x, y, 5) => format!("SUB V{:X}, V{:X}", x, y),
+        Instruction(8, x, y, 6) => format!("SHR V{:X}, V{:X}", x, y),
+        Instruction(8, x, y, 7) => format!("SUBN V{:X}, V{:X}", x, y),
+        Instruction(8, x, y, 0xE) => format!("SHL V{:X}, V{:X}", x, y),
+        Instruction(9, x, y, 0) => format!("SNE V{:X}, V{:X}", x, y),
+        Instruction(0xA, n1, n2, n3) => format!("LD I, 0x{:0>3X}", big_endian_4_3(n1, n2, n3)),
+        Instruction(0xB, n1, n2, n3) => format!("JP V0, 0x{:0>3X}", big_endian_4_3(n1, n2, n3)),
+        Instruction(0xC, x, n1, n2) => format!("RND V{:X}, 0x{:0>2X}", x, big_endian_4_2(n1, n2)),
+        Instruction(0xD, x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        Instruction(0xE, x, 0x9, 0xE) => format!("SKP V{:X}", x),
+        Instruction(0xE, x, 0xA, 0x1) => format!("SKNP V{:X}", x),
+        Instruction(0xF, 0, 0, 0) => "LD I, LONG".to_string(),
+        Instruction(0xF, n, 0, 1) => format!("PLANE {}", n),
+        Instruction(0xF, 0, 0, 2) => "AUDIO".to_string(),
+        Instruction(0xF, x, 0x0, 0x7) => format!("LD V{:X}, DT", x),
+        Instruction(0xF, x, 0x0, 0xA) => format!("LD V{:X}, K", x),
+        Instruction(0xF, x, 0x1, 0x5) => format!("LD DT, V{:X}", x),
+        Instruction(0xF, x, 0x1, 0x8) => format!("LD ST, V{:X}", x),
+        Instruction(0xF, x, 0x1, 0xE) => format!("ADD I, V{:X}", x),
+        Instruction(0xF, x, 0x2, 0x9) => format!("LD F, V{:X}", x),
+        Instruction(0xF, x, 0x3, 0x0) => format!("LD HF, V{:X}", x),
+        Instruction(0xF, x, 0x3, 0xA) => format!("LD PITCH, V{:X}", x),
+        Instruction(0xF, x, 0x3, 0x3) => format!("LD B, V{:X}", x),
+        Instruction(0xF, x, 0x5, 0x5) => format!("LD [I], V{:X}", x),
+        Instruction(0xF, x, 0x6, 0x5) => format!("LD V{:X}, [I]", x),
+        Instruction(0xF, x, 0x7, 0x5) => format!("LD R, V{:X}", x),
+        Instruction(0xF, x, 0x8, 0x5) => format!("LD V{:X}, R", x),
+        _ => return None,
+    };
+    Some(text)
+}
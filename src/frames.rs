@@ -0,0 +1,175 @@
+//! Contains the `Frames` iterator, which runs a `System` one 60 Hz frame at a time and yields a `Frame` snapshot of the display buffer after each
+//! of them, so tools and tests can process a run with iterator adapters, e.g. `sys.frames().take(600).map(hash).collect()`.
+
+use crate::system::System;
+
+/// A snapshot of the display buffer, as yielded by `System::frames()`.
+///
+/// Frames can be compared and hashed, and are formatted as one line per row with `#` for pixels that are on and `.` for pixels that are off.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Frame {
+    width: u8,
+    height: u8,
+    bytes: Vec<u8>,
+}
+
+impl Frame {
+
+    /// Captures the current display buffer of the given system, with the display planes of the XO-CHIP composited.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::frames::Frame;
+    /// # use chip8::system::System;
+    /// # let sys = System::new();
+    /// let frame = Frame::capture(&sys);
+    /// assert_eq!(frame.width(), 64);
+    /// ```
+    pub fn capture(sys: &System) -> Frame {
+        let layout = sys.memory.display_layout();
+        let bytes = (0..layout.len()).map(|offset| sys.memory.composite_byte(offset)).collect();
+        Frame { width: layout.width, height: layout.height, bytes }
+    }
+
+    /// Gets the width of the frame in pixels.
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    /// Gets the height of the frame in pixels.
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    /// Gets the raw bytes of the display buffer, with 8 pixels per byte and the most significant bit being the leftmost pixel.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Checks whether the pixel at the given position is on. Positions outside of the frame are off.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let lit = (0..64).filter(|x| frame.pixel(*x, 0)).count();
+    /// ```
+    pub fn pixel(&self, x: u8, y: u8) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        let byte = self.bytes[y as usize * (self.width as usize / 8) + x as usize / 8];
+        byte & (0x80 >> (x % 8)) != 0
+    }
+
+    /// Gets the SHA-1 hash of the frame's size and pixels as a lowercase hexadecimal string, e.g. to compare a frame to a stored screenshot
+    /// without keeping the screenshot itself.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::frames::Frame;
+    /// # use chip8::system::System;
+    /// # let sys = System::new();
+    /// assert_eq!(Frame::capture(&sys).digest(), Frame::capture(&sys).digest());
+    /// ```
+    pub fn digest(&self) -> String {
+        let mut sha1 = sha1_smol::Sha1::from([self.width, self.height]);
+        sha1.update(&self.bytes);
+        sha1.digest().to_string()
+    }
+
+    /// Counts the pixels that differ between two frames. Pixels outside of the smaller frame count as off.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::frames::Frame;
+    /// # use chip8::system::System;
+    /// # let frame = Frame::capture(&System::new());
+    /// assert_eq!(frame.differing_pixels(&frame), 0);
+    /// ```
+    pub fn differing_pixels(&self, other: &Frame) -> usize {
+        let (width, height) = (self.width.max(other.width), self.height.max(other.height));
+        (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).filter(|(x, y)| self.pixel(*x, *y) != other.pixel(*x, *y)).count()
+    }
+}
+
+impl std::str::FromStr for Frame {
+    type Err = String;
+
+    /// Parses a frame from the text format produced by its `Display` implementation: one line per row, with `#` for pixels that are on and `.`
+    /// for pixels that are off. All rows must have the same width, a multiple of 8.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let frame: Frame = fs::read_to_string("test/expected/font.txt")?.parse()?;
+    /// ```
+    fn from_str(s: &str) -> Result<Frame, String> {
+        let rows: Vec<&str> = s.lines().map(|line| line.trim_end()).filter(|line| !line.is_empty()).collect();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        if width == 0 || !width.is_multiple_of(8) || width > u8::MAX as usize || rows.len() > 64 {
+            return Err(format!("a frame of {}x{} pixels is not supported", width, rows.len()));
+        }
+        let mut bytes = Vec::with_capacity(width / 8 * rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                return Err(format!("row {} has {} pixels instead of {}", y, row.chars().count(), width));
+            }
+            let pixels = row.chars().map(|c| match c {
+                '#' => Ok(true),
+                '.' => Ok(false),
+                _ => Err(format!("unexpected character '{}' in row {}", c, y)),
+            }).collect::<Result<Vec<bool>, String>>()?;
+            bytes.extend(pixels.chunks(8).map(|chunk| chunk.iter().fold(0u8, |byte, on| byte << 1 | *on as u8)));
+        }
+        Ok(Frame { width: width as u8, height: rows.len() as u8, bytes })
+    }
+}
+
+impl std::fmt::Display for Frame {
+
+    /// Formats the frame as one line per row, with `#` for pixels that are on and `.` for pixels that are off.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for y in 0..self.height {
+            let row: String = (0..self.width).map(|x| if self.pixel(x, y) { '#' } else { '.' }).collect();
+            writeln!(f, "{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+/// An iterator running a `System` frame by frame, created by `System::frames()`.
+///
+/// Each call of `next()` executes the instructions of one 60 Hz frame (according to the `loop_frequency`, as fast as possible), ticks the timers for
+/// the emulated time that has passed and returns a snapshot of the display. When the program halts, the iterator yields the display at that point
+/// once more and then ends.
+pub struct Frames<'a> {
+    sys: &'a mut System,
+    owed_cycles: u32,
+    halted: bool,
+}
+
+impl Frames<'_> {
+
+    /// Creates an iterator running the given system.
+    pub fn new(sys: &mut System) -> Frames<'_> {
+        Frames { sys, owed_cycles: 0, halted: false }
+    }
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        if self.halted {
+            return None;
+        }
+        self.owed_cycles += self.sys.loop_frequency() as u32;
+        for _ in 0..self.owed_cycles / 60 {
+            if !self.sys.step() {
+                self.halted = true;
+                break;
+            }
+            self.sys.sync_timers();
+        }
+        self.owed_cycles %= 60;
+        Some(Frame::capture(self.sys))
+    }
+}
@@ -0,0 +1,149 @@
+//! Contains the `Environment` struct, a Gym-style wrapper around a `System` that allows agents (e.g. reinforcement learning models) to play CHIP-8
+//! games headlessly.
+//!
+//! The environment advances in frames: each call to `step(...)` applies the agent's key presses, executes the instructions of one 60 Hz frame as fast
+//! as possible and ticks the timers once. No display is rendered and no time is spent sleeping.
+
+use crate::program::Program;
+use crate::system::{LoadError, System, DEFAULT_LOOP_FREQUENCY};
+
+/// The number of instructions executed per frame by default, matching the default `loop_frequency` of 700 Hz at 60 frames per second.
+const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = DEFAULT_LOOP_FREQUENCY as u32 / 60;
+
+/// The state of the environment after a step, as returned by `Environment::step(...)` and `Environment::reset()`.
+pub struct Observation {
+    /// The state of each pixel of the display, indexed as `pixels[y][x]`.
+    pub pixels: [[bool; 64]; 32],
+    /// The values of the memory addresses selected with `Environment::with_memory_peeks(...)`, in the same order. Games usually keep their score or
+    /// number of lives at a fixed address, which can be used to compute a reward.
+    pub memory: Vec<u8>,
+    /// Whether the sound timer is active, which games often use to signal events such as collisions.
+    pub sound: bool,
+    /// Whether the program has halted. Once this is `true`, the environment has to be reset.
+    pub done: bool,
+}
+
+/// A headless CHIP-8 game environment with a Gym-style `reset()`/`step(...)` interface.
+///
+/// # Example
+/// ```ignore
+/// let mut env = Environment::new(Program::load("pong.ch8")?)?.with_memory_peeks(vec![0x2F0]);
+/// let mut observation = env.reset();
+/// while !observation.done {
+///     let mut actions = [false; 16];
+///     actions[0x1] = agent.decide(&observation);
+///     observation = env.step(actions);
+/// }
+/// ```
+pub struct Environment {
+    system: System,
+    instructions_per_frame: u32,
+    peeks: Vec<u16>,
+    done: bool,
+}
+
+impl Environment {
+
+    /// Creates a new environment running the given program with the default quirks.
+    ///
+    /// # Errors
+    /// Returns a `LoadError` if the program cannot be loaded, see `System::load_at(...)`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let env = Environment::new(Program::load("pong.ch8")?)?;
+    /// ```
+    pub fn new(program: Program) -> Result<Environment, LoadError> {
+        let mut system = System::new();
+        system.load(program)?;
+        Ok(Environment { system, instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME, peeks: Vec::new(), done: false })
+    }
+
+    /// Selects the memory addresses whose values are included in each `Observation`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let env = Environment::new(program)?.with_memory_peeks(vec![0x2F0, 0x2F1]);
+    /// ```
+    pub fn with_memory_peeks(mut self, addresses: Vec<u16>) -> Environment {
+        self.peeks = addresses;
+        self
+    }
+
+    /// Sets the number of instructions executed per frame, i.e. per call of `step(...)`. A value of `0` is treated as `1`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let env = Environment::new(program)?.with_instructions_per_frame(20);
+    /// ```
+    pub fn with_instructions_per_frame(mut self, instructions: u32) -> Environment {
+        self.instructions_per_frame = instructions.max(1);
+        self
+    }
+
+    /// Gives access to the underlying system, e.g. to change its quirks.
+    ///
+    /// # Example
+    /// ```ignore
+    /// env.system().quirks.vf_reset = true;
+    /// ```
+    pub fn system(&mut self) -> &mut System {
+        &mut self.system
+    }
+
+    /// Restarts the program from the beginning and returns the initial observation.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let observation = env.reset();
+    /// ```
+    pub fn reset(&mut self) -> Observation {
+        self.system.reset();
+        self.done = false;
+        self.observe()
+    }
+
+    /// Advances the environment by one frame: sets the state of the 16 keys to `actions`, executes the instructions of one frame and ticks the
+    /// timers once.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut actions = [false; 16];
+    /// actions[0x5] = true;
+    /// let observation = env.step(actions);
+    /// ```
+    pub fn step(&mut self, actions: [bool; 16]) -> Observation {
+        if !self.done {
+            let cycle = self.system.cycles();
+            for (key, pressed) in actions.iter().enumerate() {
+                self.system.keyboard.set(key as u8, *pressed, cycle);
+            }
+            for _ in 0..self.instructions_per_frame {
+                if !self.system.step() {
+                    self.done = true;
+                    break;
+                }
+            }
+            self.system.tick_timers();
+            self.system.memory.clear_dirty_rows();
+        }
+        self.observe()
+    }
+
+    /// Creates an observation of the current state.
+    fn observe(&self) -> Observation {
+        let layout = self.system.memory.display_layout();
+        let mut pixels = [[false; 64]; 32];
+        for (y, row) in pixels.iter_mut().enumerate().take(layout.height as usize) {
+            for (x, pixel) in row.iter_mut().enumerate().take(layout.width as usize) {
+                *pixel = self.system.memory.pixel(x as u8, y as u8);
+            }
+        }
+        Observation {
+            pixels,
+            memory: self.peeks.iter().map(|address| self.system.memory.get(*address)).collect(),
+            sound: self.system.sound_timer.get() > 0,
+            done: self.done,
+        }
+    }
+}
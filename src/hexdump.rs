@@ -0,0 +1,54 @@
+//! Contains the `HexDump` struct, a canonical hex+ASCII view of a block of bytes, used for both programs and memory.
+
+/// The number of bytes shown per row.
+const BYTES_PER_ROW: usize = 16;
+
+/// A view of a block of bytes that is formatted in the style of `xxd`: each row shows the address of its first byte, 16 bytes in groups of two and an
+/// ASCII gutter, in which non-printable bytes are shown as `.`.
+///
+/// # Example
+/// ```
+/// # use chip8::hexdump::HexDump;
+/// let dump = HexDump::new(b"\x00\xE0HELLO", 0x200);
+/// assert_eq!(dump.to_string(), "0200: 00e0 4845 4c4c 4f                        ..HELLO\n");
+/// ```
+pub struct HexDump<'a> {
+    bytes: &'a [u8],
+    start: u16,
+}
+
+impl HexDump<'_> {
+
+    /// Creates a view of the given bytes, where the first byte is located at address `start`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let memory = [0u8; 32];
+    /// println!("{}", HexDump::new(&memory, 0));
+    /// ```
+    pub fn new(bytes: &[u8], start: u16) -> HexDump<'_> {
+        HexDump { bytes, start }
+    }
+}
+
+impl std::fmt::Display for HexDump<'_> {
+
+    /// Formats the bytes as rows of the form `<address>: <hex bytes>  <ASCII>`. The last row is padded, so the ASCII gutters of all rows are aligned.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, row) in self.bytes.chunks(BYTES_PER_ROW).enumerate() {
+            write!(f, "{:0>4X}:", self.start as usize + i * BYTES_PER_ROW)?;
+            for column in 0..BYTES_PER_ROW {
+                if column.is_multiple_of(2) {
+                    write!(f, " ")?;
+                }
+                match row.get(column) {
+                    Some(byte) => write!(f, "{:0>2x}", byte)?,
+                    None => write!(f, "  ")?,
+                }
+            }
+            let ascii: String = row.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+            writeln!(f, "  {}", ascii)?;
+        }
+        write!(f, "")
+    }
+}
@@ -0,0 +1,156 @@
+//! Contains the `HexEditor` struct, the model of an editable memory pane: a cursor that is moved with the arrow keys and hex digits that overwrite
+//! the byte under the cursor, with edits going straight to the `Memory` of a live `System`.
+//!
+//! The `Debugger` shows it in place of the memory view of its monitor while the memory is edited, see `Debugger::toggle_editor(...)`.
+
+use crate::system::Memory;
+
+/// The number of bytes shown per row.
+const BYTES_PER_ROW: u16 = 16;
+
+/// A key press handled by the `HexEditor`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EditorKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    /// A hexadecimal digit (`0x0`-`0xF`) typed at the cursor.
+    Digit(u8),
+}
+
+impl EditorKey {
+
+    /// Maps a typed character to the corresponding `EditorKey::Digit`, or returns `None` if it is not a hexadecimal digit.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::hexedit::EditorKey;
+    /// assert_eq!(EditorKey::from_char('a'), Some(EditorKey::Digit(0xA)));
+    /// ```
+    pub fn from_char(c: char) -> Option<EditorKey> {
+        c.to_digit(16).map(|digit| EditorKey::Digit(digit as u8))
+    }
+}
+
+/// An editable view of `rows` rows of 16 bytes of memory.
+///
+/// Typing a digit overwrites the high nibble of the byte under the cursor, then its low nibble, after which the cursor moves to the next byte. Moving
+/// the cursor always starts again at the high nibble. The view scrolls to keep the cursor visible.
+///
+/// # Example
+/// ```
+/// # use chip8::hexedit::{EditorKey, HexEditor};
+/// # use chip8::system::System;
+/// # let mut sys = System::new();
+/// let mut editor = HexEditor::new(8);
+/// editor.goto(0x200, &sys.memory);
+/// editor.handle_key(EditorKey::Digit(0x1), &mut sys.memory);
+/// editor.handle_key(EditorKey::Digit(0x2), &mut sys.memory);
+/// assert_eq!(sys.memory.get(0x200), 0x12);
+/// print!("{}", editor.render(&sys.memory));
+/// ```
+#[derive(Clone, Debug)]
+pub struct HexEditor {
+    cursor: u16,
+    low_nibble: bool,
+    top: u16,
+    rows: u16,
+}
+
+impl HexEditor {
+
+    /// Creates a new editor showing the given number of rows (at least one), with the cursor at address `0`.
+    pub fn new(rows: u16) -> HexEditor {
+        HexEditor { cursor: 0, low_nibble: false, top: 0, rows: rows.max(1) }
+    }
+
+    /// Gets the address of the byte under the cursor.
+    pub fn cursor(&self) -> u16 {
+        self.cursor
+    }
+
+    /// Moves the cursor to the given address, which is clamped to the size of the memory.
+    ///
+    /// # Example
+    /// ```ignore
+    /// editor.goto(sys.registers.i(), &sys.memory);
+    /// ```
+    pub fn goto(&mut self, address: u16, memory: &Memory) {
+        self.cursor = (address as usize).min(memory.size() - 1) as u16;
+        self.low_nibble = false;
+        self.scroll();
+    }
+
+    /// Handles a key press, moving the cursor or editing the memory. Returns `true` if the memory was changed.
+    ///
+    /// Edits are written with `Memory::edit(...)`, so changes to the display buffer are shown by the next display update.
+    pub fn handle_key(&mut self, key: EditorKey, memory: &mut Memory) -> bool {
+        let page = self.rows * BYTES_PER_ROW;
+        match key {
+            EditorKey::Up => self.goto(self.cursor.saturating_sub(BYTES_PER_ROW), memory),
+            EditorKey::Down => self.goto(self.cursor.saturating_add(BYTES_PER_ROW), memory),
+            EditorKey::Left => self.goto(self.cursor.saturating_sub(1), memory),
+            EditorKey::Right => self.goto(self.cursor.saturating_add(1), memory),
+            EditorKey::PageUp => self.goto(self.cursor.saturating_sub(page), memory),
+            EditorKey::PageDown => self.goto(self.cursor.saturating_add(page), memory),
+            EditorKey::Digit(digit) => {
+                let digit = digit & 0xF;
+                let value = memory.get(self.cursor);
+                if self.low_nibble {
+                    memory.edit(self.cursor, (value & 0xF0) | digit);
+                    self.goto(self.cursor.saturating_add(1), memory);
+                }
+                else {
+                    memory.edit(self.cursor, (value & 0x0F) | digit << 4);
+                    self.low_nibble = true;
+                }
+                return true;
+            },
+        }
+        false
+    }
+
+    /// Renders the visible rows, each consisting of the address of its first byte and 16 bytes in hex. The nibble under the cursor is highlighted
+    /// with reverse video.
+    pub fn render(&self, memory: &Memory) -> String {
+        let mut output = String::new();
+        for row in 0..self.rows as usize {
+            let start = self.top as usize + row * BYTES_PER_ROW as usize;
+            if start >= memory.size() {
+                break;
+            }
+            output += &format!("{:0>4X}:", start);
+            for address in (start..start + BYTES_PER_ROW as usize).map(|address| address as u16) {
+                let hex = format!("{:0>2X}", memory.get(address));
+                if address == self.cursor {
+                    let (high, low) = hex.split_at(1);
+                    if self.low_nibble {
+                        output += &format!(" {}{}[7m{}{}[0m", high, 27 as char, low, 27 as char);
+                    }
+                    else {
+                        output += &format!(" {}[7m{}{}[0m{}", 27 as char, high, 27 as char, low);
+                    }
+                }
+                else {
+                    output += &format!(" {}", hex);
+                }
+            }
+            output += "\n";
+        }
+        output
+    }
+
+    /// Scrolls the view so the row of the cursor is visible.
+    fn scroll(&mut self) {
+        let row_start = self.cursor - self.cursor % BYTES_PER_ROW;
+        if row_start < self.top {
+            self.top = row_start;
+        }
+        else if row_start as u32 >= self.top as u32 + self.rows as u32 * BYTES_PER_ROW as u32 {
+            self.top = row_start - (self.rows - 1) * BYTES_PER_ROW;
+        }
+    }
+}
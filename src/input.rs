@@ -0,0 +1,329 @@
+//! Contains the `InputSource` trait, through which `System::run_with(...)` receives key presses and control events, and its implementations.
+
+#[cfg(all(windows, feature = "win32"))]
+extern crate user32;
+use std::sync::mpsc::{self, Receiver, Sender};
+#[cfg(not(target_family = "wasm"))]
+use std::thread;
+#[cfg(not(target_family = "wasm"))]
+use std::time::Duration;
+#[cfg(all(not(target_family = "wasm"), not(all(windows, feature = "win32"))))]
+use std::time::Instant;
+#[cfg(all(not(target_family = "wasm"), not(all(windows, feature = "win32"))))]
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crate::system::DisplayOption;
+
+/// An input event consumed by the fetch/decode/execute loop of `System::run_with(...)`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InputEvent {
+    /// The CHIP-8 key with the given index was pressed or released.
+    Key { key: u8, pressed: bool },
+    /// The emulation speed should be increased.
+    Faster,
+    /// The emulation speed should be decreased.
+    Slower,
+    /// The next program of the playlist should be loaded.
+    NextProgram,
+    /// The visible part of the display should be moved in the given directions (`-1`, `0` or `1`), if only a part of it is shown.
+    Pan { dx: i8, dy: i8 },
+    /// One of the rendering options of the display should be toggled.
+    ToggleDisplayOption(DisplayOption),
+    /// The `Debugger` should pause the execution, or continue it if it is paused.
+    TogglePause,
+    /// The `Debugger` should add a breakpoint at the current PC, or remove it if there is one.
+    ToggleBreakpoint,
+    /// The `Debugger` should execute a single instruction, or pause the execution if it is running.
+    Step,
+    /// The live monitor of the `Debugger` should be shown next to the display, or hidden if it is shown.
+    ToggleMonitor,
+    /// The memory view of the monitor should be scrolled by the given number of rows (negative values scroll up). Pages through the memory while
+    /// it is edited.
+    ScrollMonitor(i8),
+    /// The memory view of the monitor should be turned into a `HexEditor`, or back into a hex dump if the memory is edited.
+    ToggleEditor,
+    /// The hexadecimal digit (`0x0`-`0xF`) was typed. It is sent in addition to the `Key` event of the CHIP-8 key, if there is one, and only
+    /// used while the memory is edited, instead of the `Key` event.
+    HexDigit(u8),
+    /// The state of the machine should be saved to its state file.
+    SaveState,
+    /// The state of the machine should be restored from its state file.
+    LoadState,
+    /// The program should be restarted from the state right after it was loaded, see `System::reset()`.
+    Reset,
+    /// The program should be loaded from its file again and restarted, e.g. after it was rebuilt.
+    Reload,
+    /// The emulation should stop, e.g. because a remote client disconnected.
+    Quit,
+}
+
+/// A source of `InputEvent`s, such as the local keyboard or a network connection.
+pub trait InputSource {
+
+    /// Returns the next pending event without blocking, or `None` if there is none.
+    fn poll(&mut self) -> Option<InputEvent>;
+}
+
+/// An `InputSource` that receives its events through a channel, so they can be produced by any other thread.
+///
+/// # Example
+/// ```
+/// # use chip8::input::{ChannelInput, InputEvent, InputSource};
+/// let (tx, mut input) = ChannelInput::new();
+/// tx.send(InputEvent::Key { key: 0x5, pressed: true })?;
+/// assert_eq!(input.poll(), Some(InputEvent::Key { key: 0x5, pressed: true }));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct ChannelInput {
+    events: Receiver<InputEvent>,
+    sender: Sender<InputEvent>,
+}
+
+impl ChannelInput {
+
+    /// Creates a new `ChannelInput` together with the `Sender` through which events can be sent to it.
+    pub fn new() -> (Sender<InputEvent>, ChannelInput) {
+        let (tx, rx) = mpsc::channel();
+        (tx.clone(), ChannelInput { events: rx, sender: tx })
+    }
+
+    /// Creates another `Sender` for this input, e.g. to merge events from several sources such as the local keyboard and a network connection.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let input = keyboard();
+    /// let tx = input.sender();
+    /// ```
+    pub fn sender(&self) -> Sender<InputEvent> {
+        self.sender.clone()
+    }
+}
+
+impl InputSource for ChannelInput {
+
+    fn poll(&mut self) -> Option<InputEvent> {
+        self.events.try_recv().ok()
+    }
+}
+
+/// Creates an `InputSource` for the local keyboard, read by a worker thread.
+///
+/// The keys listed in `KEYBOARD_KEYS` are mapped to the CHIP-8 keys, and the `+`/`-`, `F2`-`F10`, arrow, `P` (pause), `Backspace` (reset), `L`
+/// (reload), `M` (monitor), `PageUp`/`PageDown` and `Tab` (edit memory) hotkeys to the corresponding control events. Typed hexadecimal digits
+/// are additionally sent as `InputEvent::HexDigit`.
+///
+/// By default, key events are read from the terminal with `crossterm`, which works on Linux, macOS and Windows. Keys are only received one by one
+/// while the terminal is in raw mode (see `TerminalGuard`), and `Ctrl-C` then produces `InputEvent::Quit`. Terminals that report key releases (see
+/// `TerminalGuard::enter()`) release CHIP-8 keys exactly, on all others a key counts as held until `KEY_HOLD` has passed without a repeat of it.
+///
+/// With the `win32` feature on Windows, the keyboard is instead polled with `user32::GetAsyncKeyState(...)`, which also sees keys pressed while the
+/// terminal is not focused. On WebAssembly (WASI or the browser), the returned input never produces events by itself, but events can still be sent to it through
+/// `ChannelInput::sender()`.
+///
+/// # Example
+/// ```ignore
+/// let mut input = keyboard();
+/// sys.run_with(&mut display, &mut input);
+/// ```
+pub fn keyboard() -> ChannelInput {
+    let (tx, input) = ChannelInput::new();
+    #[cfg(all(windows, feature = "win32"))]
+    thread::spawn(move || {
+        do_keyboard_check(tx);
+    });
+    #[cfg(all(not(target_family = "wasm"), not(all(windows, feature = "win32"))))]
+    thread::spawn(move || {
+        if let Err(e) = do_terminal_check(tx) {
+            log::warn!("Could not read keyboard input from the terminal: {}", e);
+        }
+    });
+    #[cfg(target_family = "wasm")]
+    {
+        log::warn!("Keyboard input is not supported on WebAssembly");
+        drop(tx);
+    }
+    input
+}
+
+/// Maps 16 QWERTY keyboard keys to the corresponding CHIP-8 key index they should represent.
+pub const KEYBOARD_KEYS: [u8; 16] = [
+    b'X',
+    b'1',
+    b'2',
+    b'3',
+    b'Q',
+    b'W',
+    b'E',
+    b'A',
+    b'S',
+    b'D',
+    b'Z',
+    b'C',
+    b'4',
+    b'R',
+    b'F',
+    b'V',
+];
+
+/// Maps the hotkeys that control the emulator (rather than the emulated program) to the events they trigger. Both the main keyboard and the numpad
+/// keys are supported, given as Windows virtual-key codes.
+#[cfg(all(windows, feature = "win32"))]
+const HOTKEYS: [(i32, InputEvent); 40] = [
+    (0xBB, InputEvent::Faster),
+    (0x6B, InputEvent::Faster),
+    (0xBD, InputEvent::Slower),
+    (0x6D, InputEvent::Slower),
+    (0x71, InputEvent::NextProgram),
+    (0x72, InputEvent::SaveState),
+    (0x73, InputEvent::LoadState),
+    (0x25, InputEvent::Pan { dx: -1, dy: 0 }),
+    (0x26, InputEvent::Pan { dx: 0, dy: -1 }),
+    (0x27, InputEvent::Pan { dx: 1, dy: 0 }),
+    (0x28, InputEvent::Pan { dx: 0, dy: 1 }),
+    (0x74, InputEvent::ToggleDisplayOption(DisplayOption::Invert)),
+    (0x75, InputEvent::ToggleDisplayOption(DisplayOption::HighContrast)),
+    (0x76, InputEvent::ToggleDisplayOption(DisplayOption::Decay)),
+    (0x77, InputEvent::TogglePause),
+    (0x78, InputEvent::ToggleBreakpoint),
+    (0x79, InputEvent::Step),
+    (0x50, InputEvent::TogglePause),
+    (0x08, InputEvent::Reset),
+    (0x4C, InputEvent::Reload),
+    (0x4D, InputEvent::ToggleMonitor),
+    (0x21, InputEvent::ScrollMonitor(-1)),
+    (0x22, InputEvent::ScrollMonitor(1)),
+    (0x09, InputEvent::ToggleEditor),
+    (0x30, InputEvent::HexDigit(0x0)),
+    (0x31, InputEvent::HexDigit(0x1)),
+    (0x32, InputEvent::HexDigit(0x2)),
+    (0x33, InputEvent::HexDigit(0x3)),
+    (0x34, InputEvent::HexDigit(0x4)),
+    (0x35, InputEvent::HexDigit(0x5)),
+    (0x36, InputEvent::HexDigit(0x6)),
+    (0x37, InputEvent::HexDigit(0x7)),
+    (0x38, InputEvent::HexDigit(0x8)),
+    (0x39, InputEvent::HexDigit(0x9)),
+    (0x41, InputEvent::HexDigit(0xA)),
+    (0x42, InputEvent::HexDigit(0xB)),
+    (0x43, InputEvent::HexDigit(0xC)),
+    (0x44, InputEvent::HexDigit(0xD)),
+    (0x45, InputEvent::HexDigit(0xE)),
+    (0x46, InputEvent::HexDigit(0xF)),
+];
+
+/// A worker thread responsible for polling the keyboard state repeatedly, sending updates to the main thread.
+#[cfg(all(windows, feature = "win32"))]
+fn do_keyboard_check(tx: Sender<InputEvent>) {
+    let mut states = [0;16];
+    let mut hotkey_states = [false; HOTKEYS.len()];
+
+    'outer: loop {
+        thread::sleep(Duration::from_millis(10));
+        for (idx, (key, event)) in HOTKEYS.iter().enumerate() {
+            let state = unsafe { user32::GetAsyncKeyState(*key) } < 0;
+            if state && !hotkey_states[idx] && tx.send(*event).is_err() {
+                break 'outer;
+            }
+            hotkey_states[idx] = state;
+        }
+        for (idx, key) in KEYBOARD_KEYS.iter().enumerate() {
+            let state = unsafe { user32::GetAsyncKeyState(*key as i32) } == -32767;
+
+            if state {
+                if states[idx] == 0 && tx.send(InputEvent::Key { key: idx as u8, pressed: true }).is_err() {
+                    break 'outer;
+                }
+                states[idx] = 50;
+            } else if states[idx] > 0 {
+                states[idx] -= 1;
+                if states[idx] == 0 && tx.send(InputEvent::Key { key: idx as u8, pressed: false }).is_err() {
+                    break 'outer;
+                }
+            }
+        }
+    }
+}
+
+/// The time for which a CHIP-8 key counts as held after it was pressed or repeated, if the terminal does not report key releases. Terminals start
+/// repeating a held key after about half a second, so this keeps the key held until the repeats arrive.
+#[cfg(all(not(target_family = "wasm"), not(all(windows, feature = "win32"))))]
+const KEY_HOLD: Duration = Duration::from_millis(600);
+
+/// Maps a terminal key to the control event it triggers, if it is one of the hotkeys.
+#[cfg(all(not(target_family = "wasm"), not(all(windows, feature = "win32"))))]
+fn hotkey(code: KeyCode) -> Option<InputEvent> {
+    match code {
+        KeyCode::Char('+') => Some(InputEvent::Faster),
+        KeyCode::Char('-') => Some(InputEvent::Slower),
+        KeyCode::F(2) => Some(InputEvent::NextProgram),
+        KeyCode::F(3) => Some(InputEvent::SaveState),
+        KeyCode::F(4) => Some(InputEvent::LoadState),
+        KeyCode::Left => Some(InputEvent::Pan { dx: -1, dy: 0 }),
+        KeyCode::Up => Some(InputEvent::Pan { dx: 0, dy: -1 }),
+        KeyCode::Right => Some(InputEvent::Pan { dx: 1, dy: 0 }),
+        KeyCode::Down => Some(InputEvent::Pan { dx: 0, dy: 1 }),
+        KeyCode::F(5) => Some(InputEvent::ToggleDisplayOption(DisplayOption::Invert)),
+        KeyCode::F(6) => Some(InputEvent::ToggleDisplayOption(DisplayOption::HighContrast)),
+        KeyCode::F(7) => Some(InputEvent::ToggleDisplayOption(DisplayOption::Decay)),
+        KeyCode::F(8) => Some(InputEvent::TogglePause),
+        KeyCode::F(9) => Some(InputEvent::ToggleBreakpoint),
+        KeyCode::F(10) => Some(InputEvent::Step),
+        KeyCode::Char('p') | KeyCode::Char('P') => Some(InputEvent::TogglePause),
+        KeyCode::Backspace => Some(InputEvent::Reset),
+        KeyCode::Char('l') | KeyCode::Char('L') => Some(InputEvent::Reload),
+        KeyCode::Char('m') | KeyCode::Char('M') => Some(InputEvent::ToggleMonitor),
+        KeyCode::PageUp => Some(InputEvent::ScrollMonitor(-1)),
+        KeyCode::PageDown => Some(InputEvent::ScrollMonitor(1)),
+        KeyCode::Tab => Some(InputEvent::ToggleEditor),
+        _ => None,
+    }
+}
+
+/// A worker thread responsible for reading key events from the terminal, sending updates to the main thread. Returns when the receiver was dropped,
+/// `Ctrl-C` was pressed, or the terminal cannot be read.
+#[cfg(all(not(target_family = "wasm"), not(all(windows, feature = "win32"))))]
+fn do_terminal_check(tx: Sender<InputEvent>) -> std::io::Result<()> {
+    let mut held: [Option<Instant>; 16] = [None; 16];
+
+    loop {
+        if event::poll(Duration::from_millis(10))? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                let _res = tx.send(InputEvent::Quit);
+                return Ok(());
+            }
+            if let Some(event) = hotkey(key.code) {
+                if key.kind == KeyEventKind::Press && tx.send(event).is_err() {
+                    return Ok(());
+                }
+                continue;
+            }
+            let KeyCode::Char(c) = key.code else {
+                continue;
+            };
+            if let Some(digit) = c.to_digit(16).filter(|_| key.kind == KeyEventKind::Press) {
+                if tx.send(InputEvent::HexDigit(digit as u8)).is_err() {
+                    return Ok(());
+                }
+            }
+            let Some(idx) = KEYBOARD_KEYS.iter().position(|k| *k as char == c.to_ascii_uppercase()) else {
+                continue;
+            };
+            let pressed = key.kind != KeyEventKind::Release;
+            let event = InputEvent::Key { key: idx as u8, pressed };
+            if held[idx].is_some() != pressed && tx.send(event).is_err() {
+                return Ok(());
+            }
+            held[idx] = pressed.then(Instant::now);
+        }
+        for (idx, since) in held.iter_mut().enumerate() {
+            if since.is_some_and(|since| since.elapsed() >= KEY_HOLD) && !crate::terminal::reports_key_releases() {
+                *since = None;
+                if tx.send(InputEvent::Key { key: idx as u8, pressed: false }).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! A simple implementation of a CHIP-8 emulator
+//! 
+//! I mainly used this to get started with rust. The emulator can be embedded into other programs: a `System` loads a `Program` and is driven one
+//! instruction at a time with `System::step()`, without any terminal I/O, while `System::framebuffer()` gives access to the pixels of the display.
+//! The `chip8` binary is a terminal frontend built on top of this library. With the `wasm` feature, the `wasm` module exposes it to JavaScript, so
+//! it can run on a canvas in a web page.
+//! 
+//! # Example
+//! ```ignore
+//! let mut sys = System::new();
+//! sys.load(Program::from_hex_str("00E0 A22A 600C 6108 D01F 1200")?)?;
+//! while sys.cycles() < 100 && sys.step() {}
+//! println!("{}", sys.framebuffer());
+//! ```
+
+pub mod utils;
+pub mod system;
+pub mod input;
+pub mod program;
+pub mod disasm;
+pub mod hexdump;
+pub mod asm;
+pub mod quirks;
+pub mod patch;
+pub mod octo;
+pub mod archive;
+pub mod builder;
+pub mod scheduler;
+pub mod trace;
+pub mod stats;
+pub mod gym;
+pub mod batch;
+pub mod server;
+pub mod commands;
+pub mod renderer;
+pub mod patterns;
+pub mod hexedit;
+pub mod compare;
+pub mod persistence;
+pub mod crash;
+pub mod frames;
+pub mod selftest;
+pub mod conformance;
+pub mod config;
+pub mod audio;
+pub mod debugger;
+pub mod savestate;
+pub mod replay;
+pub mod romdb;
+pub mod logging;
+pub mod terminal;
+#[cfg(feature = "watch")]
+pub mod watch;
+#[cfg(feature = "window")]
+pub mod window;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use config::Config;
+pub use frames::Frame;
+pub use program::{Instruction, Program};
+pub use quirks::Quirks;
+pub use system::{Memory, System, Variant};
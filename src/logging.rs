@@ -0,0 +1,90 @@
+//! Routes diagnostics emitted through the `log` facade to stderr or to a log file.
+//!
+//! The level is controlled by the global command line options `-v`/`--verbose` (repeatable, e.g. `-vv`), `-q`/`--quiet` and `--log-file <path>`,
+//! which may be passed before or after a subcommand. By default, only warnings and errors are shown.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::Instant;
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// A logger that writes each record as a single line, prefixed with the time since the logger was created and the record's level.
+struct Logger {
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+    start: Instant,
+}
+
+impl Log for Logger {
+
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!("[{:>9.3}s {:<5} {}] {}\n", self.start.elapsed().as_secs_f64(), record.level(), record.target(), record.args());
+        let _res = match &self.file {
+            Some(file) => file.lock().map(|mut f| f.write_all(line.as_bytes())).unwrap_or(Ok(())),
+            None => io::stderr().write_all(line.as_bytes()),
+        };
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Ok(mut f) = file.lock() {
+                let _res = f.flush();
+            }
+        }
+    }
+}
+
+/// Removes the logging options from the command line arguments and installs a logger configured accordingly.
+///
+/// # Errors
+/// Returns an error if `--log-file` is missing its path, or if the log file cannot be created.
+///
+/// # Example
+/// ```ignore
+/// let mut args: Vec<String> = env::args().collect();
+/// logging::init_from_args(&mut args)?;
+/// log::info!("logging initialized");
+/// ```
+pub fn init_from_args(args: &mut Vec<String>) -> io::Result<()> {
+    let mut verbosity = 0i32;
+    let mut file = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--verbose" => verbosity += 1,
+            "-q" | "--quiet" => verbosity = -1,
+            "--log-file" => {
+                if i + 1 >= args.len() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "--log-file requires a path"));
+                }
+                file = Some(File::create(args.remove(i + 1))?);
+            },
+            arg if arg.len() > 1 && arg.starts_with('-') && arg[1..].chars().all(|c| c == 'v') => verbosity += arg.len() as i32 - 1,
+            _ => {
+                i += 1;
+                continue;
+            },
+        }
+        args.remove(i);
+    }
+
+    let level = match verbosity {
+        i32::MIN..=-1 => LevelFilter::Error,
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    let logger = Logger { level, file: file.map(Mutex::new), start: Instant::now() };
+    log::set_logger(Box::leak(Box::new(logger))).map_err(|e| io::Error::other(e.to_string()))?;
+    log::set_max_level(level);
+    Ok(())
+}
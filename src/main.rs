@@ -1,33 +1,1095 @@
-//! A simple implementation of a CHIP-8 emulator
+//! The terminal frontend of the `chip8` library.
 //! 
-//! I mainly used this to get started with rust. Rendering is performed in the terminal. Sound output is not currently supported.
-//! The fetch/decode/execute loop supports arbitrary execution speed, however, with the time requirements of printing to stdout,
-//! there is a hard cap on the maximum reachable speed.
+//! Rendering is performed in the terminal. Sound is played with the `audio` feature.
+//! The fetch/decode/execute loop supports arbitrary execution speed. It runs in frames of 1/60 s and renders once per frame, so the time it takes
+//! to print to stdout does not slow down the emulation.
 //! 
 //! Please make sure that your terminal can show at least 34 rows at once to run the emulator, otherwise weird graphic glitches will occur.
 
-mod utils;
-mod system;
-mod program;
-
+use std::env;
+use std::fs;
 use std::io;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+use chip8::{archive, asm, batch, commands, compare, conformance, config, crash, input, logging, octo, patch, patterns, persistence, program, quirks,
+    renderer, romdb, savestate, selftest, server, system, terminal};
+#[cfg(feature = "audio")]
+use chip8::audio;
+#[cfg(feature = "watch")]
+use chip8::watch;
+#[cfg(feature = "window")]
+use chip8::window;
+
 #[deny(missing_docs)]
-/// Runs the emulator. The program to be run is hardcoded in the `main` function. You can change it by pasting your program of choice in the `test/data`
-/// directory and then changing the value of the `name` variable accordingly. 
+/// Runs the emulator: `chip8 <rom> [options]`, e.g. `chip8 roms/pong.ch8 --speed 1000 --scale 1 --no-confirm`. After printing the program, the
+/// emulator waits for `Enter` before starting, unless `--no-confirm` is given.
+/// 
+/// The program runs at 700 instructions per second, which can be changed with `--hz <n>` (or its alias `--speed <hz>`, and with `+`/`-` while
+/// running), or given as instructions per frame with `--ipf <n>`, which takes precedence. The emulator runs in frames of 1/60 s: each frame
+/// executes its share of the instructions, ticks the timers and renders the display once, so the speed does not depend on how fast the terminal
+/// is. By default, each pixel is drawn two terminal columns wide if the terminal is large enough; `--scale <1|2>` fixes the number of columns per
+/// pixel instead.
+/// 
+/// The following subcommands can be used instead of running the emulator:
+/// - `chip8 info <rom>`: Prints metadata (title, length, SHA-1 hash, CRC-32 checksum, matched ROM profile) of the given ROM. For Octo cartridges
+///   (`.gif`), prints the stored settings and the size of the compiled program instead.
+/// - `chip8 lint <rom>`: Checks the given ROM for potential problems and prints a warning for each of them.
+/// - `chip8 diff <a> <b>`: Prints all instructions that differ between two ROMs, with their addresses and disassembly.
+/// - `chip8 patch <rom> <patch>... -o <output>`: Applies one or more patches (text or IPS format) to a ROM and writes the result to `output`.
+/// - `chip8 export <rom> <c|rust|ihex> [-o <output>]`: Exports a ROM as a C array, a Rust `include_bytes!` snippet or Intel HEX records, printing
+///   the result or writing it to `output`.
+/// - `chip8 disasm <rom>...`: Prints the disassembly of each ROM as `<address>: <mnemonic>` lines, e.g. `020A: DRW V0, V1, 5`.
+/// - `chip8 asm <source> [-o <output>]`: Assembles a source file with labels and mnemonics such as `JP`, `LD`, `DRW` and `DB` (see the `asm`
+///   module) and writes the ROM to `output` (default: the source file with the extension `.ch8`).
+/// - `chip8 hexdump <rom>...`: Prints an `xxd`-style hex+ASCII dump of each ROM, with addresses starting at `0x200`.
+/// - `chip8 roundtrip <rom>...`: Disassembles and reassembles each ROM, verifying that the bytes are unchanged and reporting which regions are data.
+/// - `chip8 serve <rom> [--port <port>]`: Serves the ROM over telnet (default port 2323), so it can be played remotely with any telnet client.
+/// - `chip8 batch <dir> [--cycles <n>] [--json] [-o <report>]`: Runs every ROM in a directory headlessly for at most `n` instructions (default
+///   100000) and prints (or writes) a CSV or JSON report of how each run ended.
+/// - `chip8 gen <pattern> [-o <output>]`: Generates a test-pattern ROM (`checkerboard`, `font`, `keys` or `countdown`) and writes it to `output`
+///   (default `<pattern>.ch8`).
+/// - `chip8 compare <rom> <quirks> <quirks>`: Runs the ROM twice side by side with shared input, each with the given quirks (a comma-separated list
+///   such as `shift_uses_vy,clip_sprites=off`, a preset such as `chip8` or `schip`, or `default`), to see which configuration the ROM expects. Needs a terminal of 262 columns.
+/// - `chip8 selftest [quirks]`: Runs a tiny built-in test program for each quirk with the given quirks (default `default`) and prints a table of
+///   the observed behaviors. Exits with code 1 if a behavior does not match the configuration.
+/// - `chip8 conformance [--bless]`: Runs the well-known test ROMs in `test/roms/` and the generated test patterns, and compares their final
+///   displays to the screenshots in `test/expected/` (see the `conformance` module), then runs the opcode checks, which compare the registers and
+///   flags left by single instructions to their expected values. Exits with code 1 on a mismatch, or if a test ROM is missing and
+///   `CHIP8_SKIP_MISSING_ROMS` is not set. With `--bless`, the current displays are stored as the new screenshots instead.
+/// 
+/// With `--patch <file>` (which can be given several times), the patches (text or IPS format, see the `patch` module) are applied to the ROM
+/// before it is loaded, so variants of a ROM can be tried without writing them to a file first. Reloading the ROM from disk drops them.
+/// 
+/// When run with `--watch` (requires the `watch` feature), the ROM is reloaded and restarted whenever its file changes.
+/// 
+/// When run with `--commands <stdin|port>`, key commands such as `press 5` or `hold A 500ms` are additionally accepted line by line from stdin or
+/// from TCP clients connecting to the given port (see the `commands` module), e.g. to let a bot or a chat control the session.
+/// 
+/// When run with `--record-input <file>`, the keypad input, the speed changes and the seed of the random number generator are recorded to an input
+/// log, and `--replay <file>` plays such a log back to reproduce the session exactly, e.g. from a bug report (see the `replay` module).
+/// 
+/// When run with `--record <file.gif>`, the display is additionally recorded as an animated GIF. When run with `--dump-frames <dir>`, every frame
+/// that differs from the previous one is written to the directory as a numbered PPM image (`frame_000001.ppm`, ...).
+/// 
+/// When run with `--player2 <port>`, the emulator waits for a second player to connect with a telnet client before starting. The second player sees
+/// the display and controls the CHIP-8 keys given with `--player2-keys <keys>` as hexadecimal digits (default `CDEF`, the right paddle in Pong).
+/// 
+/// `FX0A` latches quick key taps into a queue, so they are not missed at low speeds. With `--key-mode sample`, it only samples the current key state
+/// instead, like the original hardware.
+/// 
+/// With the `audio` feature, a tone is played while the sound timer is active, unless `--mute` is given.
+/// 
+/// The delay and sound timers count down at 60 Hz. Use `--timer-rate <hz>` to change that, e.g. to 50 for PAL-derived systems.
+/// 
+/// With `--renderer window` (requires the `window` feature), the display is shown in a window instead of the terminal, which is not limited by the
+/// size or the speed of the terminal. Each pixel is drawn as a square of `--pixel-scale <n>` window pixels (default 10), and `--colors <on,off>`
+/// sets the colors of pixels that are on and off as hexadecimal RGB values (default `FFFFFF,000000`). Keys are read from the focused window.
+/// 
+/// With `--plain`, frames are printed as plain text (`#` and `.`) without escape sequences, e.g. to pipe them into a file. Without input sources
+/// such as `--commands`, the emulator then needs no terminal, threads or Windows APIs, so it can run sandboxed under WASI, e.g. to analyze untrusted
+/// ROMs: `cargo build --target wasm32-wasip1`, then `echo | wasmtime --dir . target/wasm32-wasip1/debug/chip8.wasm --plain`.
+/// 
+/// The display can be made easier to see with `--display <invert,high-contrast,no-decay>`: inverted colors, solid bright blocks on black, and pixels
+/// that turn off immediately instead of fading out. The options can also be toggled while running with `F5`, `F6` and `F7`.
+/// 
+/// With `--persist <start-end>` (e.g. `E00-EFF`), the given address range is loaded from a file next to the ROM (`<rom>.sav`) and saved to it on
+/// exit, so homebrew ROMs can keep settings and high scores.
+/// 
+/// With `--variant schip`, the SUPER-CHIP 1.1 is emulated instead of the original CHIP-8, with its 128x64 high-resolution mode, scrolling, 16x16
+/// sprites, big font and RPL user flags. `--variant xochip` emulates the XO-CHIP of Octo, which adds 64 KiB of memory, a second display plane for
+/// four colors (shown in color by `--renderer window`, composited in the terminal) and audio patterns.
+/// 
+/// Interpreters disagree on the behavior of some instructions (see the `quirks` module). `--quirks <quirks>` selects the behaviors a ROM expects,
+/// either as a preset (`chip8` for the COSMAC VIP, `schip` for the SUPER-CHIP) or as a comma-separated list such as `shift_uses_vy,vf_reset=off`
+/// that may follow a preset (e.g. `--quirks schip,clip_sprites=off`). It overrides the quirks of the ROM's CHIP-8 Archive entry, just like
+/// `--variant` overrides the variant given by the entry's platform.
+/// 
+/// Octo cartridges (`.gif`) are run like ROMs: their Octo source code is compiled (see the `octo` module), and the quirks, the tick rate and the
+/// variant stored in them are applied. They are overridden by `--variant`, `--quirks`, `--hz` and `--ipf`.
+/// 
+/// Well-known ROMs are recognized by their SHA-1 hash, their CRC-32 checksum or their file name in a bundled database (see the `romdb` module).
+/// If the ROM matched by hash or checksum, the known-good variant, quirks, speed and key remappings of its profile are applied and printed before
+/// starting. They are overridden by the CHIP-8 Archive entry and by `--variant`, `--quirks`, `--hz` and `--ipf`. A profile that only matched by
+/// file name is printed as a suggestion, but not applied. `--romdb <file>` adds a database of your own, which takes precedence over the bundled
+/// one, and `--no-profile` skips the lookup.
+/// 
+/// While running, `F3` saves the complete state of the machine to a file next to the ROM (`<rom>.state`) and `F4` restores it. `Backspace` restarts
+/// the program and `L` reloads the ROM from disk, e.g. after it was rebuilt.
+/// 
+/// With `--trace <file>`, every executed instruction is written to the file as `<cycle> <PC>: <opcode>  <mnemonic>` (see the `trace` module), e.g.
+/// to find out how a ROM reached an unknown opcode.
+/// 
+/// With `--debug`, the emulator starts paused in the debugger, which shows the PC, `I`, the registers, the stack and the timers below the display.
+/// While running, `F8` or `P` pauses or continues the execution, `F9` toggles a breakpoint at the PC and `F10` executes a single instruction. Breakpoints
+/// can also be given with `--break <addr,addr>` as hexadecimal addresses (e.g. `--break 20A,31C`); the execution pauses before they are executed.
+/// Similarly, `--watchpoint <addr,addr>` pauses the execution after an instruction wrote to one of the given addresses.
+/// 
+/// `M` (or `--monitor`) shows a live monitor next to the display, with the PC, `I`, the stack depth, the timers, the registers and a hex dump of
+/// the memory around `I`, which can be scrolled with `PageUp`/`PageDown`. The display is zoomed if the terminal is too narrow for both. `Tab`
+/// edits the memory in the monitor: the arrow keys and `PageUp`/`PageDown` move the cursor, and typed hex digits overwrite the memory of the
+/// running program (the CHIP-8 keys are not pressed meanwhile) until `Tab` is pressed again.
+/// 
+/// With `--protect <font,program>`, writes of the program to the font sprites and/or to its own bytes are reported as an error.
+/// 
+/// If the program fails, e.g. with an unknown opcode or a return without a subroutine call, the emulator halts and exits with code 1, after
+/// writing a crash report with the error, the ROM hash, quirks, registers, stack, recently executed PCs and a memory dump to
+/// `chip8-crash-<timestamp>.txt` in the working directory. The same report is written if the interpreter itself panics. With
+/// `--on-error continue`, failing instructions are logged and skipped instead.
+/// 
+/// Diagnostics are logged to stderr. The global options `-v`/`-vv`/`-vvv` increase the log level, `-q` only shows errors and `--log-file <path>` writes
+/// the log to a file instead (see the `logging` module).
 fn main() {   
+    let mut args: Vec<String> = env::args().collect();
+    if let Err(e) = logging::init_from_args(&mut args) {
+        eprintln!("Could not initialize logging: {}", e);
+        process::exit(2);
+    }
+    let watch = args.iter().position(|arg| arg == "--watch").map(|idx| args.remove(idx)).is_some();
+    let plain = args.iter().position(|arg| arg == "--plain").map(|idx| args.remove(idx)).is_some();
+    let mute = args.iter().position(|arg| arg == "--mute").map(|idx| args.remove(idx)).is_some();
+    let no_confirm = args.iter().position(|arg| arg == "--no-confirm").map(|idx| args.remove(idx)).is_some();
+    let debug = args.iter().position(|arg| arg == "--debug").map(|idx| args.remove(idx)).is_some();
+    let monitor = args.iter().position(|arg| arg == "--monitor").map(|idx| args.remove(idx)).is_some();
+    let no_profile = args.iter().position(|arg| arg == "--no-profile").map(|idx| args.remove(idx)).is_some();
+    let romdb_path = take_option(&mut args, "--romdb", "<file>");
+    let command_source = take_option(&mut args, "--commands", "<stdin|port>");
+    let player2_port = take_option(&mut args, "--player2", "<port>");
+    let player2_keys = take_option(&mut args, "--player2-keys", "<keys>");
+    let record = take_option(&mut args, "--record", "<file.gif>");
+    let record_input = take_option(&mut args, "--record-input", "<file>");
+    let replay = take_option(&mut args, "--replay", "<file>");
+    let dump_frames = take_option(&mut args, "--dump-frames", "<dir>");
+    let key_mode = take_option(&mut args, "--key-mode", "<queue|sample>");
+    let protect = take_option(&mut args, "--protect", "<font,program>");
+    let display_options = take_option(&mut args, "--display", "<invert,high-contrast,no-decay>");
+    let persist = take_option(&mut args, "--persist", "<start-end>");
+    let timer_rate = take_option(&mut args, "--timer-rate", "<hz>");
+    let hz = take_option(&mut args, "--hz", "<hz>");
+    let speed = take_option(&mut args, "--speed", "<hz>").or(hz);
+    let ipf = take_option(&mut args, "--ipf", "<n>");
+    let scale = take_option(&mut args, "--scale", "<1|2>");
+    let variant = take_option(&mut args, "--variant", "<chip8|schip|xochip>");
+    let breakpoints = take_option(&mut args, "--break", "<addr,addr>");
+    let watchpoints = take_option(&mut args, "--watchpoint", "<addr,addr>");
+    let trace = take_option(&mut args, "--trace", "<file>");
+    let on_error = take_option(&mut args, "--on-error", "<halt|continue>");
+    let renderer_name = take_option(&mut args, "--renderer", "<terminal|window>");
+    let pixel_scale = take_option(&mut args, "--pixel-scale", "<n>");
+    let colors = take_option(&mut args, "--colors", "<on,off>");
+    let quirk_flags = take_option(&mut args, "--quirks", "<chip8|schip|flags>");
+    let patches: Vec<String> = std::iter::from_fn(|| take_option(&mut args, "--patch", "<file>")).collect();
+    match args.get(1).map(|s| s.as_str()) {
+        Some("info") => return info(&args[2..]),
+        Some("lint") => return lint(&args[2..]),
+        Some("diff") => return diff(&args[2..]),
+        Some("patch") => return patch(&args[2..]),
+        Some("export") => return export(&args[2..]),
+        Some("disasm") => return disasm(&args[2..]),
+        Some("asm") => return assemble(&args[2..]),
+        Some("hexdump") => return hexdump(&args[2..]),
+        Some("serve") => return serve(&args[2..]),
+        Some("batch") => return batch(&args[2..]),
+        Some("roundtrip") => return roundtrip(&args[2..]),
+        Some("gen") => return gen(&args[2..]),
+        Some("compare") => return compare(&args[2..]),
+        Some("selftest") => return selftest(&args[2..]),
+        Some("conformance") => return conformance(&args[2..]),
+        Some(_) if args.len() == 2 => {},
+        Some(_) => {
+            eprintln!("Unexpected argument '{}'", args[2]);
+            process::exit(2);
+        },
+        None => {
+            eprintln!("Usage: chip8 <rom> [options], or chip8 <command> [args]");
+            process::exit(2);
+        },
+    }
+
     let stdin = io::stdin();
 
-    let mut sys = system::System::new();
-    let mut display = system::Display::new();
+    let mut options = system::DisplayOptions::new();
+    for option in display_options.iter().flat_map(|options| options.split(',')) {
+        match option.trim() {
+            "invert" => options.invert = true,
+            "high-contrast" => options.high_contrast = true,
+            "no-decay" => options.decay = false,
+            other => {
+                eprintln!("Unknown display option '{}', expected invert, high-contrast or no-decay", other);
+                process::exit(2);
+            },
+        }
+    }
+    let scale = scale.map(|scale| match scale.as_str() {
+        "1" => 1,
+        "2" => 2,
+        other => {
+            eprintln!("Invalid scale '{}', expected 1 or 2 terminal columns per pixel", other);
+            process::exit(2);
+        },
+    });
+    let with_scale = |display: system::Display| match scale {
+        Some(scale) => display.with_scale(scale),
+        None => display,
+    };
+    let mut display = with_scale(system::Display::new().with_options(options));
 
-    let name = "tombstontipp";
-    let program = program::Program::load("test/data/".to_string() + name + ".ch8").unwrap();
+    let path = args[1].clone();
+    let mut program = program::Program::load(&path).unwrap_or_else(|e| {
+        eprintln!("Could not load '{}': {}", path, e);
+        process::exit(1);
+    });
+    for patch_path in &patches {
+        match patch::Patch::load(patch_path) {
+            Ok(patch) => program.apply_patch(&patch),
+            Err(e) => {
+                eprintln!("Could not load patch '{}': {}", patch_path, e);
+                process::exit(1);
+            },
+        }
+    }
 
+    let mut config = config::Config::new();
     println!("Program:\n{}", program);
-    let mut string = String::new();
-    let _res = stdin.read_line(&mut string);
+    let profile = if no_profile { None } else { find_profile(&program, romdb_path.as_deref()) };
+    match &profile {
+        Some(found) if found.is_exact() => {
+            println!("Matched ROM profile by {}:\n{}", found.matched_by, found.profile);
+            found.profile.apply(&mut config);
+        },
+        Some(found) => {
+            println!("Suggested ROM profile, matched by {} only and not applied:\n{}", found.matched_by, found.profile);
+            println!("Use --variant, --quirks and --ipf to apply its settings, or add the ROM's hash to a database given with --romdb");
+        },
+        None if no_profile => log::debug!("Not looking up '{}' in the ROM database", path),
+        None => println!("No ROM profile matched, using the default settings"),
+    }
+    match archive::ArchiveEntry::find(&path) {
+        Ok(Some(entry)) => {
+            println!("{}", entry);
+            log::info!("Applying the platform and quirks of the CHIP-8 Archive entry '{}'", entry.title);
+            config.quirks = entry.quirks(config.quirks);
+            match (entry.variant(), &entry.platform) {
+                (Some(variant), _) => config.variant = variant,
+                (None, Some(platform)) => log::warn!("Unknown platform '{}' in the CHIP-8 Archive entry, keeping the variant", platform),
+                (None, None) => {},
+            }
+        },
+        Ok(None) => log::debug!("No CHIP-8 Archive entry found for '{}'", path),
+        Err(e) => log::warn!("Could not read program metadata: {}", e),
+    }
+    if path.to_lowercase().ends_with(".gif") {
+        match octo::OctoCart::load(&path) {
+            Ok(cart) => {
+                log::info!("Applying the settings of the Octo cartridge '{}'", path);
+                cart.apply(&mut config);
+            },
+            Err(e) => log::warn!("Could not read the settings of the Octo cartridge: {}", e),
+        }
+    }
+    if let Some(flags) = quirk_flags {
+        config.quirks = config.quirks.with_flags(&flags).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(2);
+        });
+    }
+    log::debug!("Quirks:\n{}", config.quirks);
+    for region in protect.iter().flat_map(|regions| regions.split(',')) {
+        match region.trim() {
+            "font" => config.write_protection.font = true,
+            "program" => config.write_protection.program = true,
+            other => {
+                eprintln!("Unknown memory region '{}', expected font or program", other);
+                process::exit(2);
+            },
+        }
+    }
+    if let Some(rate) = timer_rate {
+        match rate.parse() {
+            Ok(frequency) if frequency > 0 => config.timer_frequency = frequency,
+            _ => {
+                eprintln!("Invalid timer rate '{}', expected a frequency in Hz such as 50", rate);
+                process::exit(2);
+            },
+        }
+    }
+    if let Some(speed) = speed {
+        match speed.parse() {
+            Ok(frequency) if frequency > 0 => config.loop_frequency = frequency,
+            _ => {
+                eprintln!("Invalid speed '{}', expected a number of instructions per second such as 700", speed);
+                process::exit(2);
+            },
+        }
+    }
+    if let Some(ipf) = ipf {
+        match ipf.parse::<u16>() {
+            Ok(instructions) if instructions > 0 && instructions <= u16::MAX / 60 => config.loop_frequency = instructions * 60,
+            _ => {
+                eprintln!("Invalid number of instructions per frame '{}', expected a number between 1 and {} such as 15", ipf, u16::MAX / 60);
+                process::exit(2);
+            },
+        }
+    }
+    if let Some(variant) = variant {
+        config.variant = variant.parse().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(2);
+        });
+    }
+    match on_error.as_deref() {
+        None | Some("halt") => config.error_mode = system::ErrorMode::Halt,
+        Some("continue") => config.error_mode = system::ErrorMode::Continue,
+        Some(other) => {
+            eprintln!("Unknown error mode '{}', expected halt or continue", other);
+            process::exit(2);
+        },
+    }
+    match key_mode.as_deref() {
+        None | Some("queue") => config.key_mode = system::KeyMode::Queue,
+        Some("sample") => config.key_mode = system::KeyMode::Sample,
+        Some(other) => {
+            eprintln!("Unknown key mode '{}', expected queue or sample", other);
+            process::exit(2);
+        },
+    }
+    let window = match renderer_name.as_deref() {
+        None | Some("terminal") => false,
+        Some("window") => true,
+        Some(other) => {
+            eprintln!("Unknown renderer '{}', expected terminal or window", other);
+            process::exit(2);
+        },
+    };
+    for warning in program.validate(&config.quirks) {
+        log::warn!("{}", warning);
+    }
+    let mut sys = system::System::with_config(config);
+    if !no_confirm && command_source.as_deref() != Some("stdin") {
+        let mut string = String::new();
+        let _res = stdin.read_line(&mut string);
+    }
     
-    sys.load(program);
-    print!("{}[2J", 27 as char);
-    sys.run(&mut display);
+    let title = program.info().title;
+    display.set_title(&format!("CHIP-8 - {}", title));
+    if let Err(e) = sys.load(program) {
+        eprintln!("Could not load program: {}", e);
+        process::exit(1);
+    }
+    if let Some(persist) = persist {
+        let range = persistence::parse_range(&persist).unwrap_or_else(|| {
+            eprintln!("Invalid address range '{}', expected e.g. E00-EFF", persist);
+            process::exit(2);
+        });
+        let file = persistence::PersistentRegion::file_for(Path::new(&path));
+        if let Err(e) = sys.enable_persistence(&file, range) {
+            eprintln!("Could not use '{}' for persistent memory: {}", file.display(), e);
+            process::exit(1);
+        }
+    }
+    sys.set_playlist(playlist(&path));
+    sys.set_state_file(savestate::SaveState::file_for(Path::new(&path)));
+    for address in breakpoints.iter().flat_map(|addresses| addresses.split(',')) {
+        let address = address.trim();
+        match u16::from_str_radix(address.trim_start_matches("0x"), 16) {
+            Ok(breakpoint) if (breakpoint as usize) < sys.memory.size() => sys.debugger.add_breakpoint(breakpoint),
+            _ => {
+                eprintln!("Invalid breakpoint '{}', expected a hexadecimal address such as 20A", address);
+                process::exit(2);
+            },
+        }
+    }
+    for address in watchpoints.iter().flat_map(|addresses| addresses.split(',')) {
+        let address = address.trim();
+        match u16::from_str_radix(address.trim_start_matches("0x"), 16) {
+            Ok(watchpoint) if (watchpoint as usize) < sys.memory.size() => sys.debugger.add_watchpoint(watchpoint),
+            _ => {
+                eprintln!("Invalid watchpoint '{}', expected a hexadecimal address such as 3F0", address);
+                process::exit(2);
+            },
+        }
+    }
+    if let Some(trace) = trace {
+        if let Err(e) = sys.enable_trace(&trace) {
+            eprintln!("Could not write the trace to '{}': {}", trace, e);
+            process::exit(1);
+        }
+    }
+    if let Some(file) = replay {
+        if let Err(e) = sys.replay_input(&file) {
+            eprintln!("Could not replay '{}': {}", file, e);
+            process::exit(1);
+        }
+    }
+    else if let Some(file) = record_input {
+        if let Err(e) = sys.record_input(&file) {
+            eprintln!("Could not record the input to '{}': {}", file, e);
+            process::exit(1);
+        }
+    }
+    if debug {
+        sys.debugger.pause();
+    }
+    if monitor {
+        sys.debugger.toggle_monitor();
+    }
+    #[cfg(feature = "audio")]
+    if !mute {
+        match audio::Beeper::new() {
+            Ok(beeper) => sys.set_audio(Box::new(beeper)),
+            Err(e) => log::warn!("Could not open the audio output, running without sound: {}", e),
+        }
+    }
+    #[cfg(not(feature = "audio"))]
+    if !mute {
+        log::debug!("Built without the `audio` feature, running without sound");
+    }
+    #[cfg(feature = "watch")]
+    let _watcher = if watch {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let watcher = watch::RomWatcher::start(&path, tx).unwrap_or_else(|e| {
+            eprintln!("Could not watch '{}': {}", path, e);
+            process::exit(1);
+        });
+        sys.set_program_updates(rx);
+        Some(watcher)
+    } else {
+        None
+    };
+    #[cfg(not(feature = "watch"))]
+    if watch {
+        eprintln!("--watch requires the `watch` feature");
+        process::exit(2);
+    }
+    let mut input = input::keyboard();
+    let mirror = player2_port.map(|port| {
+        let port = port.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid port '{}'", port);
+            process::exit(2);
+        });
+        let keys = parse_key_set(player2_keys.as_deref().unwrap_or("CDEF")).unwrap_or_else(|| {
+            eprintln!("Invalid keys '{}', expected hexadecimal digits such as CDEF", player2_keys.unwrap_or_default());
+            process::exit(2);
+        });
+        println!("Waiting for the second player to connect on port {}...", port);
+        server::accept_second_player(port, keys, input.sender()).unwrap_or_else(|e| {
+            eprintln!("Could not accept the second player on port {}: {}", port, e);
+            process::exit(1);
+        })
+    });
+    if let Some(mirror) = &mirror {
+        display = with_scale(system::Display::with_output(Box::new(mirror.clone())).with_options(options));
+        display.set_title(&format!("CHIP-8 - {}", title));
+    }
+    match command_source.as_deref() {
+        Some("stdin") => commands::read_stdin(commands::CommandExecutor::new(input.sender())),
+        Some(port) => {
+            let port = port.parse().unwrap_or_else(|_| {
+                eprintln!("Invalid port '{}'", port);
+                process::exit(2);
+            });
+            if let Err(e) = commands::listen(commands::CommandExecutor::new(input.sender()), port) {
+                eprintln!("Could not listen on port {}: {}", port, e);
+                process::exit(1);
+            }
+            log::info!("Accepting key commands on port {}", port);
+        },
+        None => {},
+    }
+    let mut renderer = if plain {
+        renderer::Tee::new().with(renderer::PlainText::new())
+    } else if window {
+        open_window(&title, pixel_scale.as_deref(), colors.as_deref(), &input)
+    } else {
+        renderer::Tee::new().with(display)
+    };
+    if let Some(record) = record {
+        match renderer::GifRecorder::create(&record, 4) {
+            Ok(recorder) => renderer = renderer.with(recorder),
+            Err(e) => {
+                eprintln!("Could not record to '{}': {}", record, e);
+                process::exit(1);
+            },
+        }
+    }
+    if let Some(directory) = dump_frames {
+        match renderer::FrameDumper::create(&directory, 4) {
+            Ok(dumper) => renderer = renderer.with(dumper),
+            Err(e) => {
+                eprintln!("Could not dump frames to '{}': {}", directory, e);
+                process::exit(1);
+            },
+        }
+    }
+    let result = {
+        let _terminal = (!plain).then(|| {
+            let terminal = terminal::TerminalGuard::enter();
+            print!("{}[2J", 27 as char);
+            terminal
+        });
+        panic::catch_unwind(panic::AssertUnwindSafe(|| sys.run_with(&mut renderer, &mut input)))
+    };
+    if let Err(payload) = result {
+        let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown error".to_string());
+        write_crash_report(&sys, &message);
+        panic::resume_unwind(payload);
+    }
+    drop(renderer);
+    if let Some(mirror) = mirror {
+        mirror.close(&format!("CHIP-8 Finished after {:.2?} of emulated time!", sys.emulated_time()));
+    }
+    println!("CHIP-8 Finished after {:.2?} of emulated time!", sys.emulated_time());
+    println!("{}", sys.stats());
+    if let Some(error) = sys.error() {
+        eprintln!("The program failed: {}", error);
+        write_crash_report(&sys, &error.to_string());
+        process::exit(1);
+    }
+}
+
+/// Writes a crash report of the system to `chip8-crash-<timestamp>.txt` in the working directory.
+fn write_crash_report(sys: &system::System, message: &str) {
+    let file = format!("chip8-crash-{}.txt", SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default());
+    match crash::CrashReport::new(sys, message).save(&file) {
+        Ok(()) => eprintln!("Crash report written to '{}'", file),
+        Err(e) => eprintln!("Could not write crash report '{}': {}", file, e),
+    }
+}
+
+/// Opens the window of `--renderer window`, with the pixel scale and colors given on the command line, and forwards its keys to the input.
+#[cfg(feature = "window")]
+fn open_window(title: &str, scale: Option<&str>, colors: Option<&str>, input: &input::ChannelInput) -> renderer::Tee {
+    let scale = scale.map_or(Some(window::DEFAULT_PIXEL_SCALE), |scale| scale.parse().ok().filter(|scale| *scale > 0)).unwrap_or_else(|| {
+        eprintln!("Invalid pixel scale '{}', expected a positive number such as 10", scale.unwrap_or_default());
+        process::exit(2);
+    });
+    let (on, off) = match colors {
+        None => (window::DEFAULT_ON_COLOR, window::DEFAULT_OFF_COLOR),
+        Some(colors) => colors.split_once(',')
+            .and_then(|(on, off)| Some((u32::from_str_radix(on.trim(), 16).ok()?, u32::from_str_radix(off.trim(), 16).ok()?)))
+            .filter(|(on, off)| *on <= 0xFFFFFF && *off <= 0xFFFFFF)
+            .unwrap_or_else(|| {
+                eprintln!("Invalid colors '{}', expected two hexadecimal RGB values such as FFB000,202020", colors);
+                process::exit(2);
+            }),
+    };
+    let window = window::WindowRenderer::open(&format!("CHIP-8 - {}", title), scale).unwrap_or_else(|e| {
+        eprintln!("Could not open a window: {}", e);
+        process::exit(1);
+    });
+    renderer::Tee::new().with(window.with_colors(on, off).with_input(input.sender()))
+}
+
+/// Exits with an error, since `--renderer window` requires the `window` feature.
+#[cfg(not(feature = "window"))]
+fn open_window(_title: &str, _scale: Option<&str>, _colors: Option<&str>, _input: &input::ChannelInput) -> renderer::Tee {
+    eprintln!("--renderer window requires the `window` feature");
+    process::exit(2);
+}
+
+/// Removes a global option and its value from the arguments, exiting with a usage message if the value is missing.
+fn take_option(args: &mut Vec<String>, name: &str, value: &str) -> Option<String> {
+    let idx = args.iter().position(|arg| arg == name)?;
+    if idx + 1 >= args.len() {
+        eprintln!("Usage: chip8 {} {}", name, value);
+        process::exit(2);
+    }
+    args.drain(idx..=idx + 1).nth(1)
+}
+
+/// Parses a set of CHIP-8 keys given as hexadecimal digits, e.g. `CDEF`. Returns `None` if any character is not a hexadecimal digit.
+fn parse_key_set(keys: &str) -> Option<[bool; 16]> {
+    let mut set = [false; 16];
+    for c in keys.chars() {
+        set[c.to_digit(16)? as usize] = true;
+    }
+    Some(set)
+}
+
+/// Collects the ROMs in the same directory as the given ROM, sorted by name and rotated so the given ROM comes first. These can be cycled through
+/// with the `F2` hotkey while the emulator is running.
+fn playlist(rom: &str) -> Vec<PathBuf> {
+    let rom = Path::new(rom);
+    let mut paths: Vec<PathBuf> = rom.parent().and_then(|dir| fs::read_dir(dir).ok()).into_iter().flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ch8")))
+        .collect();
+    paths.sort();
+    if let Some(idx) = paths.iter().position(|path| path == rom) {
+        paths.rotate_left(idx);
+    }
+    paths
+}
+
+/// Implements the `info` subcommand, printing the metadata of each given ROM.
+fn info(paths: &[String]) {
+    if paths.is_empty() {
+        eprintln!("Usage: chip8 info <rom>...");
+        process::exit(2);
+    }
+    for path in paths {
+        if path.to_lowercase().ends_with(".gif") {
+            match octo::OctoCart::load(path) {
+                Ok(cart) => {
+                    println!("Octo cartridge: {}", path);
+                    println!("Source: {} lines", cart.source.lines().count());
+                    match cart.compile() {
+                        Ok(bytes) => println!("Compiled: {} bytes", bytes.len()),
+                        Err(e) => println!("Compiled: failed ({})", e),
+                    }
+                    if let Some(tickrate) = cart.tickrate {
+                        println!("Tickrate: {} instructions per frame", tickrate);
+                    }
+                    println!("{}\n", cart.quirks);
+                },
+                Err(e) => {
+                    eprintln!("Could not load cartridge '{}': {}", path, e);
+                    process::exit(1);
+                },
+            }
+            continue;
+        }
+        match program::Program::load(path) {
+            Ok(program) => {
+                println!("{}", program.info());
+                if let Some(found) = program.profile() {
+                    let kind = if found.is_exact() { "Profile" } else { "Suggested profile" };
+                    println!("{} (matched by {}):\n{}", kind, found.matched_by, found.profile);
+                }
+            },
+            Err(e) => {
+                eprintln!("Could not load '{}': {}", path, e);
+                process::exit(1);
+            },
+        }
+        if let Ok(Some(entry)) = archive::ArchiveEntry::find(path) {
+            println!("{}", entry);
+        }
+        println!();
+    }
+}
+
+/// Looks up the ROM profile of the program, first in the database given with `--romdb`, then in the bundled one. A profile matched by the ROM's
+/// contents takes precedence over one matched by file name, no matter which database it comes from.
+fn find_profile(program: &program::Program, romdb_path: Option<&str>) -> Option<romdb::RomMatch> {
+    let database = romdb_path.map(|path| romdb::RomDatabase::load(path).unwrap_or_else(|e| {
+        eprintln!("Could not read the ROM database '{}': {}", path, e);
+        process::exit(1);
+    }));
+    let custom = database.and_then(|database| database.lookup(program));
+    let bundled = program.profile().cloned();
+    match (custom, bundled) {
+        (Some(custom), _) if custom.is_exact() => Some(custom),
+        (_, Some(bundled)) if bundled.is_exact() => Some(bundled),
+        (custom, bundled) => custom.or(bundled),
+    }
+}
+
+/// Implements the `lint` subcommand, printing the warnings found by `Program::validate(...)` for each given ROM.
+/// 
+/// Exits with status code `1` if any warnings were found.
+fn lint(paths: &[String]) {
+    if paths.is_empty() {
+        eprintln!("Usage: chip8 lint <rom>...");
+        process::exit(2);
+    }
+    let mut found = false;
+    for path in paths {
+        let program = match program::Program::load(path) {
+            Ok(program) => program,
+            Err(e) => {
+                eprintln!("Could not load '{}': {}", path, e);
+                process::exit(1);
+            },
+        };
+        for warning in program.validate(&quirks::Quirks::new()) {
+            println!("{}: {}", path, warning);
+            found = true;
+        }
+    }
+    if found {
+        process::exit(1);
+    }
+}
+
+/// Implements the `diff` subcommand, printing the differences between two ROMs.
+/// 
+/// Exits with status code `1` if the ROMs differ.
+fn diff(paths: &[String]) {
+    if paths.len() != 2 {
+        eprintln!("Usage: chip8 diff <a> <b>");
+        process::exit(2);
+    }
+    let programs: Vec<program::Program> = paths.iter().map(|path| match program::Program::load(path) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Could not load '{}': {}", path, e);
+            process::exit(1);
+        },
+    }).collect();
+
+    let diff = programs[0].diff(&programs[1]);
+    if diff.addresses().is_empty() {
+        return;
+    }
+    println!("{:<4}  {:<24} | {}", "", paths[0], paths[1]);
+    print!("{}", diff);
+    process::exit(1);
+}
+
+/// Implements the `patch` subcommand, applying patches to a ROM and writing the patched ROM to a new file.
+fn patch(args: &[String]) {
+    let usage = || -> ! {
+        eprintln!("Usage: chip8 patch <rom> <patch>... -o <output>");
+        process::exit(2);
+    };
+    let (inputs, output) = match args.iter().position(|arg| arg == "-o") {
+        Some(idx) if idx + 2 == args.len() => (&args[..idx], &args[idx + 1]),
+        _ => usage(),
+    };
+    if inputs.len() < 2 {
+        usage();
+    }
+
+    let mut program = program::Program::load(&inputs[0]).unwrap_or_else(|e| {
+        eprintln!("Could not load '{}': {}", inputs[0], e);
+        process::exit(1);
+    });
+    for path in &inputs[1..] {
+        match patch::Patch::load(path) {
+            Ok(patch) => program.apply_patch(&patch),
+            Err(e) => {
+                eprintln!("Could not load patch '{}': {}", path, e);
+                process::exit(1);
+            },
+        }
+    }
+    if let Err(e) = program.save(output) {
+        eprintln!("Could not write '{}': {}", output, e);
+        process::exit(1);
+    }
+}
+
+/// Implements the `export` subcommand, converting a ROM into a format that can be consumed by other toolchains.
+fn export(args: &[String]) {
+    let (rom, format, output) = match args {
+        [rom, format] => (rom, format, None),
+        [rom, format, flag, output] if flag == "-o" => (rom, format, Some(output)),
+        _ => {
+            eprintln!("Usage: chip8 export <rom> <c|rust|ihex> [-o <output>]");
+            process::exit(2);
+        },
+    };
+    let format: program::ExportFormat = format.parse().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    let program = program::Program::load(rom).unwrap_or_else(|e| {
+        eprintln!("Could not load '{}': {}", rom, e);
+        process::exit(1);
+    });
+
+    let exported = program.export(format).to_string();
+    match output {
+        Some(output) => if let Err(e) = fs::write(output, exported) {
+            eprintln!("Could not write '{}': {}", output, e);
+            process::exit(1);
+        },
+        None => print!("{}", exported),
+    }
+}
+
+/// Implements the `compare` subcommand, running a ROM with two quirk configurations side by side.
+fn compare(args: &[String]) {
+    let [rom, left, right] = args else {
+        eprintln!("Usage: chip8 compare <rom> <quirks> <quirks>");
+        process::exit(2);
+    };
+    let program = program::Program::load(rom).unwrap_or_else(|e| {
+        eprintln!("Could not load '{}': {}", rom, e);
+        process::exit(1);
+    });
+    let quirks = [left, right].map(|flags| quirks::Quirks::new().with_flags(flags).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    }));
+
+    let systems = {
+        let _terminal = terminal::TerminalGuard::enter();
+        print!("{}[2J", 27 as char);
+        compare::run_comparison(&program, quirks, &mut input::keyboard())
+    };
+    match systems {
+        Ok([left, right]) => {
+            println!("Left:\n{}", left.stats());
+            println!("Right:\n{}", right.stats());
+        },
+        Err(e) => {
+            eprintln!("Could not load program: {}", e);
+            process::exit(1);
+        },
+    }
+}
+
+/// Implements the `gen` subcommand, writing one of the test-pattern ROMs of the `patterns` module to a file.
+fn gen(args: &[String]) {
+    let (name, output) = match args {
+        [name] => (name, format!("{}.ch8", name)),
+        [name, flag, output] if flag == "-o" => (name, output.clone()),
+        _ => {
+            eprintln!("Usage: chip8 gen <pattern> [-o <output>]");
+            eprintln!("Patterns:");
+            for (name, description) in patterns::PATTERNS {
+                eprintln!("  {:<14}{}", name, description);
+            }
+            process::exit(2);
+        },
+    };
+    let program = patterns::generate(name).unwrap_or_else(|| {
+        eprintln!("Unknown pattern '{}', run 'chip8 gen' to list the available patterns", name);
+        process::exit(2);
+    });
+    if let Err(e) = program.save(&output) {
+        eprintln!("Could not write '{}': {}", output, e);
+        process::exit(1);
+    }
+    println!("Wrote '{}' ({} bytes)", output, program.instructions.len());
+}
+
+/// Implements the `asm` subcommand, assembling a source file into a ROM.
+fn assemble(args: &[String]) {
+    let (source, output) = match args {
+        [source] => (source, Path::new(source).with_extension("ch8")),
+        [source, flag, output] if flag == "-o" => (source, PathBuf::from(output)),
+        _ => {
+            eprintln!("Usage: chip8 asm <source> [-o <output>]");
+            process::exit(2);
+        },
+    };
+    let program = program::Program::assemble(source).unwrap_or_else(|e| {
+        eprintln!("Could not assemble '{}': {}", source, e);
+        process::exit(1);
+    });
+    if let Err(e) = program.save(&output) {
+        eprintln!("Could not write '{}': {}", output.display(), e);
+        process::exit(1);
+    }
+    println!("Wrote '{}' ({} bytes)", output.display(), program.instructions.len());
+}
+
+/// Implements the `selftest` subcommand, checking the behavior of each quirk with the given configuration.
+fn selftest(args: &[String]) {
+    let flags = match args {
+        [] => "default",
+        [flags] => flags.as_str(),
+        _ => {
+            eprintln!("Usage: chip8 selftest [quirks]");
+            process::exit(2);
+        },
+    };
+    let quirks = quirks::Quirks::new().with_flags(flags).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    let checks = selftest::run_checks(quirks);
+    println!("{:<25} {:<11} {:<9} {:<36} Result", "Quirk", "Configured", "Observed", "Behavior");
+    let state = |flag: bool| if flag { "on" } else { "off" };
+    for check in &checks {
+        let result = if check.passed() { "ok" } else { "MISMATCH" };
+        println!("{:<25} {:<11} {:<9} {:<36} {}", check.name, state(check.configured), state(check.observed), check.behavior, result);
+    }
+    if !checks.iter().all(|check| check.passed()) {
+        process::exit(1);
+    }
+}
+
+/// Implements the `conformance` subcommand, checking the conformance tests against their screenshots (or recording them with `--bless`).
+fn conformance(args: &[String]) {
+    let bless = match args {
+        [] => false,
+        [flag] if flag == "--bless" => true,
+        _ => {
+            eprintln!("Usage: chip8 conformance [--bless]");
+            process::exit(2);
+        },
+    };
+    let root = Path::new(".");
+    let mut failed = false;
+    for case in &conformance::CASES {
+        let result = if bless {
+            conformance::bless(case, root).map(|written| if written { "recorded".to_string() } else { "skipped (ROM not found)".to_string() })
+        } else {
+            conformance::check(case, root).map(|verdict| {
+                failed |= !verdict.is_ok();
+                verdict.to_string()
+            })
+        };
+        match result {
+            Ok(summary) => println!("{:<22} {}", case.name, summary),
+            Err(e) => {
+                println!("{:<22} error: {}", case.name, e);
+                failed = true;
+            },
+        }
+    }
+    for check in &conformance::OPCODE_CHECKS {
+        match conformance::check_opcodes(check) {
+            Ok(()) => println!("{:<22} passed", check.name),
+            Err(e) => {
+                println!("{:<22} failed: {}", check.name, e);
+                failed = true;
+            },
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Implements the `hexdump` subcommand, printing a hex+ASCII dump of each given ROM.
+fn hexdump(paths: &[String]) {
+    if paths.is_empty() {
+        eprintln!("Usage: chip8 hexdump <rom>...");
+        process::exit(2);
+    }
+    for path in paths {
+        match program::Program::load(path) {
+            Ok(program) => {
+                if paths.len() > 1 {
+                    println!("{}:", path);
+                }
+                print!("{}", program.hexdump());
+            },
+            Err(e) => {
+                eprintln!("Could not load '{}': {}", path, e);
+                process::exit(1);
+            },
+        }
+    }
+}
+
+/// Implements the `disasm` subcommand, printing the disassembly of each given ROM.
+fn disasm(paths: &[String]) {
+    if paths.is_empty() {
+        eprintln!("Usage: chip8 disasm <rom>...");
+        process::exit(2);
+    }
+    for path in paths {
+        match program::Program::load(path) {
+            Ok(program) => {
+                if paths.len() > 1 {
+                    println!("{}:", path);
+                }
+                for (address, text) in program.disassemble() {
+                    println!("{:0>4X}: {}", address, text);
+                }
+            },
+            Err(e) => {
+                eprintln!("Could not load '{}': {}", path, e);
+                process::exit(1);
+            },
+        }
+    }
+}
+
+/// Implements the `serve` subcommand, serving a ROM over telnet. Quirks are taken from the ROM's CHIP-8 Archive entry, if there is one.
+fn serve(args: &[String]) {
+    let (rom, port) = match args {
+        [rom] => (rom, 2323),
+        [rom, flag, port] if flag == "--port" => match port.parse() {
+            Ok(port) => (rom, port),
+            Err(_) => {
+                eprintln!("Invalid port '{}'", port);
+                process::exit(2);
+            },
+        },
+        _ => {
+            eprintln!("Usage: chip8 serve <rom> [--port <port>]");
+            process::exit(2);
+        },
+    };
+    let program = program::Program::load(rom).unwrap_or_else(|e| {
+        eprintln!("Could not load '{}': {}", rom, e);
+        process::exit(1);
+    });
+    let quirks = match archive::ArchiveEntry::find(rom) {
+        Ok(Some(entry)) => entry.quirks(quirks::Quirks::new()),
+        _ => quirks::Quirks::new(),
+    };
+    if let Err(e) = server::serve(program, quirks, port) {
+        eprintln!("Could not serve on port {}: {}", port, e);
+        process::exit(1);
+    }
+}
+
+/// Implements the `batch` subcommand, running all ROMs in a directory headlessly and reporting their outcomes.
+fn batch(args: &[String]) {
+    let usage = || -> ! {
+        eprintln!("Usage: chip8 batch <dir> [--cycles <n>] [--json] [-o <report>]");
+        process::exit(2);
+    };
+    let mut directory = None;
+    let mut cycles = 100_000;
+    let mut json = false;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cycles" => cycles = args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| usage()),
+            "--json" => json = true,
+            "-o" => output = Some(args.next().unwrap_or_else(|| usage())),
+            _ if directory.is_none() => directory = Some(arg),
+            _ => usage(),
+        }
+    }
+    let directory = directory.unwrap_or_else(|| usage());
+
+    let results = batch::run_directory(directory, cycles).unwrap_or_else(|e| {
+        eprintln!("Could not read '{}': {}", directory, e);
+        process::exit(1);
+    });
+    let report = if json { batch::to_json(&results) } else { batch::to_csv(&results) };
+    match output {
+        Some(output) => if let Err(e) = fs::write(output, report) {
+            eprintln!("Could not write '{}': {}", output, e);
+            process::exit(1);
+        },
+        None => print!("{}", report),
+    }
+}
+
+/// Implements the `roundtrip` subcommand, disassembling and reassembling each given ROM with `asm::roundtrip(...)`.
+/// 
+/// Exits with status code `1` if the reassembled bytes of any ROM differ from the original ones.
+fn roundtrip(paths: &[String]) {
+    if paths.is_empty() {
+        eprintln!("Usage: chip8 roundtrip <rom>...");
+        process::exit(2);
+    }
+    let mut failed = false;
+    for path in paths {
+        let program = match program::Program::load(path) {
+            Ok(program) => program,
+            Err(e) => {
+                eprintln!("Could not load '{}': {}", path, e);
+                process::exit(1);
+            },
+        };
+        let result = asm::roundtrip(&program);
+        let status = if result.mismatches.is_empty() { "OK" } else { "MISMATCH" };
+        println!("{}: {} ({} instructions, {} data regions)", path, status, result.instructions, result.data_regions.len());
+        for (start, end) in &result.data_regions {
+            println!("  data {:0>3X}-{:0>3X}", start, end - 1);
+        }
+        for address in &result.mismatches {
+            println!("  mismatch at {:0>3X}", address);
+        }
+        failed |= !result.mismatches.is_empty();
+    }
+    if failed {
+        process::exit(1);
+    }
 }
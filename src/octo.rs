@@ -0,0 +1,1040 @@
+//! Supports Octo cartridges ("OctoCarts"): GIF images that steganographically embed a program together with the settings it should be run with.
+//!
+//! The payload of a cartridge is stored in the two least significant bits of the palette index of each pixel, concatenated over all frames of the
+//! GIF. Four consecutive pixels form one byte (most significant bits first). The first four bytes hold the length of the payload as a big-endian
+//! integer, followed by the payload itself: a UTF-8 encoded JSON object with the fields `program` (the program's Octo source code) and `options`
+//! (the quirk settings, tick rate and colors used by Octo).
+//!
+//! Since cartridges store Octo source code rather than bytecode, the program is compiled with `compile(...)`, which implements the Octo language:
+//! labels (`: name`), constants, aliases, macros, `:calc` expressions, the structured `if ... then`, `if ... begin ... else ... end` and
+//! `loop ... while ... again` statements, and the instructions of the CHIP-8, SUPER-CHIP and XO-CHIP. `:stringmode` is not supported.
+//!
+//! ```text
+//! : main
+//!     i := digit
+//!     loop
+//!         sprite v0 v1 5
+//!         v0 += 1
+//!         if v0 == 60 then v0 := 0
+//!     again
+//! : digit 0xF0 0x90 0x90 0x90 0xF0
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde_json::Value;
+use crate::config::Config;
+use crate::program::PROGRAM_START;
+use crate::quirks::Quirks;
+use crate::system::Variant;
+
+/// The memory sizes Octo offers for programs of the original CHIP-8 (up to 3232 bytes); cartridges for larger memories are run as XO-CHIP.
+const CHIP8_MAX_SIZE: u64 = 3232;
+
+/// The number of macro expansions after which compilation fails, to stop macros that expand themselves endlessly.
+const MAX_EXPANSIONS: usize = 100_000;
+
+/// The binary operators of `:calc` expressions.
+const BINARY_OPERATORS: [&str; 19] = ["+", "-", "*", "/", "%", "&", "|", "^", "<<", ">>", "pow", "min", "max", "<", "<=", ">", ">=", "==", "!="];
+
+/// The contents of an Octo cartridge.
+pub struct OctoCart {
+    /// The Octo source code of the program.
+    pub source: String,
+    /// The quirk settings stored in the cartridge's options.
+    pub quirks: Quirks,
+    /// The number of instructions executed per 60 Hz frame, if the cartridge specifies it.
+    pub tickrate: Option<u32>,
+    /// The variant to run the program as: the CHIP-8 if the cartridge limits the program to the memory of the original CHIP-8, the XO-CHIP
+    /// (which Octo emulates) otherwise.
+    pub variant: Variant,
+}
+
+impl OctoCart {
+
+    /// Loads and decodes the cartridge stored in the GIF file at the given path.
+    ///
+    /// # Errors
+    /// Returns an error of kind `InvalidData` if the file is not a GIF image, or if it does not contain a valid cartridge payload.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let cart = OctoCart::load("game.gif")?;
+    /// println!("{}", cart.source);
+    /// ```
+    pub fn load<P>(path: P) -> io::Result<OctoCart>
+        where P: AsRef<Path>, {
+            OctoCart::from_bytes(&fs::read(path)?)
+    }
+
+    /// Decodes the cartridge stored in the bytes of a GIF file.
+    ///
+    /// # Errors
+    /// Returns an error of kind `InvalidData` if the bytes are not a GIF image, or if it does not contain a valid cartridge payload.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<OctoCart> {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(bytes).map_err(invalid)?;
+
+        let mut pixels = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().map_err(invalid)? {
+            pixels.extend_from_slice(&frame.buffer);
+        }
+        OctoCart::decode(&pixels)
+    }
+
+    /// Compiles the source code of the cartridge into the bytes of a program starting at `PROGRAM_START`, see `compile(...)`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let cart = OctoCart::load("game.gif")?;
+    /// let program = Program::from_bytes(cart.compile()?);
+    /// ```
+    pub fn compile(&self) -> Result<Vec<u8>, OctoError> {
+        compile(&self.source)
+    }
+
+    /// Applies the settings of the cartridge to the given configuration: the variant, the quirks and the tick rate.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut config = Config::new();
+    /// OctoCart::load("game.gif")?.apply(&mut config);
+    /// ```
+    pub fn apply(&self, config: &mut Config) {
+        config.variant = self.variant;
+        config.quirks = self.quirks;
+        if let Some(tickrate) = self.tickrate {
+            config.loop_frequency = (tickrate.clamp(1, (u16::MAX / 60) as u32) * 60) as u16;
+        }
+    }
+
+    /// Decodes a cartridge from the palette indices of all pixels of the cartridge image.
+    fn decode(pixels: &[u8]) -> io::Result<OctoCart> {
+        let bytes: Vec<u8> = pixels.chunks_exact(4)
+            .map(|p| (p[0] & 3) << 6 | (p[1] & 3) << 4 | (p[2] & 3) << 2 | (p[3] & 3))
+            .collect();
+        if bytes.len() < 4 {
+            return Err(invalid("image is too small to contain a cartridge"));
+        }
+        let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        let payload = bytes.get(4..4 + length).ok_or_else(|| invalid("cartridge payload is truncated"))?;
+        let payload: Value = serde_json::from_slice(payload).map_err(invalid)?;
+
+        let source = payload["program"].as_str().ok_or_else(|| invalid("cartridge does not contain a program"))?.to_string();
+        let options = &payload["options"];
+        Ok(OctoCart {
+            source,
+            quirks: apply_octo_options(Quirks::new(), options),
+            tickrate: options["tickrate"].as_u64().map(|t| t as u32),
+            variant: match options["maxSize"].as_u64() {
+                Some(size) if size <= CHIP8_MAX_SIZE => Variant::Chip8,
+                _ => Variant::XoChip,
+            },
+        })
+    }
+}
+
+/// Applies the quirk settings of an Octo options object (as used in cartridges and the CHIP-8 Archive) to a set of quirks.
+///
+/// Settings that are missing from the options object keep the value they have in `quirks`.
+///
+/// # Example
+/// ```
+/// # use chip8::octo::apply_octo_options;
+/// # use chip8::quirks::Quirks;
+/// let options = serde_json::json!({ "shiftQuirks": true, "clipQuirks": false });
+/// let quirks = apply_octo_options(Quirks::new(), &options);
+/// assert!(!quirks.clip_sprites);
+/// ```
+pub fn apply_octo_options(mut quirks: Quirks, options: &Value) -> Quirks {
+    let flag = |name: &str| options[name].as_bool();
+    if let Some(shift) = flag("shiftQuirks") {
+        quirks.shift_uses_vy = !shift;
+    }
+    if let Some(load_store) = flag("loadStoreQuirks") {
+        quirks.load_store_increments_i = !load_store;
+    }
+    if let Some(jump) = flag("jumpQuirks") {
+        quirks.jump_uses_vx = jump;
+    }
+    if let Some(logic) = flag("logicQuirks") {
+        quirks.vf_reset = logic;
+    }
+    if let Some(clip) = flag("clipQuirks") {
+        quirks.clip_sprites = clip;
+    }
+    quirks
+}
+
+/// Wraps any error into an `io::Error` of kind `InvalidData`.
+fn invalid<E>(error: E) -> io::Error
+    where E: Into<Box<dyn std::error::Error + Send + Sync>>, {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Describes why Octo source code could not be compiled.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OctoError {
+    pub message: String,
+}
+
+impl std::fmt::Display for OctoError {
+
+    /// Formats the `OctoError` as its message.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for OctoError {}
+
+/// Compiles Octo source code into the bytes of a program starting at `PROGRAM_START`.
+///
+/// The program starts with a jump to the label `main`. Errors are reported with the number of the line they occurred in (starting at 1).
+///
+/// # Example
+/// ```
+/// # use chip8::octo::compile;
+/// assert_eq!(compile(": main v0 := 5 loop again"), Ok(vec![0x12, 0x02, 0x60, 0x05, 0x12, 0x04]));
+/// ```
+///
+/// Constants, `:calc` expressions and macros are evaluated at compile time, and `:unpack` loads the address of a label into `v0` and `v1`:
+/// ```
+/// # use chip8::octo::compile;
+/// let source = ": main
+///     :const SPEED 3
+///     :calc FAST { SPEED * 2 }
+///     :macro bump REG { REG += FAST }
+///     bump v2
+///     :unpack 0xA data
+///     i := data
+///     loop again
+///     : data 0xF0 0x90";
+/// let expected = vec![0x12, 0x02, 0x72, 0x06, 0x60, 0xA2, 0x61, 0x0C, 0xA2, 0x0C, 0x12, 0x0A, 0xF0, 0x90];
+/// assert_eq!(compile(source), Ok(expected));
+/// ```
+///
+/// The comparisons `<`, `<=`, `>` and `>=` are compiled into a subtraction in `vF` (the `compare-temp` alias) and a check of its flag:
+/// ```
+/// # use chip8::octo::compile;
+/// # use chip8::program::Program;
+/// # use chip8::system::System;
+/// // v1 to v4 are set if v0 < 5, v0 <= 5, v0 > 5 and v0 >= 5, comparing with a constant and with a register
+/// for operand in ["5", "v5"] {
+///     for (value, expected) in [(4, [1, 1, 0, 0]), (5, [0, 1, 0, 1]), (6, [0, 0, 1, 1])] {
+///         let source = format!(": main v0 := {} v5 := 5 if v0 < {operand} then v1 := 1 if v0 <= {operand} then v2 := 1 \
+///                               if v0 > {operand} then v3 := 1 if v0 >= {operand} then v4 := 1 loop again", value);
+///         let mut sys = System::new();
+///         sys.load(Program::from_bytes(compile(&source)?))?;
+///         for _ in 0..30 {
+///             sys.step();
+///         }
+///         assert_eq!([1, 2, 3, 4].map(|register| sys.registers.get(register)), expected, "v0 = {}, operand {}", value, operand);
+///     }
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// `if ... begin ... else ... end` runs exactly one of its branches, and `loop ... while ... again` leaves the loop once the condition is false:
+/// ```
+/// # use chip8::octo::compile;
+/// # use chip8::program::Program;
+/// # use chip8::system::System;
+/// let source = ": main
+///     loop
+///         v0 += 1
+///         if v0 == 3 begin v1 += 1 else v2 += 1 end
+///         while v0 != 5
+///     again
+///     v3 := 1
+///     loop again";
+/// let mut sys = System::new();
+/// sys.load(Program::from_bytes(compile(source)?))?;
+/// for _ in 0..100 {
+///     sys.step();
+/// }
+/// assert_eq!([0, 1, 2, 3].map(|register| sys.registers.get(register)), [5, 1, 4, 1]);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn compile(source: &str) -> Result<Vec<u8>, OctoError> {
+    let mut compiler = Compiler::new(source);
+    compiler.compile().map_err(|message| OctoError { message: format!("line {}: {}", compiler.line, message) })
+}
+
+/// A token of Octo source code, with the line it appears in.
+#[derive(Clone)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+/// Splits Octo source code into tokens separated by whitespace, dropping comments (from `#` to the end of the line). Strings in double quotes are
+/// kept as single tokens.
+fn tokenize(source: &str) -> VecDeque<Token> {
+    let mut tokens = VecDeque::new();
+    for (idx, line) in source.lines().enumerate() {
+        let mut text = String::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if text.is_empty() && c == '#' {
+                break;
+            }
+            if text.is_empty() && c == '"' {
+                text.push(c);
+                for c in chars.by_ref() {
+                    text.push(c);
+                    if c == '"' {
+                        break;
+                    }
+                }
+            }
+            else if !c.is_whitespace() {
+                text.push(c);
+                continue;
+            }
+            if !text.is_empty() {
+                tokens.push_back(Token { text: std::mem::take(&mut text), line: idx + 1 });
+            }
+        }
+        if !text.is_empty() {
+            tokens.push_back(Token { text, line: idx + 1 });
+        }
+    }
+    tokens
+}
+
+/// Parses a number in decimal, hexadecimal (with a `0x` prefix) or binary (with a `0b` prefix) notation, optionally preceded by a `-`.
+fn parse_number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16).ok()? as f64
+    }
+    else if let Some(binary) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        u32::from_str_radix(binary, 2).ok()? as f64
+    }
+    else if digits.starts_with(|c: char| c.is_ascii_digit()) && digits.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        digits.parse().ok()?
+    }
+    else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Checks whether a token can be the name of a label, constant, alias or macro.
+fn is_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// How the address of a label that is used before its definition is filled in once it is known.
+#[derive(Copy, Clone)]
+enum Fixup {
+    /// The lower 12 bits of an instruction.
+    Address,
+    /// A 16-bit word, e.g. of `i := long`.
+    Long,
+    /// The operands of the two `6XNN` instructions of `:unpack`, with the given upper nibble.
+    Unpack(u8),
+    /// The operands of the two `6XNN` instructions of `:unpack long`.
+    UnpackLong,
+}
+
+/// A use of a label before its definition.
+struct Reference {
+    address: usize,
+    fixup: Fixup,
+    label: String,
+    line: usize,
+}
+
+/// A macro defined with `:macro`.
+#[derive(Clone)]
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<Token>,
+}
+
+/// A `loop` that has not been closed by `again` yet.
+struct Loop {
+    start: usize,
+    /// The addresses of the jumps of its `while` statements, which are filled in with the address after the loop.
+    exits: Vec<usize>,
+}
+
+/// The state of compiling a single source file.
+struct Compiler {
+    tokens: VecDeque<Token>,
+    /// The line of the token that is compiled.
+    line: usize,
+    memory: Vec<u8>,
+    /// The address at which the next byte is emitted.
+    here: usize,
+    /// The address after the last byte that was emitted.
+    end: usize,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    references: Vec<Reference>,
+    loops: Vec<Loop>,
+    /// The addresses of the jumps of `begin` and `else`, which are filled in by the matching `else` or `end`.
+    branches: Vec<usize>,
+    expansions: usize,
+}
+
+impl Compiler {
+
+    /// Creates a compiler for the given source code.
+    fn new(source: &str) -> Compiler {
+        Compiler {
+            tokens: tokenize(source),
+            line: 1,
+            memory: vec![0; 0x10000],
+            here: PROGRAM_START as usize,
+            end: PROGRAM_START as usize,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            references: Vec::new(),
+            loops: Vec::new(),
+            branches: Vec::new(),
+            expansions: 0,
+        }
+    }
+
+    /// Compiles all statements, resolves the labels used before their definition and returns the program.
+    fn compile(&mut self) -> Result<Vec<u8>, String> {
+        // Replaced by the jump to `main` at the end
+        self.instruction(0x1000)?;
+        while let Some(token) = self.tokens.pop_front() {
+            self.line = token.line;
+            self.statement(token.text)?;
+        }
+        if !self.loops.is_empty() {
+            return Err("'loop' without 'again'".to_string());
+        }
+        if !self.branches.is_empty() {
+            return Err("'begin' without 'end'".to_string());
+        }
+        for reference in std::mem::take(&mut self.references) {
+            self.line = reference.line;
+            let address = *self.labels.get(&reference.label).ok_or_else(|| format!("undefined name '{}'", reference.label))? as usize;
+            let at = reference.address;
+            match reference.fixup {
+                Fixup::Address => self.patch(at, address)?,
+                Fixup::Long => self.memory[at..at + 2].copy_from_slice(&(address as u16).to_be_bytes()),
+                Fixup::Unpack(nibble) => {
+                    self.check_address(address, 0xFFF)?;
+                    self.memory[at + 1] = nibble << 4 | (address >> 8) as u8;
+                    self.memory[at + 3] = address as u8;
+                },
+                Fixup::UnpackLong => {
+                    self.memory[at + 1] = (address >> 8) as u8;
+                    self.memory[at + 3] = address as u8;
+                },
+            }
+        }
+        let main = *self.labels.get("main").ok_or("the program has no 'main' label")? as usize;
+        self.patch(PROGRAM_START as usize, main)?;
+        Ok(self.memory[PROGRAM_START as usize..self.end].to_vec())
+    }
+
+    /// Compiles a single statement starting with the given token.
+    fn statement(&mut self, token: String) -> Result<(), String> {
+        if let Some(x) = self.register(&token) {
+            return self.assignment(x);
+        }
+        match token.as_str() {
+            ":" => {
+                let name = self.next()?;
+                self.define_label(name, self.here)?;
+            },
+            ":next" => {
+                let name = self.next()?;
+                self.define_label(name, self.here + 1)?;
+            },
+            ":const" => {
+                let name = self.name()?;
+                let value = self.value()?;
+                if self.constants.insert(name.clone(), value).is_some() || self.labels.contains_key(&name) {
+                    return Err(format!("'{}' is already defined", name));
+                }
+            },
+            ":calc" => {
+                let name = self.name()?;
+                self.expect("{")?;
+                let value = self.calc_block()?;
+                if self.labels.contains_key(&name) {
+                    return Err(format!("'{}' is already defined", name));
+                }
+                self.constants.insert(name, value);
+            },
+            ":alias" => {
+                let name = self.name()?;
+                let token = self.next()?;
+                let register = match self.register(&token) {
+                    Some(register) => register,
+                    None => self.integer(&token, 0, 15)? as u8,
+                };
+                self.aliases.insert(name, register);
+            },
+            ":unpack" => {
+                let (hi, lo) = (self.alias("unpack-hi", 0x0), self.alias("unpack-lo", 0x1));
+                let token = self.next()?;
+                let (hi_byte, lo_byte) = if token == "long" {
+                    let target = self.next()?;
+                    let address = self.address(&target, 0xFFFF, Fixup::UnpackLong)?;
+                    ((address >> 8) as u8, address as u8)
+                }
+                else {
+                    let nibble = self.integer(&token, 0, 15)? as u8;
+                    let target = self.next()?;
+                    let address = self.address(&target, 0xFFF, Fixup::Unpack(nibble))?;
+                    (nibble << 4 | (address >> 8) as u8, address as u8)
+                };
+                self.instruction(0x6000 | (hi as u16) << 8 | hi_byte as u16)?;
+                self.instruction(0x6000 | (lo as u16) << 8 | lo_byte as u16)?;
+            },
+            ":org" => {
+                let token = self.next()?;
+                self.here = self.integer(&token, PROGRAM_START as i64, 0xFFFF)? as usize;
+            },
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit(byte)?;
+            },
+            ":pointer" => {
+                let token = self.next()?;
+                let address = self.address(&token, 0xFFFF, Fixup::Long)?;
+                self.instruction(address)?;
+            },
+            ":call" => self.address_instruction(0x2000)?,
+            ":macro" => self.define_macro()?,
+            ":breakpoint" => {
+                self.next()?;
+            },
+            ":monitor" => {
+                self.skip_operand()?;
+                self.skip_operand()?;
+            },
+            ":assert" => {
+                let mut message = "assertion failed".to_string();
+                if self.tokens.front().is_some_and(|token| token.text.starts_with('"')) {
+                    message = self.next()?.trim_matches('"').to_string();
+                }
+                self.expect("{")?;
+                if self.calc_block()? == 0.0 {
+                    return Err(message);
+                }
+            },
+            ":stringmode" => return Err("':stringmode' is not supported".to_string()),
+            ";" | "return" => self.instruction(0x00EE)?,
+            "clear" => self.instruction(0x00E0)?,
+            "scroll-right" => self.instruction(0x00FB)?,
+            "scroll-left" => self.instruction(0x00FC)?,
+            "exit" => self.instruction(0x00FD)?,
+            "lores" => self.instruction(0x00FE)?,
+            "hires" => self.instruction(0x00FF)?,
+            "audio" => self.instruction(0xF002)?,
+            "scroll-down" | "scroll-up" => {
+                let operand = self.next()?;
+                let rows = self.integer(&operand, 0, 15)? as u16;
+                self.instruction(if token == "scroll-down" { 0x00C0 } else { 0x00D0 } | rows)?;
+            },
+            "plane" => {
+                let token = self.next()?;
+                let planes = self.integer(&token, 0, 15)? as u16;
+                self.instruction(0xF001 | planes << 8)?;
+            },
+            "bcd" => self.register_instruction(0xF033)?,
+            "saveflags" => self.register_instruction(0xF075)?,
+            "loadflags" => self.register_instruction(0xF085)?,
+            "save" | "load" => {
+                let x = self.next_register()? as u16;
+                let store = token == "save";
+                if self.tokens.front().is_some_and(|token| token.text == "-") {
+                    self.next()?;
+                    let y = self.next_register()? as u16;
+                    self.instruction(if store { 0x5002 } else { 0x5003 } | x << 8 | y << 4)?;
+                }
+                else {
+                    self.instruction(if store { 0xF055 } else { 0xF065 } | x << 8)?;
+                }
+            },
+            "sprite" => {
+                let x = self.next_register()? as u16;
+                let y = self.next_register()? as u16;
+                let token = self.next()?;
+                let rows = self.integer(&token, 0, 15)? as u16;
+                self.instruction(0xD000 | x << 8 | y << 4 | rows)?;
+            },
+            "jump" => self.address_instruction(0x1000)?,
+            "jump0" => self.address_instruction(0xB000)?,
+            "native" => self.address_instruction(0x0000)?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                self.register_instruction(match token.as_str() {
+                    "delay" => 0xF015,
+                    "buzzer" => 0xF018,
+                    _ => 0xF03A,
+                })?;
+            },
+            "i" => {
+                let operator = self.next()?;
+                let operand = self.next()?;
+                match (operator.as_str(), operand.as_str()) {
+                    (":=", "hex") => self.register_instruction(0xF029)?,
+                    (":=", "bighex") => self.register_instruction(0xF030)?,
+                    (":=", "long") => {
+                        self.instruction(0xF000)?;
+                        let token = self.next()?;
+                        let address = self.address(&token, 0xFFFF, Fixup::Long)?;
+                        self.instruction(address)?;
+                    },
+                    (":=", _) => {
+                        let address = self.address(&operand, 0xFFF, Fixup::Address)?;
+                        self.instruction(0xA000 | address)?;
+                    },
+                    ("+=", _) => {
+                        let x = self.register(&operand).ok_or_else(|| format!("expected a register but found '{}'", operand))? as u16;
+                        self.instruction(0xF01E | x << 8)?;
+                    },
+                    _ => return Err(format!("unknown operator '{}' for i", operator)),
+                }
+            },
+            "if" => {
+                let (prelude, when_false, when_true) = self.condition()?;
+                for opcode in prelude {
+                    self.instruction(opcode)?;
+                }
+                match self.next()?.as_str() {
+                    "then" => self.instruction(when_false)?,
+                    "begin" => {
+                        self.instruction(when_true)?;
+                        self.branches.push(self.here);
+                        self.instruction(0x1000)?;
+                    },
+                    other => return Err(format!("expected 'then' or 'begin' but found '{}'", other)),
+                }
+            },
+            "else" => {
+                let branch = self.branches.pop().ok_or("'else' without 'begin'")?;
+                let jump = self.here;
+                self.instruction(0x1000)?;
+                self.patch(branch, self.here)?;
+                self.branches.push(jump);
+            },
+            "end" => {
+                let branch = self.branches.pop().ok_or("'end' without 'begin'")?;
+                self.patch(branch, self.here)?;
+            },
+            "loop" => self.loops.push(Loop { start: self.here, exits: Vec::new() }),
+            "while" => {
+                if self.loops.is_empty() {
+                    return Err("'while' outside of a loop".to_string());
+                }
+                let (prelude, _, when_true) = self.condition()?;
+                for opcode in prelude {
+                    self.instruction(opcode)?;
+                }
+                self.instruction(when_true)?;
+                let exit = self.here;
+                if let Some(current) = self.loops.last_mut() {
+                    current.exits.push(exit);
+                }
+                self.instruction(0x1000)?;
+            },
+            "again" => {
+                let finished = self.loops.pop().ok_or("'again' without 'loop'")?;
+                self.instruction(0x1000)?;
+                self.patch(self.here - 2, finished.start)?;
+                for exit in finished.exits {
+                    self.patch(exit, self.here)?;
+                }
+            },
+            _ if self.macros.contains_key(&token) => self.expand(&token)?,
+            _ if !self.labels.contains_key(&token) && self.lookup(&token).is_some() => {
+                let byte = self.byte_of(&token)?;
+                self.emit(byte)?;
+            },
+            _ if is_name(&token) => {
+                let address = self.address(&token, 0xFFF, Fixup::Address)?;
+                self.instruction(0x2000 | address)?;
+            },
+            _ => return Err(format!("unexpected '{}'", token)),
+        }
+        Ok(())
+    }
+
+    /// Compiles an assignment to the register `VX`, e.g. `v0 += v1` or `v0 := random 0xFF`.
+    fn assignment(&mut self, x: u8) -> Result<(), String> {
+        let operator = self.next()?;
+        let operand = self.next()?;
+        let x = (x as u16) << 8;
+        let opcode = if let Some(y) = self.register(&operand) {
+            let kind = match operator.as_str() {
+                ":=" => 0x0,
+                "|=" => 0x1,
+                "&=" => 0x2,
+                "^=" => 0x3,
+                "+=" => 0x4,
+                "-=" => 0x5,
+                ">>=" => 0x6,
+                "=-" => 0x7,
+                "<<=" => 0xE,
+                _ => return Err(format!("unknown operator '{}'", operator)),
+            };
+            0x8000 | x | (y as u16) << 4 | kind
+        }
+        else {
+            match (operator.as_str(), operand.as_str()) {
+                (":=", "key") => 0xF00A | x,
+                (":=", "delay") => 0xF007 | x,
+                (":=", "random") => 0xC000 | x | self.byte()? as u16,
+                (":=", _) => 0x6000 | x | self.byte_of(&operand)? as u16,
+                ("+=", _) => 0x7000 | x | self.byte_of(&operand)? as u16,
+                ("-=", _) => 0x7000 | x | self.byte_of(&operand)?.wrapping_neg() as u16,
+                _ => return Err(format!("operator '{}' expects a register but found '{}'", operator, operand)),
+            }
+        };
+        self.instruction(opcode)
+    }
+
+    /// Compiles the condition of an `if` or `while` statement. Returns the instructions that have to be executed before the condition can be
+    /// checked, an instruction that skips the next one if the condition is false and one that skips it if the condition is true.
+    fn condition(&mut self) -> Result<(Vec<u16>, u16, u16), String> {
+        let x = self.next_register()? as u16;
+        let operator = self.next()?;
+        match operator.as_str() {
+            "key" => return Ok((Vec::new(), 0xE0A1 | x << 8, 0xE09E | x << 8)),
+            "-key" => return Ok((Vec::new(), 0xE09E | x << 8, 0xE0A1 | x << 8)),
+            _ => {},
+        }
+        let operand = self.next()?;
+        let register = self.register(&operand);
+        let byte = match register {
+            Some(_) => 0,
+            None => self.byte_of(&operand)? as u16,
+        };
+        let condition = match (operator.as_str(), register) {
+            ("==", Some(y)) => (Vec::new(), 0x9000 | x << 8 | (y as u16) << 4, 0x5000 | x << 8 | (y as u16) << 4),
+            ("==", None) => (Vec::new(), 0x4000 | x << 8 | byte, 0x3000 | x << 8 | byte),
+            ("!=", Some(y)) => (Vec::new(), 0x5000 | x << 8 | (y as u16) << 4, 0x9000 | x << 8 | (y as u16) << 4),
+            ("!=", None) => (Vec::new(), 0x3000 | x << 8 | byte, 0x4000 | x << 8 | byte),
+            ("<" | ">" | "<=" | ">=", _) => {
+                let temp = (self.alias("compare-temp", 0xF) as u16) << 8;
+                let load = match register {
+                    Some(y) => 0x8000 | temp | (y as u16) << 4,
+                    None => 0x6000 | temp | byte,
+                };
+                // VF is cleared on a borrow: VX - operand borrows if VX < operand (so VF is set if VX >= operand), operand - VX borrows if
+                // VX > operand (so VF is set if VX <= operand)
+                let subtract = match operator.as_str() {
+                    "<" | ">=" => 0x8007 | temp | x << 4,
+                    _ => 0x8005 | temp | x << 4,
+                };
+                let flag = if operator.ends_with('=') { 1 } else { 0 };
+                (vec![load, subtract], 0x3F00 | (1 - flag), 0x3F00 | flag)
+            },
+            _ => return Err(format!("unknown comparison '{}'", operator)),
+        };
+        Ok(condition)
+    }
+
+    /// Parses the definition of a macro after `:macro`: its name, its parameters and its body in braces.
+    fn define_macro(&mut self) -> Result<(), String> {
+        let name = self.name()?;
+        let mut parameters = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token == "{" {
+                break;
+            }
+            parameters.push(token);
+        }
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let token = self.tokens.pop_front().ok_or_else(|| format!("macro '{}' is not terminated by '}}'", name))?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {},
+            }
+            if depth == 0 {
+                break;
+            }
+            body.push(token);
+        }
+        self.macros.insert(name, Macro { parameters, body });
+        Ok(())
+    }
+
+    /// Expands a macro, taking as many tokens as arguments as it has parameters, and compiles its body next.
+    fn expand(&mut self, name: &str) -> Result<(), String> {
+        let definition = self.macros[name].clone();
+        let arguments = definition.parameters.iter().map(|_| self.next()).collect::<Result<Vec<_>, _>>()?;
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(format!("too many macro expansions, does '{}' expand itself?", name));
+        }
+        for token in definition.body.iter().rev() {
+            let text = match definition.parameters.iter().position(|parameter| *parameter == token.text) {
+                Some(idx) => arguments[idx].clone(),
+                None => token.text.clone(),
+            };
+            self.tokens.push_front(Token { text, line: self.line });
+        }
+        Ok(())
+    }
+
+    /// Evaluates the expression of a `:calc` block up to and including its closing brace.
+    fn calc_block(&mut self) -> Result<f64, String> {
+        let value = self.calc()?;
+        self.expect("}")?;
+        Ok(value)
+    }
+
+    /// Evaluates an expression. As in Octo, binary operators have no precedence and are evaluated from right to left.
+    fn calc(&mut self) -> Result<f64, String> {
+        let left = self.calc_term()?;
+        let Some(operator) = self.tokens.front().map(|token| token.text.clone()).filter(|op| BINARY_OPERATORS.contains(&op.as_str())) else {
+            return Ok(left);
+        };
+        self.next()?;
+        let right = self.calc()?;
+        let int = |value: f64| value as i64;
+        let value = match operator.as_str() {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" | "%" if right == 0.0 => return Err("division by zero".to_string()),
+            "/" => left / right,
+            "%" => left % right,
+            "&" => (int(left) & int(right)) as f64,
+            "|" => (int(left) | int(right)) as f64,
+            "^" => (int(left) ^ int(right)) as f64,
+            "<<" | ">>" if !(0.0..64.0).contains(&right) => return Err(format!("invalid shift amount {}", right)),
+            "<<" => (int(left) << int(right)) as f64,
+            ">>" => (int(left) >> int(right)) as f64,
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "<" => (left < right) as u8 as f64,
+            "<=" => (left <= right) as u8 as f64,
+            ">" => (left > right) as u8 as f64,
+            ">=" => (left >= right) as u8 as f64,
+            "==" => (left == right) as u8 as f64,
+            _ => (left != right) as u8 as f64,
+        };
+        Ok(value)
+    }
+
+    /// Evaluates a term of an expression: a number, a name, a parenthesized expression or a unary operator applied to a term.
+    fn calc_term(&mut self) -> Result<f64, String> {
+        let token = self.next()?;
+        let value = match token.as_str() {
+            "(" => {
+                let value = self.calc()?;
+                self.expect(")")?;
+                value
+            },
+            "-" => -self.calc_term()?,
+            "~" => !(self.calc_term()? as i64) as f64,
+            "!" => (self.calc_term()? == 0.0) as u8 as f64,
+            "abs" => self.calc_term()?.abs(),
+            "sqrt" => self.calc_term()?.sqrt(),
+            "sin" => self.calc_term()?.sin(),
+            "cos" => self.calc_term()?.cos(),
+            "tan" => self.calc_term()?.tan(),
+            "exp" => self.calc_term()?.exp(),
+            "log" => self.calc_term()?.ln(),
+            "ceil" => self.calc_term()?.ceil(),
+            "floor" => self.calc_term()?.floor(),
+            "sign" => {
+                let value = self.calc_term()?;
+                if value == 0.0 { 0.0 } else { value.signum() }
+            },
+            "@" => {
+                let address = self.calc_term()? as i64;
+                *self.memory.get(address as usize).filter(|_| address >= 0).ok_or_else(|| format!("address {} is out of range", address))? as f64
+            },
+            "HERE" => self.here as f64,
+            "PI" => std::f64::consts::PI,
+            "E" => std::f64::consts::E,
+            _ => self.lookup(&token).ok_or_else(|| format!("undefined name '{}'", token))?,
+        };
+        Ok(value)
+    }
+
+    /// Gets the next token.
+    fn next(&mut self) -> Result<String, String> {
+        let token = self.tokens.pop_front().ok_or("unexpected end of the source code")?;
+        self.line = token.line;
+        Ok(token.text)
+    }
+
+    /// Consumes the next token, which has to be the given one.
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token != expected {
+            return Err(format!("expected '{}' but found '{}'", expected, token));
+        }
+        Ok(())
+    }
+
+    /// Gets the next token, which has to be a valid name.
+    fn name(&mut self) -> Result<String, String> {
+        let token = self.next()?;
+        if !is_name(&token) || self.register(&token).is_some() {
+            return Err(format!("invalid name '{}'", token));
+        }
+        Ok(token)
+    }
+
+    /// Skips an operand that is not used by the emulator: a single token or an expression in braces.
+    fn skip_operand(&mut self) -> Result<(), String> {
+        if self.next()? == "{" {
+            self.calc_block()?;
+        }
+        Ok(())
+    }
+
+    /// Gets the register a token refers to: `v0` to `vF`, or an alias.
+    fn register(&self, token: &str) -> Option<u8> {
+        if let Some(register) = self.aliases.get(token) {
+            return Some(*register);
+        }
+        match token.as_bytes() {
+            [b'v' | b'V', digit] => (*digit as char).to_digit(16).map(|digit| digit as u8),
+            _ => None,
+        }
+    }
+
+    /// Gets the register with the given alias, or the given default register if it has not been aliased.
+    fn alias(&self, name: &str, default: u8) -> u8 {
+        self.aliases.get(name).copied().unwrap_or(default)
+    }
+
+    /// Gets the next token, which has to refer to a register.
+    fn next_register(&mut self) -> Result<u8, String> {
+        let token = self.next()?;
+        self.register(&token).ok_or_else(|| format!("expected a register but found '{}'", token))
+    }
+
+    /// Gets the value of a number, a constant or a label.
+    fn lookup(&self, token: &str) -> Option<f64> {
+        parse_number(token)
+            .or_else(|| self.constants.get(token).copied())
+            .or_else(|| self.labels.get(token).map(|address| *address as f64))
+    }
+
+    /// Gets the value of a token, evaluating the expression that follows if it is an opening brace.
+    fn value_of(&mut self, token: &str) -> Result<f64, String> {
+        if token == "{" {
+            return self.calc_block();
+        }
+        self.lookup(token).ok_or_else(|| format!("undefined name '{}'", token))
+    }
+
+    /// Gets the value of the next token, see `value_of(...)`.
+    fn value(&mut self) -> Result<f64, String> {
+        let token = self.next()?;
+        self.value_of(&token)
+    }
+
+    /// Gets the value of a token as an integer between `min` and `max`.
+    fn integer(&mut self, token: &str, min: i64, max: i64) -> Result<i64, String> {
+        let value = self.value_of(token)?.floor() as i64;
+        if value < min || value > max {
+            return Err(format!("value {} is out of range, expected {} to {}", value, min, max));
+        }
+        Ok(value)
+    }
+
+    /// Gets the value of a token as a byte. Negative values down to -128 are stored in two's complement.
+    fn byte_of(&mut self, token: &str) -> Result<u8, String> {
+        Ok(self.integer(token, -128, 255)? as u8)
+    }
+
+    /// Gets the value of the next token as a byte, see `byte_of(...)`.
+    fn byte(&mut self) -> Result<u8, String> {
+        let token = self.next()?;
+        self.byte_of(&token)
+    }
+
+    /// Gets the address a token refers to. Labels that are not defined yet are resolved at the end, with the given fixup applied at the current
+    /// address.
+    fn address(&mut self, token: &str, max: i64, fixup: Fixup) -> Result<u16, String> {
+        if token != "{" && self.lookup(token).is_none() && is_name(token) && self.register(token).is_none() {
+            self.references.push(Reference { address: self.here, fixup, label: token.to_string(), line: self.line });
+            return Ok(0);
+        }
+        Ok(self.integer(token, 0, max)? as u16)
+    }
+
+    /// Compiles an instruction with a 12-bit address operand, e.g. `jump`.
+    fn address_instruction(&mut self, opcode: u16) -> Result<(), String> {
+        let token = self.next()?;
+        let address = self.address(&token, 0xFFF, Fixup::Address)?;
+        self.instruction(opcode | address)
+    }
+
+    /// Compiles an instruction with a register operand `X`, e.g. `bcd`.
+    fn register_instruction(&mut self, opcode: u16) -> Result<(), String> {
+        let x = self.next_register()? as u16;
+        self.instruction(opcode | x << 8)
+    }
+
+    /// Defines a label with the given address.
+    fn define_label(&mut self, name: String, address: usize) -> Result<(), String> {
+        if !is_name(&name) || self.register(&name).is_some() {
+            return Err(format!("invalid label '{}'", name));
+        }
+        if self.constants.contains_key(&name) || self.labels.insert(name.clone(), address as u16).is_some() {
+            return Err(format!("'{}' is already defined", name));
+        }
+        Ok(())
+    }
+
+    /// Checks that an address fits into the 12-bit operand of an instruction.
+    fn check_address(&self, address: usize, max: usize) -> Result<(), String> {
+        if address > max {
+            return Err(format!("address {:#X} is out of range, expected at most {:#X}", address, max));
+        }
+        Ok(())
+    }
+
+    /// Fills in the 12-bit address operand of the instruction at the given address.
+    fn patch(&mut self, at: usize, address: usize) -> Result<(), String> {
+        self.check_address(address, 0xFFF)?;
+        self.memory[at] = (self.memory[at] & 0xF0) | (address >> 8) as u8;
+        self.memory[at + 1] = address as u8;
+        Ok(())
+    }
+
+    /// Emits a single byte at the current address.
+    fn emit(&mut self, byte: u8) -> Result<(), String> {
+        if self.here >= self.memory.len() {
+            return Err("the program does not fit into 64 KiB of memory".to_string());
+        }
+        self.memory[self.here] = byte;
+        self.here += 1;
+        self.end = self.end.max(self.here);
+        Ok(())
+    }
+
+    /// Emits a 16-bit instruction at the current address.
+    fn instruction(&mut self, opcode: u16) -> Result<(), String> {
+        let [high, low] = opcode.to_be_bytes();
+        self.emit(high)?;
+        self.emit(low)
+    }
+}
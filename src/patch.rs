@@ -0,0 +1,130 @@
+//! Contains the `Patch` struct, which describes modifications to a program's bytecode, e.g. to fix bugs or translate texts in a ROM.
+//!
+//! Two patch formats are supported:
+//! - A simple text format, consisting of `address=value` lines. Addresses are given as memory addresses (i.e. starting at 0x200), values as any number
+//!   of hexadecimal bytes. Empty lines and everything after a `#` are ignored.
+//! - The binary IPS format, which is widely used for ROM hacks.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::program::{Program, PROGRAM_START};
+
+/// The signature at the start of every IPS patch.
+const IPS_HEADER: &[u8] = b"PATCH";
+
+/// The marker at the end of every IPS patch.
+const IPS_FOOTER: &[u8] = b"EOF";
+
+/// A single modification of a program: the given bytes replace the bytes starting at `offset`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PatchRecord {
+    /// The offset from the start of the program (not the memory address) of the first modified byte.
+    pub offset: usize,
+    /// The new values of the modified bytes.
+    pub bytes: Vec<u8>,
+}
+
+/// A list of modifications that can be applied to a `Program` using `Program::apply_patch(...)`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Patch {
+    pub records: Vec<PatchRecord>,
+}
+
+impl Patch {
+
+    /// Loads a patch from the given file path, detecting its format automatically.
+    ///
+    /// Files starting with the IPS header `PATCH` are parsed as IPS patches, all other files as text patches.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let patch = Patch::load("fix.ips")?;
+    /// ```
+    pub fn load<P>(path: P) -> io::Result<Patch>
+        where P: AsRef<Path>, {
+            let bytes = fs::read(path)?;
+            if bytes.starts_with(IPS_HEADER) {
+                return Patch::from_ips(&bytes);
+            }
+            let text = String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Patch::from_text(&text)
+    }
+
+    /// Parses a patch in the text format.
+    ///
+    /// Each line has the form `address=value`, where `address` is a hexadecimal memory address (optionally prefixed with `0x`) and `value` consists of
+    /// one or more hexadecimal bytes, which may be separated by whitespace.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::patch::Patch;
+    /// let patch = Patch::from_text("0x204=6A03  # start with 3 lives\n0x300=00E0 00EE")?;
+    /// assert_eq!(patch.records[0].offset, 4);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_text(text: &str) -> io::Result<Patch> {
+        let mut records = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", idx + 1, message));
+
+            let (address, value) = line.split_once('=').ok_or_else(|| invalid("expected 'address=value'"))?;
+            let address = address.trim();
+            let address = u16::from_str_radix(address.strip_prefix("0x").unwrap_or(address), 16).map_err(|_| invalid("invalid address"))?;
+            if address < PROGRAM_START {
+                return Err(invalid("address lies before the start of the program"));
+            }
+            let value = value.trim();
+            let bytes = Program::from_hex_str(value.strip_prefix("0x").unwrap_or(value)).map_err(|e| invalid(&e.to_string()))?.instructions;
+            records.push(PatchRecord { offset: (address - PROGRAM_START) as usize, bytes });
+        }
+        Ok(Patch { records })
+    }
+
+    /// Parses a patch in the binary IPS format.
+    ///
+    /// Both regular and run-length encoded records are supported. An optional truncation offset after the `EOF` marker is ignored.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::patch::Patch;
+    /// let patch = Patch::from_ips(b"PATCH\x00\x00\x04\x00\x02\x6A\x03EOF")?;
+    /// assert_eq!(patch.records[0].bytes, vec![0x6A, 0x03]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_ips(bytes: &[u8]) -> io::Result<Patch> {
+        let truncated = || io::Error::new(io::ErrorKind::UnexpectedEof, "truncated IPS patch");
+        if !bytes.starts_with(IPS_HEADER) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "missing IPS header"));
+        }
+
+        let mut records = Vec::new();
+        let mut pos = IPS_HEADER.len();
+        loop {
+            let header = bytes.get(pos..pos + 3).ok_or_else(truncated)?;
+            if header == IPS_FOOTER {
+                break;
+            }
+            let offset = (header[0] as usize) << 16 | (header[1] as usize) << 8 | header[2] as usize;
+            let size = bytes.get(pos + 3..pos + 5).ok_or_else(truncated)?;
+            let size = (size[0] as usize) << 8 | size[1] as usize;
+            pos += 5;
+
+            if size > 0 {
+                records.push(PatchRecord { offset, bytes: bytes.get(pos..pos + size).ok_or_else(truncated)?.to_vec() });
+                pos += size;
+            }
+            else {
+                let run = bytes.get(pos..pos + 3).ok_or_else(truncated)?;
+                let length = (run[0] as usize) << 8 | run[1] as usize;
+                records.push(PatchRecord { offset, bytes: vec![run[2]; length] });
+                pos += 3;
+            }
+        }
+        Ok(Patch { records })
+    }
+}
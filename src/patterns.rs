@@ -0,0 +1,117 @@
+//! Generates small synthetic test-pattern ROMs with the `ProgramBuilder`, e.g. to check a new renderer or input backend without external files.
+
+use crate::builder::ProgramBuilder;
+use crate::program::Program;
+
+/// The names of the available test patterns, together with a short description of each of them.
+pub const PATTERNS: [(&str, &str); 4] = [
+    ("checkerboard", "fills the whole display with a checkerboard pattern"),
+    ("font", "draws the 16 built-in font sprites in two rows"),
+    ("keys", "waits for key presses and shows the hex digit of each pressed key"),
+    ("countdown", "counts down from 9 to 0 using the delay timer, then beeps"),
+];
+
+/// Generates the test pattern with the given name (see `PATTERNS`), or returns `None` if there is no such pattern.
+///
+/// All patterns end in an idle loop (a jump to itself) once they are done, except for `keys`, which runs forever.
+///
+/// # Example
+/// ```ignore
+/// let program = generate("checkerboard").unwrap();
+/// program.save("checkerboard.ch8")?;
+/// ```
+pub fn generate(name: &str) -> Option<Program> {
+    let mut builder = ProgramBuilder::new();
+    match name {
+        "checkerboard" => checkerboard(&mut builder),
+        "font" => font(&mut builder),
+        "keys" => keys(&mut builder),
+        "countdown" => countdown(&mut builder),
+        _ => return None,
+    };
+    Some(builder.build().expect("test patterns only refer to defined labels"))
+}
+
+/// Draws an 8x8 checkerboard tile at every position of the 64x32 display. V1 and V2 hold the position of the next tile.
+fn checkerboard(builder: &mut ProgramBuilder) -> &mut ProgramBuilder {
+    builder
+        .op(0x00E0)
+        .load_i("tile")
+        .op(0x6100)
+        .op(0x6200)
+        .label("draw")
+        .op(0xD128)
+        .op(0x7108)
+        .op(0x3140)
+        .jump("draw")
+        .op(0x6100)
+        .op(0x7208)
+        .op(0x3220)
+        .jump("draw")
+        .label("idle")
+        .jump("idle")
+        .label("tile")
+        .sprite(&[0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55])
+}
+
+/// Draws the font sprites of the digits `0`-`F` in two rows of eight. V0 holds the digit, V1 and V2 the position of its sprite.
+fn font(builder: &mut ProgramBuilder) -> &mut ProgramBuilder {
+    builder
+        .op(0x00E0)
+        .op(0x6000)
+        .op(0x6101)
+        .op(0x6201)
+        .label("draw")
+        .op(0xF029)
+        .op(0xD125)
+        .op(0x7001)
+        .op(0x7108)
+        .op(0x3141)
+        .jump("next")
+        .op(0x6101)
+        .op(0x7208)
+        .label("next")
+        .op(0x3010)
+        .jump("draw")
+        .label("idle")
+        .jump("idle")
+}
+
+/// Waits for a key press with `FX0A` and shows the digit of the pressed key in the middle of the display, forever.
+fn keys(builder: &mut ProgramBuilder) -> &mut ProgramBuilder {
+    builder
+        .op(0x00E0)
+        .op(0x611E)
+        .op(0x620D)
+        .label("wait")
+        .op(0xF00A)
+        .op(0x00E0)
+        .op(0xF029)
+        .op(0xD125)
+        .jump("wait")
+}
+
+/// Shows the digits from `9` down to `0`, each for 60 ticks of the delay timer, and starts the sound timer at the end. V0 holds the digit.
+fn countdown(builder: &mut ProgramBuilder) -> &mut ProgramBuilder {
+    builder
+        .op(0x6009)
+        .op(0x611E)
+        .op(0x620D)
+        .label("digit")
+        .op(0x00E0)
+        .op(0xF029)
+        .op(0xD125)
+        .op(0x633C)
+        .op(0xF315)
+        .label("wait")
+        .op(0xF307)
+        .op(0x3300)
+        .jump("wait")
+        .op(0x70FF)
+        .op(0x30FF)
+        .jump("digit")
+        .op(0x631E)
+        .op(0xF318)
+        .label("idle")
+        .jump("idle")
+}
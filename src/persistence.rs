@@ -0,0 +1,152 @@
+//! Contains the `PersistentRegion` struct, an address range of the CHIP-8 memory that is backed by a file on the host, so homebrew ROMs can keep
+//! settings and high scores between sessions by simply writing to that range.
+
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use crate::system::{Memory, FONT_END, FONT_START};
+
+/// An address range whose contents are loaded from a host file when a program starts, and written back to it with `flush(...)`.
+///
+/// If the file does not exist yet, or is shorter than the range, the remaining bytes are set to `0`. The file is only written when the contents of
+/// the range changed since they were last loaded or flushed.
+///
+/// # Example
+/// ```ignore
+/// let mut region = PersistentRegion::new("pong.sav", 0xE00..0xE10, 0x200..0x2F6, &sys.memory)?;
+/// region.restore(&mut sys.memory)?;
+/// while sys.step() {}
+/// region.flush(&sys.memory)?;
+/// ```
+pub struct PersistentRegion {
+    path: PathBuf,
+    range: Range<u16>,
+    saved: Vec<u8>,
+}
+
+impl PersistentRegion {
+
+    /// Creates a region for the given address range of `memory`, backed by the file at `path`. `program` is the address range of the loaded
+    /// program, which the region must not overwrite. Nothing is read or written yet.
+    ///
+    /// # Errors
+    /// Returns an error of kind `InvalidInput` if the range is empty, exceeds the main memory, or overlaps the font sprites, the display buffer
+    /// (at its current `DisplayLayout`, in all planes) or the program.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::persistence::PersistentRegion;
+    /// # use chip8::system::{DisplayLayout, Memory, XO_MEMORY_SIZE};
+    /// let mut memory = Memory::new();
+    /// memory.set_display_layout(DisplayLayout { start: 0xE00, width: 64, height: 32 });
+    /// assert!(PersistentRegion::new("game.sav", 0xE80..0xE90, 0x200..0x300, &memory).is_err());
+    /// assert!(PersistentRegion::new("game.sav", 0xF80..0xF90, 0x200..0x300, &memory).is_ok());
+    /// let memory = Memory::with_size(XO_MEMORY_SIZE, 2);
+    /// assert!(PersistentRegion::new("game.sav", 0x8000..0x8010, 0x200..0x300, &memory).is_ok());
+    /// ```
+    pub fn new<P>(path: P, range: Range<u16>, program: Range<u16>, memory: &Memory) -> io::Result<PersistentRegion>
+        where P: AsRef<Path>, {
+            if range.is_empty() || range.end as usize > memory.size() || overlaps(&range, &(FONT_START..FONT_END)) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("0x{:0>3X}-0x{:0>3X} is not a valid persistent region", range.start, range.end.wrapping_sub(1))));
+            }
+            let layout = memory.display_layout();
+            let display = layout.start..layout.start.saturating_add(layout.len().saturating_mul(memory.planes() as u16));
+            if overlaps(&range, &display) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("0x{:0>3X}-0x{:0>3X} overlaps the display buffer at 0x{:0>3X}-0x{:0>3X}", range.start, range.end - 1, display.start,
+                        display.end - 1)));
+            }
+            if overlaps(&range, &program) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("0x{:0>3X}-0x{:0>3X} overlaps the program at 0x{:0>3X}-0x{:0>3X}", range.start, range.end - 1, program.start, program.end - 1)));
+            }
+            Ok(PersistentRegion { path: path.as_ref().to_path_buf(), range, saved: Vec::new() })
+    }
+
+    /// Checks whether the region overlaps the given address range, e.g. the one of a program that is about to be loaded.
+    pub fn overlaps(&self, range: &Range<u16>) -> bool {
+        overlaps(&self.range, range)
+    }
+
+    /// Gets the file that stores the persistent region of the given ROM: the ROM's path with the extension `.sav`.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::persistence::PersistentRegion;
+    /// # use std::path::{Path, PathBuf};
+    /// assert_eq!(PersistentRegion::file_for(Path::new("roms/pong.ch8")), PathBuf::from("roms/pong.sav"));
+    /// ```
+    pub fn file_for(rom: &Path) -> PathBuf {
+        rom.with_extension("sav")
+    }
+
+    /// Gets the file backing this region.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Changes the file backing this region, e.g. when a different ROM is loaded. The contents are not flushed to the old file.
+    pub fn set_path<P>(&mut self, path: P)
+        where P: AsRef<Path>, {
+            self.path = path.as_ref().to_path_buf();
+            self.saved.clear();
+    }
+
+    /// Loads the contents of the file into the region of the given memory, filling the rest of the region with `0`.
+    ///
+    /// # Errors
+    /// Returns an error if the file exists but cannot be read. A missing file is not an error.
+    pub fn restore(&mut self, memory: &mut Memory) -> io::Result<()> {
+        let mut bytes = match fs::read(&self.path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        bytes.resize(self.range.len(), 0);
+        for (address, byte) in self.range.clone().zip(&bytes) {
+            memory.store(address, *byte);
+        }
+        log::info!("Restored 0x{:0>3X}-0x{:0>3X} from '{}'", self.range.start, self.range.end - 1, self.path.display());
+        self.saved = bytes;
+        Ok(())
+    }
+
+    /// Writes the region of the given memory to the file, if it changed since it was last restored or flushed.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written.
+    pub fn flush(&mut self, memory: &Memory) -> io::Result<()> {
+        let bytes: Vec<u8> = self.range.clone().map(|address| memory.get(address)).collect();
+        if bytes == self.saved {
+            return Ok(());
+        }
+        fs::write(&self.path, &bytes)?;
+        log::debug!("Flushed 0x{:0>3X}-0x{:0>3X} to '{}'", self.range.start, self.range.end - 1, self.path.display());
+        self.saved = bytes;
+        Ok(())
+    }
+}
+
+/// Checks whether two address ranges have an address in common.
+fn overlaps(a: &Range<u16>, b: &Range<u16>) -> bool {
+    !a.is_empty() && !b.is_empty() && a.start < b.end && b.start < a.end
+}
+
+/// Parses an inclusive address range of the form `E00-EFF` (hexadecimal, with an optional `0x` prefix) into an exclusive `Range`.
+///
+/// # Example
+/// ```
+/// # use chip8::persistence::parse_range;
+/// assert_eq!(parse_range("0xE00-0xE0F"), Some(0xE00..0xE10));
+/// ```
+pub fn parse_range(s: &str) -> Option<Range<u16>> {
+    let parse = |s: &str| u16::from_str_radix(s.trim().trim_start_matches("0x"), 16).ok();
+    let (start, end) = s.split_once('-')?;
+    let (start, end) = (parse(start)?, parse(end)?);
+    if end < start {
+        return None;
+    }
+    Some(start..end.checked_add(1)?)
+}
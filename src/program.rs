@@ -1,12 +1,34 @@
 //! Contains structs related to input programs that can be run on the system. The `Program` struct is responsible for the representation of bytes contained in a given
 //! input. The `Instruction` struct contains the four hexadecimal digits that represent a single instruction, and the functionality to run it on a given system state.
 
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 use rand::Rng;
 use std::fs::File;
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 use std::path::Path;
+use crate::asm;
+use crate::disasm;
+use crate::hexdump::HexDump;
+use crate::octo::OctoCart;
+use crate::patch::Patch;
+use crate::quirks::Quirks;
+use crate::romdb::{self, RomMatch};
 use crate::system;
-use crate::utils::{big_endian_4_2, big_endian_4_3};
+use crate::utils::{big_endian_4_2, big_endian_4_3, big_endian_8_2};
+
+/// The address at which programs are loaded into memory, and at which execution starts.
+pub const PROGRAM_START: u16 = 0x200;
+
+/// The signature at the start of every zip archive.
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// The signature at the start of every gzip-compressed file.
+const GZIP_MAGIC: &[u8] = b"\x1F\x8B";
+
+/// The signature at the start of every GIF image, used to detect Octo cartridges.
+const GIF_MAGIC: &[u8] = b"GIF8";
 
 #[derive(Copy, Clone)]
 /// Represents the instructions of a program's byte code as four hexadecimal digits (unsigned 4-bit integers). 
@@ -14,7 +36,7 @@ use crate::utils::{big_endian_4_2, big_endian_4_3};
 /// Instructions can be parsed from a single 16-bit unsigned integer, and, given a system state, run to update the state.
 /// 
 /// 
-pub struct Instruction(u8, u8, u8, u8);
+pub struct Instruction(pub u8, pub u8, pub u8, pub u8);
 
 impl From<u16> for Instruction {
 
@@ -22,6 +44,7 @@ impl From<u16> for Instruction {
     /// 
     /// # Example
     /// ```
+    /// # use chip8::program::Instruction;
     /// let instruction: Instruction = 0xD01Fu16.into();
     /// assert_eq!(instruction.3, 0xF);
     /// ```
@@ -34,23 +57,61 @@ impl From<u16> for Instruction {
     }
 }
 
+/// A summary of what executing a single `Instruction` did, as returned by `Instruction::execute(...)`.
+/// 
+/// Lets frontends and the main loop react to an instruction (e.g. render, beep or show a key prompt) without comparing the system state before and
+/// after it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SideEffects {
+    /// The display buffer was changed by `00E0` or `DXYN`, or by the scrolling and resolution instructions of the SUPER-CHIP.
+    pub screen_dirty: bool,
+    /// A `DXYN` sprite turned off at least one pixel, i.e. `VF` was set to `1`.
+    pub collision: bool,
+    /// `FX18` started the sound timer while it was not active.
+    pub sound_started: bool,
+    /// `FX0A` found no key release and will be executed again.
+    pub waiting_for_key: bool,
+    /// The PC does not point at the next instruction, because of a jump, call, return or skipped instruction, or because `FX0A` is waiting.
+    pub pc_changed: bool,
+}
+
 impl Instruction {
 
-    /// Decodes and executes an Instruction given the mutable state of a `System`.
+    /// Decodes and executes an Instruction given the mutable state of a `System`, and returns a summary of its `SideEffects`. The PC of the system is
+    /// expected to point behind the instruction, as `System::step()` increments it before executing.
     /// 
     /// The exact action taken by this method depends on the instruction itself. Typically, the first digit represents the action to be made,
     /// and the remaining digits contain additional information, such as parameters, for the execution.
+    /// Instructions whose behavior differs between interpreters follow the `quirks` of the given system.
     /// 
-    /// # Panics
-    /// If an instruction is passed that cannot be decoded, a panic is raised.
+    /// # Errors
+    /// Returns `Chip8Error::UnknownOpcode` if the instruction cannot be decoded, `Chip8Error::StackUnderflow` if `00EE` is executed with an empty
+    /// stack, and `Chip8Error::AddressOutOfRange` or `Chip8Error::WriteProtected` if it writes outside of the main memory or to a region protected
+    /// by the system's `write_protection` (see `System::store(...)`). Writes of `FX33`/`FX55` that precede the failing one are kept.
     /// 
-    pub fn execute(self, sys: &mut system::System) {
+    /// # Example
+    /// ```
+    /// # use chip8::program::Instruction;
+    /// # use chip8::system::System;
+    /// # let mut sys = System::new();
+    /// let effects = Instruction::from(0x00E0).execute(&mut sys)?;
+    /// assert!(effects.screen_dirty);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", name = "execute", skip_all, fields(instruction = %self)))]
+    pub fn execute(self, sys: &mut system::System) -> Result<SideEffects, system::Chip8Error> {
+        let mut effects = SideEffects::default();
+        let next_pc = sys.pc;
+        let pc = next_pc.wrapping_sub(2);
+        let superchip = sys.variant().superchip();
+        let xochip = sys.variant() == system::Variant::XoChip;
         match self {
             Instruction(0, 0, 0xE, 0) => { //DISPLAY Clear
                 sys.memory.clear_display();
+                effects.screen_dirty = true;
             },
             Instruction(0, 0, 0xE, 0xE) => { //RETURN
-                sys.pc = sys.stack.pop().unwrap();
+                sys.pc = sys.stack.pop().ok_or(system::Chip8Error::StackUnderflow { pc })?;
             },
             Instruction(1, n1, n2, n3) => { //JUMP
                 let address = big_endian_4_3(n1, n2, n3);
@@ -61,6 +122,33 @@ impl Instruction {
                 sys.stack.push(sys.pc);
                 sys.pc = address;
             },
+            Instruction(0, 0, 0xC, n) if superchip => { //Scroll the display down by N pixels
+                sys.memory.scroll(0, n as i16);
+                effects.screen_dirty = true;
+            },
+            Instruction(0, 0, 0xD, n) if xochip => { //Scroll the display up by N pixels
+                sys.memory.scroll(0, -(n as i16));
+                effects.screen_dirty = true;
+            },
+            Instruction(0, 0, 0xF, 0xB) if superchip => { //Scroll the display right by 4 pixels
+                sys.memory.scroll(4, 0);
+                effects.screen_dirty = true;
+            },
+            Instruction(0, 0, 0xF, 0xC) if superchip => { //Scroll the display left by 4 pixels
+                sys.memory.scroll(-4, 0);
+                effects.screen_dirty = true;
+            },
+            Instruction(0, 0, 0xF, 0xD) if superchip => { //Exit the interpreter
+                sys.exit();
+            },
+            Instruction(0, 0, 0xF, 0xE) if superchip => { //Switch to the low-resolution mode (64x32)
+                sys.set_hires(false);
+                effects.screen_dirty = true;
+            },
+            Instruction(0, 0, 0xF, 0xF) if superchip => { //Switch to the high-resolution mode (128x64)
+                sys.set_hires(true);
+                effects.screen_dirty = true;
+            },
             Instruction(0, n1, n2, n3) => { //CALL MACHINE
                 let _address = big_endian_4_3(n1, n2, n3);
                 //SKIP
@@ -69,21 +157,31 @@ impl Instruction {
                 let val = big_endian_4_2(n1, n2);
                 let v_val = sys.registers.get(x);
                 if val == v_val {
-                    sys.increment_pc();
+                    sys.skip();
                 }
             },
             Instruction(4, x, n1, n2) => { //Skip if VX != NN
                 let val = big_endian_4_2(n1, n2);
                 let v_val = sys.registers.get(x);
                 if val != v_val {
-                    sys.increment_pc();
+                    sys.skip();
                 }
             },
             Instruction(5, x, y, 0) => { //Skip if VX == VY
                 let vx_val = sys.registers.get(x);
                 let vy_val = sys.registers.get(y);
                 if vx_val == vy_val {
-                    sys.increment_pc();
+                    sys.skip();
+                }
+            },
+            Instruction(5, x, y, 2) if xochip => { //Store [VX..VY] (or in reverse order, if X > Y) in memory at [*I, *(I+1),...]
+                for (offset, register) in register_range(x, y).enumerate() {
+                    sys.store(sys.registers.i().wrapping_add(offset as u16), sys.registers.get(register))?;
+                }
+            },
+            Instruction(5, x, y, 3) if xochip => { //Load [VX..VY] (or in reverse order, if X > Y) from memory at [*I, *(I+1),...]
+                for (offset, register) in register_range(x, y).enumerate() {
+                    sys.registers.set(register, sys.memory.get(sys.registers.i().wrapping_add(offset as u16)));
                 }
             },
             Instruction(6, x, n1, n2) => { //VX = NN
@@ -97,164 +195,230 @@ impl Instruction {
             Instruction(8, x, y, 0) => { //VX = VY
                 sys.registers.set(x, sys.registers.get(y));
             },
-            Instruction(8, x, y, 1) => { //VX |= VY
+            Instruction(8, x, y, 1) => { //VX |= VY (may reset VF, depending on quirks)
                 sys.registers.set(x, sys.registers.get(x) | sys.registers.get(y));
+                if sys.quirks.vf_reset {
+                    sys.registers.set_vF(0);
+                }
             },
-            Instruction(8, x, y, 2) => { //VX &= VY
+            Instruction(8, x, y, 2) => { //VX &= VY (may reset VF, depending on quirks)
                 sys.registers.set(x, sys.registers.get(x) & sys.registers.get(y));
+                if sys.quirks.vf_reset {
+                    sys.registers.set_vF(0);
+                }
             },
-            Instruction(8, x, y, 3) => { //VX ^= VY
+            Instruction(8, x, y, 3) => { //VX ^= VY (may reset VF, depending on quirks)
                 sys.registers.set(x, sys.registers.get(x) ^ sys.registers.get(y));
+                if sys.quirks.vf_reset {
+                    sys.registers.set_vF(0);
+                }
             },
             Instruction(8, x, y, 4) => { //VX += VY (may set VF carry flag)
-                let mut sum = sys.registers.get(x) as u16 + sys.registers.get(y) as u16;
-                sys.registers.set_vF(0);
-                if sum >= 0x100 {
-                    sum -= 0x100;
-                    sys.registers.set_vF(1);
-                }
+                let sum = sys.registers.get(x) as u16 + sys.registers.get(y) as u16;
                 sys.registers.set(x, sum as u8);
+                sys.registers.set_vF((sum >= 0x100) as u8);
             },
             Instruction(8, x, y, 5) => { //VX -= VY (may un-set VF carry flag on borrow)
-                let mut sum = 0x100 + sys.registers.get(x) as u16 - sys.registers.get(y) as u16;
-                sys.registers.set_vF(0);
-                if sum >= 0x100 {
-                    sum -= 0x100;
-                    sys.registers.set_vF(1);
-                }
+                let sum = 0x100 + sys.registers.get(x) as u16 - sys.registers.get(y) as u16;
                 sys.registers.set(x, sum as u8);
+                sys.registers.set_vF((sum >= 0x100) as u8);
             },
-            Instruction(8, x, _, 6) => { //VX shifted right by 1, lsb set to VF
-                let val = sys.registers.get(x);
-                sys.registers.set_vF(x & 1);
+            Instruction(8, x, y, 6) => { //VX = VX (or VY, depending on quirks) shifted right by 1, lsb set to VF
+                let val = sys.registers.get(if sys.quirks.shift_uses_vy { y } else { x });
                 sys.registers.set(x, val >> 1);
+                sys.registers.set_vF(val & 1);
             },
             Instruction(8, x, y, 7) => { //VX = VY - VX (may un-set VF carry flag on borrow)
-                let mut sum = 0x100 + sys.registers.get(y) as u16 - sys.registers.get(x) as u16;
-                sys.registers.set_vF(0);
-                if sum >= 0x100 {
-                    sum -= 0x100;
-                    sys.registers.set_vF(1);
-                }
+                let sum = 0x100 + sys.registers.get(y) as u16 - sys.registers.get(x) as u16;
                 sys.registers.set(x, sum as u8);
+                sys.registers.set_vF((sum >= 0x100) as u8);
             },
-            Instruction(8, x, _, 0xE) => { //VX shifted left by 1, msb set to VF
-                let mut val = sys.registers.get(x) as u16;
-                sys.registers.set_vF((x & 0b10000000) >> 7);
-                val <<= 1;
-                if val > 0x100 {
-                    val -= 0x100;
-                }
-                sys.registers.set(x, val as u8);
+            Instruction(8, x, y, 0xE) => { //VX = VX (or VY, depending on quirks) shifted left by 1, msb set to VF
+                let val = sys.registers.get(if sys.quirks.shift_uses_vy { y } else { x });
+                sys.registers.set(x, val << 1);
+                sys.registers.set_vF((val & 0b10000000) >> 7);
             },
             Instruction(9, x, y, 0) => { //Skip if VX != VY
                 let vx_val = sys.registers.get(x);
                 let vy_val = sys.registers.get(y);
                 if vx_val != vy_val {
-                    sys.increment_pc();
+                    sys.skip();
                 }
             },
             Instruction(0xA, n1, n2, n3) => { //I = NNN
                 let address = big_endian_4_3(n1, n2, n3);
                 sys.registers.set_i(address);
             },
-            Instruction(0xB, n1, n2, n3) => { //Jump to NNN + V0
+            Instruction(0xB, n1, n2, n3) => { //Jump to NNN + V0 (or XNN + VX, depending on quirks)
                 let address = big_endian_4_3(n1, n2, n3);
-                let v0_val = sys.registers.get(0);
-                sys.pc = address + v0_val as u16;
+                let offset = sys.registers.get(if sys.quirks.jump_uses_vx { n1 } else { 0 });
+                sys.pc = address + offset as u16;
             },
             Instruction(0xC, x, n1, n2) => { //VX = rand(0-255) & NN
                 let val = big_endian_4_2(n1, n2);
                 let r = sys.rng.gen_range(0..=255u8) & val;
                 sys.registers.set(x, r);
             },
-            Instruction(0xD, x, y, n) => { //draw(sprite(x: VX, y: VY, w: 8, h: N)), sprite defined at I, VF set if anything is drawn
-                let x_pos = sys.registers.get(x) % 64;
-                let y_pos = sys.registers.get(y) % 32;
-                sys.registers.set_vF(0);
-
-                for i in 0..n {
-
-                    if y_pos + i >= sys.screen_height {
-                        break;
-                    }
+            Instruction(0xD, x, y, n) => { //draw(sprite(x: VX, y: VY, w: 8, h: N)), sprite defined at I, VF set if anything is drawn, 16x16 for N = 0 (SCHIP)
+                let x_pos = sys.registers.get(x) % sys.screen_width;
+                let y_pos = sys.registers.get(y) % sys.screen_height;
+                let (width, height) = if superchip && n == 0 { (2, 16) } else { (1, n) };
+                let mut collided_rows = 0;
+                let mut sprite = sys.registers.i();
+                effects.screen_dirty = true;
 
-                    let sprite_byte = sys.memory.get(sys.registers.i() + i as u16);
-                    for j in 0..8u8 {
+                // The XO-CHIP draws into each selected plane, with the sprite data of the planes following each other
+                let planes = sys.memory.selected_planes();
+                for plane in (0..8).filter(|plane| planes & (1 << plane) != 0) {
+                    for i in 0..height {
 
-                        if x_pos + j >= sys.screen_width {
+                        if sys.quirks.clip_sprites && y_pos as u16 + i as u16 >= sys.screen_height as u16 {
                             break;
                         }
 
-                        if sprite_byte & (1 << (7 - j)) == 0 {
-                            continue;
+                        let mut collision = false;
+                        for b in 0..width {
+                            let mut column = x_pos as u16 + 8 * b as u16;
+                            if column >= sys.screen_width as u16 {
+                                if sys.quirks.clip_sprites {
+                                    break;
+                                }
+                                column %= sys.screen_width as u16;
+                            }
+                            let sprite_byte = sys.memory.get(sprite.wrapping_add((i * width + b) as u16));
+                            let row = ((y_pos as u16 + i as u16) % sys.screen_height as u16) as u8;
+                            collision |= sys.memory.draw_plane_byte(plane, column as u8, row, sprite_byte, sys.quirks.clip_sprites);
                         }
-                        if sys.memory.flip_pixel(x_pos + j, y_pos + i) {
-                            sys.registers.set_vF(1);
+                        if collision {
+                            collided_rows += 1;
+                            effects.collision = true;
                         }
                     }
+                    sprite = sprite.wrapping_add(width as u16 * height as u16);
                 }
+                // In its high-resolution mode, the SUPER-CHIP reports the number of rows with a collision instead of a flag
+                sys.registers.set_vF(if sys.hires() && !xochip { collided_rows } else { effects.collision as u8 });
             },
             Instruction(0xE, x, 0x9, 0xE) => { //Skip if key x is pressed
                 if sys.keyboard.get(x) {
-                    sys.increment_pc();
+                    sys.skip();
                 }
             },
             Instruction(0xE, x, 0xA, 0x1) => { //Skip if key x is not pressed
                 if !sys.keyboard.get(x) {
-                    sys.increment_pc();
+                    sys.skip();
                 }
             },
             Instruction(0xF, x, 0x0, 0x7) => { //VX = delay timer
                 sys.registers.set(x, sys.delay_timer.get());
             },
             Instruction(0xF, x, 0x0, 0xA) => { //VX = await key()
-                let l = sys.keyboard.latest();
-                if l == 16 {
-                    sys.pc -= 2;
+                let released = match sys.key_mode {
+                    system::KeyMode::Queue => sys.keyboard.next_release(sys.cycles()),
+                    system::KeyMode::Sample => sys.keyboard.sample_release(),
+                };
+                match released {
+                    Some(key) => sys.registers.set(x, key),
+                    None => {
+                        sys.pc = sys.pc.wrapping_sub(2);
+                        effects.waiting_for_key = true;
+                    },
                 }
-                else {
-                    sys.registers.set(x, l);
+            },
+            Instruction(0xF, 0, 0, 0) if xochip => { //I = NNNN, the 16-bit address in the following word
+                let address = big_endian_8_2(sys.memory.get(sys.pc), sys.memory.get(sys.pc.wrapping_add(1)));
+                sys.registers.set_i(address);
+                sys.increment_pc();
+            },
+            Instruction(0xF, n, 0, 1) if xochip => { //Select the display planes N (a bit mask) for drawing, clearing and scrolling
+                sys.memory.select_planes(n);
+            },
+            Instruction(0xF, 0, 0, 2) if xochip => { //Load the 16 bytes at [*I, *(I+1),...] into the audio pattern buffer
+                let mut pattern = [0u8; 16];
+                for (offset, byte) in pattern.iter_mut().enumerate() {
+                    *byte = sys.memory.get(sys.registers.i().wrapping_add(offset as u16));
                 }
+                sys.set_audio_pattern(Some(pattern));
             },
             Instruction(0xF, x, 0x1, 0x5) => { //delay timer = VX
                 sys.delay_timer.set(sys.registers.get(x));
             },
             Instruction(0xF, x, 0x1, 0x8) => { //sound timer = VX
+                effects.sound_started = sys.sound_timer.get() == 0 && sys.registers.get(x) > 0;
                 sys.sound_timer.set(sys.registers.get(x));
             },
-            Instruction(0xF, x, 0x1, 0xE) => { //I += VX
-                let mut val = sys.registers.i() + sys.registers.get(x) as u16;
+            Instruction(0xF, x, 0x1, 0xE) if xochip => { //I += VX, wrapping around at the end of the 16-bit address space
+                sys.registers.set_i(sys.registers.i().wrapping_add(sys.registers.get(x) as u16));
+            },
+            Instruction(0xF, x, 0x1, 0xE) => { //I += VX, wrapping around at the end of the 12-bit address space
+                let val = sys.registers.i().wrapping_add(sys.registers.get(x) as u16);
                 if val >= 0x1000 {
-                    val -= 0x1000;
                     sys.registers.set_vF(1);
                 }
 
-                sys.registers.set_i(val);
+                sys.registers.set_i(val & 0xFFF);
             },
             Instruction(0xF, x, 0x2, 0x9) => { //I = address of sprite VX
                 let c = sys.registers.get(x) & 0xF;
-                sys.registers.set_i(0x50u16 + 5u16 * c as u16);
+                sys.registers.set_i(system::FONT_START + 5u16 * c as u16);
+            },
+            Instruction(0xF, x, 0x3, 0x0) if superchip => { //I = address of big sprite VX
+                let c = sys.registers.get(x) & 0xF;
+                sys.registers.set_i(system::BIG_FONT_START + 10u16 * c as u16);
+            },
+            Instruction(0xF, x, 0x3, 0xA) if xochip => { //Audio pitch = VX
+                sys.set_pitch(sys.registers.get(x));
             },
             Instruction(0xF, x, 0x3, 0x3) => { //Convert VX to decimal. Store 100-digit at *I, 10-digit at *(I+1) and 1-digit at *(I+2).
                 let value = sys.registers.get(x);
-                sys.memory.store(sys.registers.i(), value / 100);
-                sys.memory.store(sys.registers.i() + 1, (value % 100) / 10);
-                sys.memory.store(sys.registers.i() + 2, value % 10);
+                sys.store(sys.registers.i(), value / 100)?;
+                sys.store(sys.registers.i().wrapping_add(1), (value % 100) / 10)?;
+                sys.store(sys.registers.i().wrapping_add(2), value % 10)?;
             },
-            Instruction(0xF, x, 0x5, 0x5) => { //Store [V0..VX] in memory at [*I, *(I+1),...]
+            Instruction(0xF, x, 0x5, 0x5) => { //Store [V0..VX] in memory at [*I, *(I+1),...] (may increment I, depending on quirks)
                 for i in 0..=x {
-                    sys.memory.store(sys.registers.i() + i as u16, sys.registers.get(i));
+                    sys.store(sys.registers.i().wrapping_add(i as u16), sys.registers.get(i))?;
+                }
+                if sys.quirks.load_store_increments_i {
+                    sys.registers.set_i(sys.registers.i().wrapping_add(x as u16 + 1));
                 }
             },
-            Instruction(0xF, x, 0x6, 0x5) => { //Loads [V0..VX] from memory at [*I, *(I+1),...]
+            Instruction(0xF, x, 0x6, 0x5) => { //Loads [V0..VX] from memory at [*I, *(I+1),...] (may increment I, depending on quirks)
                 for i in 0..=x {
-                    sys.registers.set(i, sys.memory.get(sys.registers.i() + i as u16));
+                    sys.registers.set(i, sys.memory.get(sys.registers.i().wrapping_add(i as u16)));
+                }
+                if sys.quirks.load_store_increments_i {
+                    sys.registers.set_i(sys.registers.i().wrapping_add(x as u16 + 1));
+                }
+            },
+            Instruction(0xF, x, 0x7, 0x5) if superchip => { //Store [V0..VX] in the RPL user flags (X <= 7)
+                for i in 0..=x.min(7) {
+                    sys.rpl_flags[i as usize] = sys.registers.get(i);
+                }
+            },
+            Instruction(0xF, x, 0x8, 0x5) if superchip => { //Load [V0..VX] from the RPL user flags (X <= 7)
+                for i in 0..=x.min(7) {
+                    sys.registers.set(i, sys.rpl_flags[i as usize]);
                 }
             },
 
-            _ => panic!(),
+            _ => {
+                let opcode = big_endian_8_2(big_endian_4_2(self.0, self.1), big_endian_4_2(self.2, self.3));
+                return Err(system::Chip8Error::UnknownOpcode { pc, opcode });
+            },
         }
+        effects.pc_changed = sys.pc != next_pc;
+        Ok(effects)
+    }
+}
+
+/// Gets the registers from `VX` to `VY` of the XO-CHIP instructions `5XY2`/`5XY3`, in descending order if `X` is greater than `Y`.
+fn register_range(x: u8, y: u8) -> Box<dyn Iterator<Item = u8>> {
+    if x <= y {
+        Box::new(x..=y)
+    }
+    else {
+        Box::new((y..=x).rev())
     }
 }
 
@@ -267,33 +431,581 @@ impl std::fmt::Display for Instruction {
 }
 
 /// Represents a program's bytecode as a list of bytes
+#[derive(Clone)]
 pub struct Program {
     pub instructions: Vec<u8>,
+    file_name: Option<String>,
+    profile: Option<RomMatch>,
+}
+
+/// Metadata describing a `Program`, as returned by `Program::info()`.
+pub struct ProgramInfo {
+    /// The name of the file the program was loaded from, if any.
+    pub file_name: Option<String>,
+    /// A human-readable title, derived from the file name (or `Untitled` if the program was not loaded from a file).
+    pub title: String,
+    /// The size of the program in bytes.
+    pub length: usize,
+    /// The SHA-1 hash of the program's bytes, as a lowercase hexadecimal string.
+    pub sha1: String,
+    /// The CRC-32 checksum of the program's bytes, as used by many ROM collections.
+    pub crc32: u32,
+    /// Whether the program consists of an odd number of bytes, which means that its last instruction is incomplete.
+    pub odd_length: bool,
+}
+
+/// A potential problem found in a program by `Program::validate(...)`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Warning {
+    /// The program is larger than the memory available between `PROGRAM_START` and the display buffer.
+    TooLarge { length: usize, available: usize },
+    /// The program consists of an odd number of bytes, so its last instruction is incomplete.
+    OddLength,
+    /// The opcode at the given address cannot be decoded. This may also be sprite data embedded between instructions.
+    UnknownOpcode { address: u16, opcode: u16 },
+    /// The jump or call at the given address targets an address outside of the program, or an odd address.
+    SuspiciousJump { address: u16, target: u16 },
+}
+
+impl std::fmt::Display for Warning {
+
+    /// Formats the `Warning` as a human-readable message.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::TooLarge { length, available } =>
+                write!(f, "program is too large: {} bytes, but only {} bytes are available", length, available),
+            Warning::OddLength =>
+                write!(f, "program has an odd length, its last instruction is incomplete"),
+            Warning::UnknownOpcode { address, opcode } =>
+                write!(f, "{:0>4X}: unknown opcode {:0>4X}", address, opcode),
+            Warning::SuspiciousJump { address, target } =>
+                write!(f, "{:0>4X}: suspicious jump target {:0>4X}", address, target),
+        }
+    }
+}
+
+impl std::fmt::Display for ProgramInfo {
+
+    /// Formats the `ProgramInfo` struct as a list of `key: value` lines.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Title:  {}", self.title)?;
+        writeln!(f, "File:   {}", self.file_name.as_deref().unwrap_or("-"))?;
+        writeln!(f, "Length: {} bytes{}", self.length, if self.odd_length { " (odd)" } else { "" })?;
+        writeln!(f, "SHA-1:  {}", self.sha1)?;
+        write!(f, "CRC-32: {:0>8x}", self.crc32)
+    }
 }
 
 impl Program {
 
     /// Attempts to load a program from a given file path
     /// 
+    /// If the file is a zip archive, the program is extracted from it transparently. The archive must contain exactly one `.ch8` file (or exactly one file
+    /// in total), otherwise an error of kind `InvalidData` is returned. Similarly, gzip-compressed files are decompressed transparently.
+    /// 
+    /// Octo cartridges (GIF images) contain Octo source code instead of bytecode, which is compiled transparently; an error of kind `InvalidData`
+    /// is returned if it does not compile. The settings stored in a cartridge are not part of the program, use `OctoCart::load(...)` to apply them.
+    /// 
+    /// The program is looked up in the bundled ROM database by its fingerprint (see the `romdb` module), and the matched profile with its known-good
+    /// settings is returned by `profile()`.
+    /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let program = Program::load("rom.ch8")?;
+    /// let zipped = Program::load("rom.zip")?;
+    /// let compressed = Program::load("rom.ch8.gz")?;
     /// ```
     pub fn load<P>(path: P) -> io::Result<Program> 
         where P: AsRef<Path>, {
-            let file = File::open(path)?;
-            Ok(Program { instructions: file.bytes().filter_map(|b| b.ok()).collect() })
+            let mut file_name = path.as_ref().file_name().map(|name| name.to_string_lossy().into_owned());
+            let mut instructions = Vec::new();
+            File::open(path)?.read_to_end(&mut instructions)?;
+            if instructions.starts_with(ZIP_MAGIC) {
+                let (name, bytes) = extract_zip(&instructions)?;
+                log::debug!("Extracted '{}' from zip archive", name);
+                file_name = Some(name);
+                instructions = bytes;
+            }
+            else if instructions.starts_with(GIF_MAGIC) {
+                let cart = OctoCart::from_bytes(&instructions)?;
+                instructions = cart.compile().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                log::debug!("Compiled {} bytes from the Octo cartridge", instructions.len());
+            }
+            else if instructions.starts_with(GZIP_MAGIC) {
+                let mut bytes = Vec::new();
+                GzDecoder::new(instructions.as_slice()).read_to_end(&mut bytes)?;
+                log::debug!("Decompressed {} bytes of gzip data", bytes.len());
+                file_name = file_name.map(|name| name.strip_suffix(".gz").map(|n| n.to_string()).unwrap_or(name));
+                instructions = bytes;
+            }
+            let mut program = Program { instructions, file_name, profile: None };
+            program.profile = romdb::lookup(&program);
+            Ok(program)
+    }
+
+    /// Assembles a program from the assembly source file at the given path (see the `asm` module for its syntax).
+    /// 
+    /// Returns an error of kind `InvalidData` with the offending line if the source cannot be assembled.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let program = Program::assemble("game.s")?;
+    /// program.save("game.ch8")?;
+    /// ```
+    pub fn assemble<P>(path: P) -> io::Result<Program>
+        where P: AsRef<Path>, {
+            let file_name = path.as_ref().file_name().map(|name| name.to_string_lossy().into_owned());
+            let source = std::fs::read_to_string(path)?;
+            let instructions = asm::assemble(&source).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Ok(Program { instructions, file_name, profile: None })
+    }
+
+    /// Saves the program's raw bytecode to the given file path.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let program = ProgramBuilder::new().label("halt").jump("halt").build()?;
+    /// program.save("halt.ch8")?;
+    /// ```
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+        where P: AsRef<Path>, {
+            File::create(path)?.write_all(&self.instructions)
+    }
+
+    /// Saves the program's bytecode to the given file path, compressed with gzip.
+    /// 
+    /// The resulting file can be loaded again using `Program::load(...)`.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let program = Program::load("rom.ch8")?;
+    /// program.save_compressed("rom.ch8.gz")?;
+    /// ```
+    pub fn save_compressed<P>(&self, path: P) -> io::Result<()>
+        where P: AsRef<Path>, {
+            let mut encoder = GzEncoder::new(File::create(path)?, Compression::best());
+            encoder.write_all(&self.instructions)?;
+            encoder.finish()?;
+            Ok(())
+    }
+
+    /// Creates a program from its raw bytecode.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let program = Program::from_bytes(vec![0x00, 0xE0, 0x12, 0x00]);
+    /// ```
+    pub fn from_bytes(bytes: Vec<u8>) -> Program {
+        Program { instructions: bytes, file_name: None, profile: None }
+    }
+
+    /// Parses a program from a string of hexadecimal digits.
+    /// 
+    /// Whitespace is ignored, so instructions can be grouped freely. Returns an error of kind `InvalidData` if the string contains a character that is
+    /// not a hexadecimal digit, or if the number of digits is odd.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::program::Program;
+    /// let program = Program::from_hex_str("00E0 A22A 600C")?;
+    /// assert_eq!(program.instructions, vec![0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_hex_str(hex: &str) -> io::Result<Program> {
+        let digits = hex.chars().filter(|c| !c.is_whitespace())
+            .map(|c| c.to_digit(16).map(|d| d as u8).ok_or(c))
+            .collect::<Result<Vec<u8>, char>>()
+            .map_err(|c| io::Error::new(io::ErrorKind::InvalidData, format!("invalid hex digit '{}'", c)))?;
+        if !digits.len().is_multiple_of(2) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "odd number of hex digits"));
+        }
+        Ok(Program::from_bytes(digits.chunks(2).map(|pair| big_endian_4_2(pair[0], pair[1])).collect()))
+    }
+
+    /// Collects metadata about the program, such as its title, length, SHA-1 hash and CRC-32 checksum.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let program = Program::load("test/data/pong.ch8")?;
+    /// let info = program.info();
+    /// assert_eq!(info.title, "pong");
+    /// ```
+    pub fn info(&self) -> ProgramInfo {
+        let title = self.file_name.as_ref()
+            .map(|name| Path::new(name).file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_else(|| name.clone()))
+            .unwrap_or_else(|| "Untitled".to_string());
+        ProgramInfo {
+            file_name: self.file_name.clone(),
+            title,
+            length: self.instructions.len(),
+            sha1: sha1_smol::Sha1::from(&self.instructions).digest().to_string(),
+            crc32: crc32fast::hash(&self.instructions),
+            odd_length: !self.instructions.len().is_multiple_of(2),
+        }
+    }
+
+    /// Gets the profile of the program in the bundled ROM database, if it was recognized when it was loaded with `load(...)`. Programs created in
+    /// other ways can be looked up with `romdb::lookup(...)`.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let program = Program::load("roms/INVADERS")?;
+    /// if let Some(found) = program.profile() {
+    ///     println!("{} (matched by {})", found.profile.title, found.matched_by);
+    /// }
+    /// ```
+    pub fn profile(&self) -> Option<&RomMatch> {
+        self.profile.as_ref()
+    }
+
+    /// Checks the program for potential problems, assuming it is loaded at `PROGRAM_START` and run with the given `quirks`.
+    /// 
+    /// The following problems are reported:
+    /// - The program is too large to fit into memory.
+    /// - The program has an odd length.
+    /// - An opcode cannot be decoded.
+    /// - A jump or call (`1NNN`, `2NNN`) targets an address outside of the program or an odd address.
+    /// - The base address of a `BNNN` jump lies outside of the program. This check is skipped if `quirks.jump_uses_vx` is set, since `BXNN` then
+    ///   adds an arbitrary register to an address that is usually not meant to be a target on its own.
+    /// 
+    /// Since the instructions are checked linearly, data embedded between instructions (such as sprites) may be reported as unknown opcodes as well.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::program::{Program, Warning};
+    /// # use chip8::quirks::Quirks;
+    /// let program = Program::from_hex_str("00E0 1300")?;
+    /// let warnings = program.validate(&Quirks::new());
+    /// assert_eq!(warnings, vec![Warning::SuspiciousJump { address: 0x202, target: 0x300 }]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn validate(&self, quirks: &Quirks) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        let length = self.instructions.len();
+        let available = (system::DISPLAY_START - PROGRAM_START) as usize;
+        if length > available {
+            warnings.push(Warning::TooLarge { length, available });
+        }
+        if !length.is_multiple_of(2) {
+            warnings.push(Warning::OddLength);
+        }
+
+        let end = PROGRAM_START as usize + length;
+        for (i, pair) in self.instructions.chunks_exact(2).enumerate() {
+            let address = PROGRAM_START + 2 * i as u16;
+            let opcode = big_endian_8_2(pair[0], pair[1]);
+            let instruction: Instruction = opcode.into();
+            if disasm::mnemonic(instruction).is_none() {
+                warnings.push(Warning::UnknownOpcode { address, opcode });
+                continue;
+            }
+
+            let (target, aligned) = match instruction {
+                Instruction(1, n1, n2, n3) | Instruction(2, n1, n2, n3) => (big_endian_4_3(n1, n2, n3), true),
+                Instruction(0xB, n1, n2, n3) if !quirks.jump_uses_vx => (big_endian_4_3(n1, n2, n3), false),
+                _ => continue,
+            };
+            if target < PROGRAM_START || target as usize >= end || (aligned && target % 2 != 0) {
+                warnings.push(Warning::SuspiciousJump { address, target });
+            }
+        }
+        warnings
+    }
+
+    /// Applies all records of a patch to the program, in order.
+    /// 
+    /// If a record modifies bytes beyond the end of the program, the program is extended accordingly, padding any gap with zeros.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::program::Program;
+    /// # use chip8::patch::Patch;
+    /// let mut program = Program::from_hex_str("00E0 6A02")?;
+    /// program.apply_patch(&Patch::from_text("0x202=6A03")?);
+    /// assert_eq!(program.instructions, vec![0x00, 0xE0, 0x6A, 0x03]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn apply_patch(&mut self, patch: &Patch) {
+        for record in &patch.records {
+            let end = record.offset + record.bytes.len();
+            if end > self.instructions.len() {
+                self.instructions.resize(end, 0);
+            }
+            self.instructions[record.offset..end].copy_from_slice(&record.bytes);
+        }
+    }
+
+    /// Compares the program to another program word by word, returning the differences between them.
+    /// 
+    /// The result can be formatted as a listing of all differing instructions (see `ProgramDiff`). If one program is longer than the other, the excess
+    /// instructions are reported as differences as well.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::program::Program;
+    /// let a = Program::from_hex_str("00E0 6A02")?;
+    /// let b = Program::from_hex_str("00E0 6A03")?;
+    /// let diff = a.diff(&b);
+    /// assert_eq!(diff.addresses(), vec![0x202]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn diff<'a>(&'a self, other: &'a Program) -> ProgramDiff<'a> {
+        ProgramDiff { left: self, right: other }
+    }
+
+    /// Returns a view of the program that is formatted in the given export format, for use with other toolchains.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let program = Program::load("pong.ch8")?;
+    /// fs::write("pong.h", program.export(ExportFormat::CArray).to_string())?;
+    /// ```
+    pub fn export(&self, format: ExportFormat) -> Export<'_> {
+        Export { program: self, format }
+    }
+
+    /// Returns a hex+ASCII dump view of the program, with 16 bytes per row and addresses starting at `PROGRAM_START`.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let program = Program::from_hex_str("00E0 A22A")?;
+    /// print!("{}", program.hexdump());
+    /// ```
+    pub fn hexdump(&self) -> HexDump<'_> {
+        HexDump::new(&self.instructions, PROGRAM_START)
+    }
+
+    /// Returns a view of the program that is formatted as a raw hex dump, without mnemonics.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let program = Program::from_hex_str("00E0 1200")?;
+    /// println!("{}", program.raw());
+    /// ```
+    pub fn raw(&self) -> RawListing<'_> {
+        RawListing(self)
+    }
+
+    /// Disassembles the whole program into pairs of addresses (starting at `PROGRAM_START`) and mnemonics, one for every two bytes.
+    /// 
+    /// Opcodes that cannot be decoded are returned as data words (`DW`), and a trailing odd byte as a data byte (`DB`). Since programs may mix
+    /// code and data, data that happens to look like an instruction is disassembled as well.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::program::Program;
+    /// let program = Program::from_hex_str("00E0 D015")?;
+    /// assert_eq!(program.disassemble()[1], (0x202, "DRW V0, V1, 5".to_string()));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn disassemble(&self) -> Vec<(u16, String)> {
+        self.instructions.chunks(2).enumerate().map(|(i, pair)| {
+            let address = PROGRAM_START + 2 * i as u16;
+            let text = match pair {
+                [high, low] => {
+                    let opcode = big_endian_8_2(*high, *low);
+                    disasm::mnemonic(opcode.into()).unwrap_or_else(|| format!("DW 0x{:0>4X}", opcode))
+                },
+                _ => format!("DB 0x{:0>2X}", pair[0]),
+            };
+            (address, text)
+        }).collect()
     }
 }
 
+/// Extracts the single ROM contained in a zip archive, returning its file name and contents.
+/// 
+/// If the archive contains exactly one `.ch8` file, that file is used. Otherwise, the archive must contain exactly one file.
+fn extract_zip(bytes: &[u8]) -> io::Result<(String, Vec<u8>)> {
+    let mut archive = zip::ZipArchive::new(io::Cursor::new(bytes)).map_err(io::Error::other)?;
+    let files: Vec<String> = archive.file_names().filter_map(|name| name.ok()).map(|name| name.into_owned()).filter(|name| !name.ends_with('/')).collect();
+    let roms: Vec<&String> = files.iter().filter(|name| name.to_lowercase().ends_with(".ch8")).collect();
+    let name = match (roms.as_slice(), files.as_slice()) {
+        ([rom], _) => (*rom).clone(),
+        ([], [file]) => file.clone(),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("zip archive contains {} ROMs, expected exactly one", roms.len().max(files.len())))),
+    };
+
+    let mut entry = archive.by_name(&name).map_err(io::Error::other)?;
+    let mut contents = Vec::new();
+    entry.read_to_end(&mut contents)?;
+    let file_name = Path::new(&name).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or(name);
+    Ok((file_name, contents))
+}
+
 impl std::fmt::Display for Program {
 
-    /// Formats the `Program` struct as `<line>: <instruction>` where `line` and `instruction` are both represented as hexadecimal numbers.  
+    /// Formats the `Program` struct as a disassembly listing of the form `<address>: <opcode>  <mnemonic>`, with addresses starting at `PROGRAM_START`.
+    /// 
+    /// Opcodes that cannot be decoded are listed as data words (`DW`), and a trailing odd byte is listed as a data byte (`DB`).
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (pair, (address, text)) in self.instructions.chunks(2).zip(self.disassemble()) {
+            match pair {
+                [high, low] => writeln!(f, "{:0>4X}: {:0>4X}  {}", address, big_endian_8_2(*high, *low), text)?,
+                _ => writeln!(f, "{:0>4X}: {:0>2X}    {}", address, pair[0], text)?,
+            }
+        }
+        write!(f, "")
+    }
+}
+
+/// The differences between two programs, as returned by `Program::diff(...)`.
+pub struct ProgramDiff<'a> {
+    left: &'a Program,
+    right: &'a Program,
+}
+
+impl ProgramDiff<'_> {
+
+    /// Returns the addresses of all instructions that differ between the two programs.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::program::Program;
+    /// let a = Program::from_hex_str("00E0 6A02")?;
+    /// let diff = a.diff(&a);
+    /// assert!(diff.addresses().is_empty());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn addresses(&self) -> Vec<u16> {
+        let length = self.left.instructions.len().max(self.right.instructions.len());
+        (0..length).step_by(2)
+            .filter(|&i| ProgramDiff::word(self.left, i) != ProgramDiff::word(self.right, i))
+            .map(|i| PROGRAM_START + i as u16)
+            .collect()
+    }
+
+    /// Returns the (up to) two bytes of the instruction at the given offset of a program.
+    fn word(program: &Program, offset: usize) -> &[u8] {
+        let bytes = &program.instructions;
+        &bytes[offset.min(bytes.len())..(offset + 2).min(bytes.len())]
+    }
+
+    /// Formats the instruction at the given offset of a program as `<opcode> <mnemonic>`, or `--` if the program is too short.
+    fn describe(program: &Program, offset: usize) -> String {
+        match program.instructions.get(offset..offset + 2) {
+            Some(pair) => {
+                let opcode = big_endian_8_2(pair[0], pair[1]);
+                format!("{:0>4X} {}", opcode, disasm::mnemonic(opcode.into()).unwrap_or_else(|| format!("DW 0x{:0>4X}", opcode)))
+            },
+            None => match program.instructions.get(offset) {
+                Some(byte) => format!("{:0>2X}   DB 0x{:0>2X}", byte, byte),
+                None => "--".to_string(),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for ProgramDiff<'_> {
+
+    /// Formats the differences as lines of the form `<address>: <left opcode> <left mnemonic> | <right opcode> <right mnemonic>`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for address in self.addresses() {
+            let offset = (address - PROGRAM_START) as usize;
+            writeln!(f, "{:0>4X}: {:<24} | {}", address, ProgramDiff::describe(self.left, offset), ProgramDiff::describe(self.right, offset))?;
+        }
+        write!(f, "")
+    }
+}
+
+/// A view of a `Program` that is formatted as a raw hex dump of its instructions. Created by `Program::raw()`.
+pub struct RawListing<'a>(&'a Program);
+
+impl std::fmt::Display for RawListing<'_> {
+
+    /// Formats the program as `<address>: <instruction>` where `address` and `instruction` are both represented as hexadecimal numbers.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for i in 0..self.instructions.len()/2 {
-            let x = 0x100u16 * *self.instructions.get(2*i).unwrap() as u16 + *self.instructions.get(2*i + 1).unwrap() as u16;
-            writeln!(f, "{:0>2X}: {:0>4X}", i, x)?;
+        for (i, pair) in self.0.instructions.chunks(2).enumerate() {
+            write!(f, "{:0>4X}: ", PROGRAM_START as usize + 2 * i)?;
+            for byte in pair {
+                write!(f, "{:0>2X}", byte)?;
+            }
+            writeln!(f)?;
         }
         write!(f, "")
     }
-}
\ No newline at end of file
+}
+
+/// A format in which a `Program` can be exported with `Program::export(...)`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A C array definition of the form `const unsigned char name[] = { ... };`.
+    CArray,
+    /// A Rust constant that embeds the ROM file with `include_bytes!`. The ROM has to be saved next to the Rust source file.
+    RustIncludeBytes,
+    /// Intel HEX records, with addresses starting at `PROGRAM_START`.
+    IntelHex,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    /// Parses an export format from its name: `c`, `rust` or `ihex`.
+    fn from_str(s: &str) -> Result<ExportFormat, String> {
+        match s.to_lowercase().as_str() {
+            "c" => Ok(ExportFormat::CArray),
+            "rust" => Ok(ExportFormat::RustIncludeBytes),
+            "ihex" | "hex" => Ok(ExportFormat::IntelHex),
+            _ => Err(format!("unknown export format '{}', expected 'c', 'rust' or 'ihex'", s)),
+        }
+    }
+}
+
+/// A view of a `Program` that is formatted in an `ExportFormat`. Created by `Program::export(...)`.
+pub struct Export<'a> {
+    program: &'a Program,
+    format: ExportFormat,
+}
+
+/// The number of data bytes per line in C arrays, and per record in Intel HEX files.
+const EXPORT_BYTES_PER_LINE: usize = 16;
+
+impl Export<'_> {
+
+    /// Derives an identifier from the program's title, replacing all characters that are not allowed in C and Rust identifiers with underscores.
+    fn identifier(&self) -> String {
+        let identifier: String = self.program.info().title.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+        match identifier.chars().next() {
+            Some(c) if !c.is_ascii_digit() => identifier,
+            _ => format!("rom_{}", identifier),
+        }
+    }
+
+    /// Writes the program as Intel HEX data records, followed by an end-of-file record.
+    fn write_intel_hex(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, chunk) in self.program.instructions.chunks(EXPORT_BYTES_PER_LINE).enumerate() {
+            let address = PROGRAM_START as usize + i * EXPORT_BYTES_PER_LINE;
+            let header = [chunk.len() as u8, (address >> 8) as u8, address as u8, 0x00];
+            let sum = header.iter().chain(chunk).fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+            write!(f, ":")?;
+            for byte in header.iter().chain(chunk) {
+                write!(f, "{:0>2X}", byte)?;
+            }
+            writeln!(f, "{:0>2X}", sum.wrapping_neg())?;
+        }
+        writeln!(f, ":00000001FF")
+    }
+}
+
+impl std::fmt::Display for Export<'_> {
+
+    /// Formats the program in the export format of the view.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bytes = &self.program.instructions;
+        match self.format {
+            ExportFormat::CArray => {
+                writeln!(f, "const unsigned char {}[{}] = {{", self.identifier(), bytes.len())?;
+                for chunk in bytes.chunks(EXPORT_BYTES_PER_LINE) {
+                    let line: Vec<String> = chunk.iter().map(|byte| format!("0x{:0>2X}", byte)).collect();
+                    writeln!(f, "    {},", line.join(", "))?;
+                }
+                writeln!(f, "}};")
+            },
+            ExportFormat::RustIncludeBytes => {
+                let file_name = self.program.file_name.clone().unwrap_or_else(|| format!("{}.ch8", self.program.info().title));
+                writeln!(f, "pub const {}: &[u8; {}] = include_bytes!({:?});", self.identifier().to_uppercase(), bytes.len(), file_name)
+            },
+            ExportFormat::IntelHex => self.write_intel_hex(f),
+        }
+    }
+}
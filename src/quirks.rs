@@ -0,0 +1,146 @@
+//! Contains the `Quirks` struct, describing the behaviors in which different CHIP-8 interpreters disagree with each other.
+
+/// A set of compatibility flags for instructions whose behavior differs between CHIP-8 interpreters.
+///
+/// ROMs are usually written against one particular interpreter, so running them correctly may require selecting the matching set of quirks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY6`/`8XYE` shift `VY` and store the result in `VX` (original COSMAC VIP), instead of shifting `VX` in place.
+    pub shift_uses_vy: bool,
+    /// `FX55`/`FX65` increment `I` by `X + 1` after storing/loading the registers (original COSMAC VIP).
+    pub load_store_increments_i: bool,
+    /// `BNNN` is interpreted as `BXNN` and jumps to `XNN + VX` (SUPER-CHIP), instead of `NNN + V0`.
+    pub jump_uses_vx: bool,
+    /// `8XY1`, `8XY2` and `8XY3` reset `VF` to `0` (original COSMAC VIP).
+    pub vf_reset: bool,
+    /// Sprites are clipped at the edges of the screen, instead of wrapping around to the opposite side.
+    pub clip_sprites: bool,
+}
+
+impl Quirks {
+
+    /// Creates a new instance of the `Quirks` struct, describing the behavior of this emulator's original implementation: registers are shifted in place,
+    /// `I` is left untouched by `FX55`/`FX65`, `BNNN` uses `V0`, `VF` is not reset by logic instructions and sprites are clipped.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::quirks::Quirks;
+    /// let quirks = Quirks::new();
+    /// assert!(quirks.clip_sprites);
+    /// ```
+    pub fn new() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: false,
+            vf_reset: false,
+            clip_sprites: true,
+        }
+    }
+
+    /// Creates the quirks of the original CHIP-8 interpreter of the COSMAC VIP: `8XY6`/`8XYE` shift `VY`, `FX55`/`FX65` increment `I`, `BNNN`
+    /// uses `V0`, logic instructions reset `VF` and sprites are clipped.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::quirks::Quirks;
+    /// let quirks = Quirks::chip8();
+    /// assert!(quirks.shift_uses_vy && quirks.load_store_increments_i);
+    /// ```
+    pub fn chip8() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            vf_reset: true,
+            clip_sprites: true,
+        }
+    }
+
+    /// Creates the quirks of the SUPER-CHIP 1.1 interpreter of the HP48: registers are shifted in place, `I` is left untouched by `FX55`/`FX65`,
+    /// `BNNN` jumps to `XNN + VX`, `VF` is not reset by logic instructions and sprites are clipped.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::quirks::Quirks;
+    /// let quirks = Quirks::schip();
+    /// assert!(quirks.jump_uses_vx && !quirks.shift_uses_vy);
+    /// ```
+    pub fn schip() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+        }
+    }
+
+    /// Applies a comma-separated list of settings of the form `name` (turns the quirk on) or `name=on`/`name=off`, using the field names of this
+    /// struct. The presets `chip8` and `schip` replace all quirks with those of `Quirks::chip8()` and `Quirks::schip()`, so they can be followed by
+    /// individual settings. An empty list or `default` leaves the quirks unchanged.
+    ///
+    /// # Errors
+    /// Returns an error message if a quirk name or value is unknown.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::quirks::Quirks;
+    /// let quirks = Quirks::new().with_flags("vf_reset,clip_sprites=off")?;
+    /// assert!(quirks.vf_reset && !quirks.clip_sprites);
+    /// let quirks = Quirks::new().with_flags("schip,clip_sprites=off")?;
+    /// assert!(quirks.jump_uses_vx && !quirks.clip_sprites);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_flags(mut self, flags: &str) -> Result<Quirks, String> {
+        for setting in flags.split(',').map(|s| s.trim()).filter(|s| !s.is_empty() && *s != "default") {
+            match setting {
+                "chip8" => {
+                    self = Quirks::chip8();
+                    continue;
+                },
+                "schip" => {
+                    self = Quirks::schip();
+                    continue;
+                },
+                _ => {},
+            }
+            let (name, value) = setting.split_once('=').unwrap_or((setting, "on"));
+            let value = match value {
+                "on" => true,
+                "off" => false,
+                _ => return Err(format!("invalid value '{}' for quirk '{}', expected on or off", value, name)),
+            };
+            let flag = match name {
+                "shift_uses_vy" => &mut self.shift_uses_vy,
+                "load_store_increments_i" => &mut self.load_store_increments_i,
+                "jump_uses_vx" => &mut self.jump_uses_vx,
+                "vf_reset" => &mut self.vf_reset,
+                "clip_sprites" => &mut self.clip_sprites,
+                _ => return Err(format!("unknown quirk '{}'", name)),
+            };
+            *flag = value;
+        }
+        Ok(self)
+    }
+}
+
+impl Default for Quirks {
+
+    fn default() -> Quirks {
+        Quirks::new()
+    }
+}
+
+impl std::fmt::Display for Quirks {
+
+    /// Formats the `Quirks` struct as a list of `name: on/off` lines.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = |flag: bool| if flag { "on" } else { "off" };
+        writeln!(f, "shift_uses_vy:           {}", state(self.shift_uses_vy))?;
+        writeln!(f, "load_store_increments_i: {}", state(self.load_store_increments_i))?;
+        writeln!(f, "jump_uses_vx:            {}", state(self.jump_uses_vx))?;
+        writeln!(f, "vf_reset:                {}", state(self.vf_reset))?;
+        write!(f, "clip_sprites:            {}", state(self.clip_sprites))
+    }
+}
@@ -0,0 +1,424 @@
+//! Contains the `Renderer` trait, through which `System::run_with(...)` outputs the display, and renderers that can be used in addition to or
+//! instead of the terminal `Display`: a `Tee` that forwards every frame to several renderers, a `GifRecorder` that records the session as an
+//! animated GIF, a `FrameDumper` that writes every frame to a numbered image file, and `PlainText`, which prints frames without escape sequences.
+//! With the `window` feature, the `WindowRenderer` of the `window` module shows the display in a window.
+
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use crate::system::{DisplayOption, System};
+
+/// The colors of pixels that are only on in the second display plane of the XO-CHIP, and of pixels that are on in both planes, as `0xRRGGBB`.
+pub const PLANE_COLORS: [u32; 2] = [0xFF6600, 0x662200];
+
+/// The palette of recorded frames, indexed by the bit mask of the display planes a pixel is on in: black for pixels that are off, white for
+/// pixels that are on in the first plane, and the `PLANE_COLORS` for the second and both planes of the XO-CHIP.
+const PALETTE: [u8; 12] = {
+    let [plane, both] = PLANE_COLORS;
+    [
+        0x00, 0x00, 0x00,
+        0xFF, 0xFF, 0xFF,
+        (plane >> 16) as u8, (plane >> 8) as u8, plane as u8,
+        (both >> 16) as u8, (both >> 8) as u8, both as u8,
+    ]
+};
+
+/// The minimum delay between two recorded GIF frames in hundredths of a second. Many viewers play shorter delays slower than intended, so changes
+/// within this delay are merged into a single frame.
+const MIN_GIF_DELAY: u16 = 2;
+
+/// A display backend of the fetch/decode/execute loop of `System::run_with(...)`.
+pub trait Renderer {
+
+    /// Prepares the output, e.g. by clearing the terminal. Called once when `System::run_with(...)` starts.
+    fn begin(&mut self) {}
+
+    /// Updates the output with the current state of the system. Called after every instruction, so implementations should skip the update if
+    /// `Memory::dirty_rows()` is `0`. Returns `true` if a new frame was rendered.
+    fn update(&mut self, sys: &System) -> bool;
+
+    /// Shows the title of the running program, if the output supports it. Called when a different program is loaded.
+    fn set_title(&mut self, _title: &str) {}
+
+    /// Moves the visible part of the display in the given directions (`-1`, `0` or `1`), if the output only shows a part of it. Called for
+    /// `InputEvent::Pan`.
+    fn pan(&mut self, _dx: i8, _dy: i8) {}
+
+    /// Toggles one of the rendering options, if the output supports it. Called for `InputEvent::ToggleDisplayOption`.
+    fn toggle(&mut self, _option: DisplayOption) {}
+
+    /// Shows a panel of text next to the display, such as the registers while the `Debugger` is paused. An empty text hides the panel. Called
+    /// whenever the state of the debugger changes.
+    fn show_panel(&mut self, _text: &str) {}
+
+    /// Shows a panel of text to the right of the display, such as the live `monitor(...)` of the `Debugger`. An empty text hides the panel. Called
+    /// a few times per second while the monitor is shown, so implementations should only redraw what changed.
+    fn show_side_panel(&mut self, _text: &str) {}
+}
+
+/// A `Renderer` that forwards everything to several other renderers, e.g. to show the display in the terminal while recording it.
+///
+/// # Example
+/// ```ignore
+/// let mut renderer = Tee::new().with(Display::new()).with(GifRecorder::create("session.gif", 4)?);
+/// sys.run_with(&mut renderer, &mut input);
+/// ```
+pub struct Tee {
+    renderers: Vec<Box<dyn Renderer>>,
+}
+
+impl Tee {
+
+    /// Creates a new `Tee` without any renderers.
+    pub fn new() -> Tee {
+        Tee { renderers: Vec::new() }
+    }
+
+    /// Adds a renderer to the `Tee`. Renderers are called in the order they were added.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let tee = Tee::new().with(Display::new());
+    /// ```
+    pub fn with<R: Renderer + 'static>(mut self, renderer: R) -> Tee {
+        self.renderers.push(Box::new(renderer));
+        self
+    }
+}
+
+impl Default for Tee {
+
+    fn default() -> Tee {
+        Tee::new()
+    }
+}
+
+impl Renderer for Tee {
+
+    fn begin(&mut self) {
+        for renderer in &mut self.renderers {
+            renderer.begin();
+        }
+    }
+
+    fn update(&mut self, sys: &System) -> bool {
+        let mut rendered = false;
+        for renderer in &mut self.renderers {
+            rendered |= renderer.update(sys);
+        }
+        rendered
+    }
+
+    fn set_title(&mut self, title: &str) {
+        for renderer in &mut self.renderers {
+            renderer.set_title(title);
+        }
+    }
+
+    fn pan(&mut self, dx: i8, dy: i8) {
+        for renderer in &mut self.renderers {
+            renderer.pan(dx, dy);
+        }
+    }
+
+    fn toggle(&mut self, option: DisplayOption) {
+        for renderer in &mut self.renderers {
+            renderer.toggle(option);
+        }
+    }
+
+    fn show_panel(&mut self, text: &str) {
+        for renderer in &mut self.renderers {
+            renderer.show_panel(text);
+        }
+    }
+
+    fn show_side_panel(&mut self, text: &str) {
+        for renderer in &mut self.renderers {
+            renderer.show_side_panel(text);
+        }
+    }
+}
+
+/// A `Renderer` that records the display as an animated GIF, using the wall-clock time between two changes as the frame delay.
+///
+/// Each CHIP-8 pixel is drawn as a square of `scale` x `scale` pixels. If the display changes its size later on (e.g. the high-resolution mode of
+/// the SUPER-CHIP), the pixels are scaled to keep the size of the GIF. The last frame is written when the recorder is dropped.
+pub struct GifRecorder {
+    file: Option<BufWriter<File>>,
+    encoder: Option<gif::Encoder<BufWriter<File>>>,
+    scale: u16,
+    width: u16,
+    height: u16,
+    pending: Option<(Vec<u8>, Instant)>,
+}
+
+impl GifRecorder {
+
+    /// Creates a recorder writing to the given file. The dimensions of the GIF are taken from the system when the first frame is recorded.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be created.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let recorder = GifRecorder::create("session.gif", 4)?;
+    /// ```
+    pub fn create<P>(path: P, scale: u16) -> io::Result<GifRecorder>
+        where P: AsRef<Path>, {
+            let file = BufWriter::new(File::create(path)?);
+            Ok(GifRecorder { file: Some(file), encoder: None, scale: scale.max(1), width: 0, height: 0, pending: None })
+    }
+
+    /// Writes the pending frame, displayed until `until`. Stops recording if the GIF cannot be written.
+    fn write_pending(&mut self, until: Instant) {
+        let (Some(encoder), Some((buffer, since))) = (self.encoder.as_mut(), self.pending.take()) else { return };
+        let delay = (until.duration_since(since).as_millis() / 10).clamp(MIN_GIF_DELAY as u128, u16::MAX as u128) as u16;
+        let frame = gif::Frame { width: self.width, height: self.height, delay, buffer: Cow::Owned(buffer), ..gif::Frame::default() };
+        if let Err(e) = encoder.write_frame(&frame) {
+            log::error!("Could not record GIF frame: {}", e);
+            self.encoder = None;
+        }
+    }
+}
+
+impl Renderer for GifRecorder {
+
+    fn update(&mut self, sys: &System) -> bool {
+        if sys.memory.dirty_rows() == 0 {
+            return false;
+        }
+        if let Some(file) = self.file.take() {
+            self.width = sys.screen_width as u16 * self.scale;
+            self.height = sys.screen_height as u16 * self.scale;
+            let encoder = gif::Encoder::new(file, self.width, self.height, &PALETTE).and_then(|mut encoder| {
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+                Ok(encoder)
+            });
+            match encoder {
+                Ok(encoder) => self.encoder = Some(encoder),
+                Err(e) => log::error!("Could not start GIF recording: {}", e),
+            }
+        }
+        if self.encoder.is_none() {
+            return false;
+        }
+
+        let now = Instant::now();
+        let scale = (self.width / sys.screen_width as u16).max(1);
+        let buffer = capture(sys, scale);
+        if buffer.len() != self.width as usize * self.height as usize {
+            log::debug!("Skipping a frame of {}x{} pixels that does not fit into the GIF", sys.screen_width, sys.screen_height);
+            return false;
+        }
+        match &mut self.pending {
+            Some((pending, _)) if *pending == buffer => false,
+            Some((pending, since)) if now.duration_since(*since).as_millis() < MIN_GIF_DELAY as u128 * 10 => {
+                *pending = buffer;
+                true
+            },
+            _ => {
+                self.write_pending(now);
+                self.pending = Some((buffer, now));
+                true
+            },
+        }
+    }
+}
+
+impl Drop for GifRecorder {
+
+    fn drop(&mut self) {
+        self.write_pending(Instant::now());
+        if let Some(encoder) = self.encoder.take() {
+            let result = encoder.into_inner().map_err(io::Error::other).and_then(|mut file| file.flush());
+            if let Err(e) = result {
+                log::error!("Could not finish GIF recording: {}", e);
+            }
+        }
+    }
+}
+
+/// A `Renderer` that writes every frame that differs from the previous one as a numbered binary PPM image (`frame_000001.ppm`, ...) into a
+/// directory, e.g. to assemble a video or to inspect single frames of flickering programs.
+///
+/// Each CHIP-8 pixel is drawn as a square of `scale` x `scale` pixels, in the colors of the display planes it is on in.
+///
+/// # Example
+/// ```
+/// # use chip8::program::Program;
+/// # use chip8::renderer::{FrameDumper, Renderer, PLANE_COLORS};
+/// # use chip8::system::{System, Variant};
+/// // Draw the font sprite of 0 into the second display plane of the XO-CHIP
+/// let mut sys = System::new();
+/// sys.set_variant(Variant::XoChip);
+/// sys.load(Program::from_hex_str("F201 A050 D015")?)?;
+/// for _ in 0..3 {
+///     sys.step();
+/// }
+/// let directory = std::env::temp_dir().join(format!("chip8-frames-{}", std::process::id()));
+/// let mut dumper = FrameDumper::create(&directory, 1)?;
+/// assert!(dumper.update(&sys));
+/// let image = std::fs::read(directory.join("frame_000001.ppm"))?;
+/// let pixels = &image[image.len() - sys.screen_width as usize * sys.screen_height as usize * 3..];
+/// assert_eq!(pixels[..3], PLANE_COLORS[0].to_be_bytes()[1..]);
+/// # std::fs::remove_dir_all(&directory)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct FrameDumper {
+    directory: PathBuf,
+    scale: u16,
+    frames: u64,
+    previous: Vec<u8>,
+}
+
+impl FrameDumper {
+
+    /// Creates a dumper writing into the given directory, which is created if it does not exist.
+    ///
+    /// # Errors
+    /// Returns an error if the directory cannot be created.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let dumper = FrameDumper::create("frames", 4)?;
+    /// ```
+    pub fn create<P>(directory: P, scale: u16) -> io::Result<FrameDumper>
+        where P: AsRef<Path>, {
+            fs::create_dir_all(&directory)?;
+            Ok(FrameDumper { directory: directory.as_ref().to_path_buf(), scale: scale.max(1), frames: 0, previous: Vec::new() })
+    }
+
+    /// Writes a frame of palette indices as a PPM image.
+    fn write_frame(&self, path: &Path, width: u16, height: u16, buffer: &[u8]) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        write!(file, "P6\n{} {}\n255\n", width, height)?;
+        for pixel in buffer {
+            let offset = *pixel as usize * 3;
+            file.write_all(&PALETTE[offset..offset + 3])?;
+        }
+        file.flush()
+    }
+}
+
+impl Renderer for FrameDumper {
+
+    fn update(&mut self, sys: &System) -> bool {
+        if sys.memory.dirty_rows() == 0 {
+            return false;
+        }
+        let buffer = capture(sys, self.scale);
+        if buffer == self.previous {
+            return false;
+        }
+        self.frames += 1;
+        let path = self.directory.join(format!("frame_{:0>6}.ppm", self.frames));
+        let (width, height) = (sys.screen_width as u16 * self.scale, sys.screen_height as u16 * self.scale);
+        if let Err(e) = self.write_frame(&path, width, height, &buffer) {
+            log::error!("Could not write '{}': {}", path.display(), e);
+        }
+        self.previous = buffer;
+        true
+    }
+}
+
+/// A minimal `Renderer` that prints every frame that differs from the previous one as plain text, one line per row with `#` for pixels that are
+/// on and `.` for pixels that are off, followed by an empty line. The debugger panel is printed the same way whenever it is shown.
+///
+/// Unlike the `Display`, it uses no escape sequences and does not depend on the size of the terminal, so its output can be piped into a file or
+/// compared between runs, e.g. when analyzing untrusted ROMs in a sandbox.
+///
+/// # Example
+/// ```ignore
+/// sys.run_with(&mut PlainText::new(), &mut input);
+/// ```
+pub struct PlainText {
+    out: Box<dyn Write>,
+    frames: u64,
+    previous: Vec<u8>,
+}
+
+impl PlainText {
+
+    /// Creates a renderer printing to stdout.
+    pub fn new() -> PlainText {
+        PlainText::with_output(Box::new(io::stdout()))
+    }
+
+    /// Creates a renderer writing to the given output.
+    pub fn with_output(out: Box<dyn Write>) -> PlainText {
+        PlainText { out, frames: 0, previous: Vec::new() }
+    }
+
+    /// Writes a frame of palette indices as text.
+    fn write_frame(&mut self, width: usize, buffer: &[u8]) -> io::Result<()> {
+        writeln!(self.out, "Frame {}", self.frames)?;
+        for row in buffer.chunks(width) {
+            let line: String = row.iter().map(|pixel| if *pixel > 0 { '#' } else { '.' }).collect();
+            writeln!(self.out, "{}", line)?;
+        }
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+impl Default for PlainText {
+
+    fn default() -> PlainText {
+        PlainText::new()
+    }
+}
+
+impl Renderer for PlainText {
+
+    fn update(&mut self, sys: &System) -> bool {
+        if sys.memory.dirty_rows() == 0 {
+            return false;
+        }
+        let buffer = capture(sys, 1);
+        if buffer == self.previous {
+            return false;
+        }
+        self.frames += 1;
+        if let Err(e) = self.write_frame(sys.screen_width as usize, &buffer) {
+            log::error!("Could not write frame {}: {}", self.frames, e);
+        }
+        self.previous = buffer;
+        true
+    }
+
+    fn show_panel(&mut self, text: &str) {
+        if !text.is_empty() {
+            let _res = writeln!(self.out, "{}\n", text).and_then(|_| self.out.flush());
+        }
+    }
+}
+
+/// Captures the display of the system as a buffer of palette indices, with each pixel scaled to `scale` x `scale`. The index of a pixel is the bit
+/// mask of the display planes it is on in (see `Memory::pixel_color(...)`), i.e. `0` for off and `1` for on with a single plane.
+///
+/// # Example
+/// ```
+/// # use chip8::renderer::capture;
+/// # use chip8::system::System;
+/// # let sys = System::new();
+/// let buffer = capture(&sys, 1);
+/// assert_eq!(buffer.len(), sys.screen_width as usize * sys.screen_height as usize);
+/// ```
+pub fn capture(sys: &System, scale: u16) -> Vec<u8> {
+    let scale = scale as usize;
+    let mut buffer = Vec::with_capacity(sys.screen_width as usize * sys.screen_height as usize * scale * scale);
+    for y in 0..sys.screen_height {
+        let mut row = Vec::with_capacity(sys.screen_width as usize * scale);
+        for x in 0..sys.screen_width {
+            let pixel = sys.memory.pixel_color(x, y);
+            row.extend(std::iter::repeat_n(pixel, scale));
+        }
+        for _ in 0..scale {
+            buffer.extend_from_slice(&row);
+        }
+    }
+    buffer
+}
@@ -0,0 +1,174 @@
+//! Records the input of a session to a file and replays it later, so a session (e.g. one that triggered a bug) can be reproduced exactly.
+//!
+//! An input log is a text file. Its header holds the seed of the random number generator of `CXNN` and the initial `loop_frequency`, followed by
+//! one line per key event or change of the speed, stamped with the value of `System::cycles()` before which it was applied:
+//!
+//! ```text
+//! chip8-input 2
+//! seed 12345678901234567890
+//! hz 700
+//! 1520 key 5 down
+//! 1520 key 5 up
+//! 1701 key A down
+//! 2200 hz 875
+//! ```
+//!
+//! Every key event is recorded on its own, in the order it was applied, so taps that start and end before the same instruction (which `FX0A`
+//! latches in `KeyMode::Queue`) are replayed as well. Since the random numbers only depend on the seed and all input is applied at the same
+//! instruction, a replayed session draws the same random numbers and takes the same branches as the recorded one. Input the log cannot reproduce,
+//! such as resetting the program or loading a state, is ignored while recording and replaying. See `System::record_input(...)` and
+//! `System::replay_input(...)`.
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// The first line of every input log, identifying the file format and its version.
+const HEADER: &str = "chip8-input 2";
+
+/// A single recorded change of the input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InputChange {
+    /// The key was pressed or released.
+    Key { key: u8, pressed: bool },
+    /// The `loop_frequency` changed to the given value, e.g. with the `+`/`-` hotkeys.
+    Frequency(u16),
+}
+
+/// Writes the changes of the input during a session to an input log.
+///
+/// # Example
+/// ```ignore
+/// let mut recorder = InputRecorder::create("run.log", seed, sys.loop_frequency())?;
+/// recorder.record_key(sys.cycles(), 0x5, true)?;
+/// recorder.record_frequency(sys.cycles(), sys.loop_frequency())?;
+/// ```
+pub struct InputRecorder {
+    out: BufWriter<File>,
+    frequency: u16,
+}
+
+impl InputRecorder {
+
+    /// Creates the input log at the given path and writes its header.
+    pub fn create<P>(path: P, seed: u64, frequency: u16) -> io::Result<InputRecorder>
+        where P: AsRef<Path>, {
+            let mut out = BufWriter::new(File::create(path)?);
+            writeln!(out, "{}\nseed {}\nhz {}", HEADER, seed, frequency)?;
+            Ok(InputRecorder { out, frequency })
+    }
+
+    /// Records a key event that was applied at the given cycle.
+    pub fn record_key(&mut self, cycle: u64, key: u8, pressed: bool) -> io::Result<()> {
+        writeln!(self.out, "{} key {:X} {}", cycle, key, if pressed { "down" } else { "up" })
+    }
+
+    /// Records the frequency at the given cycle, writing a line if it changed since the last call.
+    pub fn record_frequency(&mut self, cycle: u64, frequency: u16) -> io::Result<()> {
+        if self.frequency != frequency {
+            self.frequency = frequency;
+            writeln!(self.out, "{} hz {}", cycle, frequency)?;
+        }
+        Ok(())
+    }
+
+    /// Writes all recorded changes to the file.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// The contents of an input log, which are handed out in order by `next_due(...)` while the session is replayed.
+///
+/// # Example
+/// ```ignore
+/// let mut replay = InputReplay::load("run.log")?;
+/// while let Some(change) = replay.next_due(sys.cycles()) {
+///     println!("{:?}", change);
+/// }
+/// ```
+pub struct InputReplay {
+    seed: u64,
+    frequency: u16,
+    changes: VecDeque<(u64, InputChange)>,
+}
+
+impl InputReplay {
+
+    /// Loads the input log at the given path.
+    ///
+    /// # Errors
+    /// Returns an error of kind `InvalidData` if the file is not an input log or contains an invalid line.
+    pub fn load<P>(path: P) -> io::Result<InputReplay>
+        where P: AsRef<Path>, {
+            fs::read_to_string(path)?.parse()
+    }
+
+    /// Gets the seed of the random number generator the session was recorded with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Gets the `loop_frequency` the session was started with.
+    pub fn frequency(&self) -> u16 {
+        self.frequency
+    }
+
+    /// Removes and returns the next change if it is due at the given cycle, i.e. if it was recorded at this cycle or before.
+    pub fn next_due(&mut self, cycle: u64) -> Option<InputChange> {
+        match self.changes.front() {
+            Some((at, _)) if *at <= cycle => self.changes.pop_front().map(|(_, change)| change),
+            _ => None,
+        }
+    }
+
+    /// Checks whether all changes have been replayed.
+    pub fn is_finished(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl std::str::FromStr for InputReplay {
+    type Err = io::Error;
+
+    /// Parses the contents of an input log. Empty lines are ignored.
+    fn from_str(text: &str) -> Result<InputReplay, io::Error> {
+        let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid line '{}' in the input log", line));
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next() != Some(HEADER) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "file is not an input log"));
+        }
+        let mut header = |name: &str| {
+            let line = lines.next().unwrap_or("");
+            line.strip_prefix(name).and_then(|value| value.trim().parse::<u64>().ok()).ok_or_else(|| invalid(line))
+        };
+        let seed = header("seed")?;
+        let frequency = header("hz")?.clamp(1, u16::MAX as u64) as u16;
+
+        let mut changes = VecDeque::new();
+        for line in lines {
+            let change = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [cycle, "key", key, state] => {
+                    let key = u8::from_str_radix(key, 16).ok().filter(|key| *key < 16);
+                    let pressed = match *state {
+                        "down" => Some(true),
+                        "up" => Some(false),
+                        _ => None,
+                    };
+                    cycle.parse().ok().zip(key.zip(pressed).map(|(key, pressed)| InputChange::Key { key, pressed }))
+                },
+                [cycle, "hz", hz] => cycle.parse().ok().zip(hz.parse().ok().map(InputChange::Frequency)),
+                _ => None,
+            };
+            changes.push_back(change.ok_or_else(|| invalid(line))?);
+        }
+        Ok(InputReplay { seed, frequency, changes })
+    }
+}
+
+/// The input log of a `System`, which is either being written or replayed.
+pub enum InputLog {
+    Recording(InputRecorder),
+    Replaying(InputReplay),
+}
@@ -0,0 +1,309 @@
+//! Recognizes well-known ROMs and provides the settings they are known to run well with, so they work without tuning the quirks and the speed by hand.
+//!
+//! The database is a TOML file with one `[[rom]]` table per ROM. A ROM is identified by the SHA-1 hash or the CRC-32 checksum of its bytes, or,
+//! if neither is known, by its file name without extension (ignoring case), e.g. the names of the classic game packs:
+//! ```toml
+//! [[rom]]
+//! title = "Space Invaders (David Winter)"
+//! sha1 = ["<40 hex digits>"]
+//! crc32 = ["<8 hex digits>"]
+//! files = ["invaders"]
+//! variant = "chip8"
+//! quirks = "chip8,shift_uses_vy=off,load_store_increments_i=off"
+//! ipf = 15
+//! keys = { "7" = "4", "9" = "6" }
+//! ```
+//! All settings are optional. `quirks` uses the syntax of `Quirks::with_flags(...)`, `ipf` is the number of instructions per 60 Hz frame and `keys`
+//! remaps the CHIP-8 keys: `"7" = "4"` makes the key that sends CHIP-8 key 7 (`A`) send key 4 instead.
+//!
+//! A file name says little about the contents of a ROM (it may be a different version, or a different program altogether), so a profile that only
+//! matched by file name is meant as a suggestion, see `RomMatch::is_exact()`.
+//!
+//! A database is compiled into the emulator (see `RomDatabase::bundled()`), and `Program::load(...)` looks every ROM up in it, see
+//! `Program::profile()`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+use serde::Deserialize;
+use crate::config::Config;
+use crate::program::Program;
+use crate::quirks::Quirks;
+use crate::system::{Variant, DEFAULT_KEYMAP};
+
+/// The database that is compiled into the emulator.
+const BUNDLED: &str = include_str!("romdb.toml");
+
+/// The known-good settings of a ROM.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RomProfile {
+    pub title: String,
+    /// The variant of the CHIP-8 the ROM was written for.
+    pub variant: Option<Variant>,
+    /// The quirks the ROM expects, as accepted by `Quirks::with_flags(...)`. See `quirks(...)`.
+    pub quirk_flags: Option<String>,
+    /// The recommended number of instructions per 60 Hz frame.
+    pub instructions_per_frame: Option<u16>,
+    /// Maps each CHIP-8 key to the key that is pressed instead, see `System::keymap`.
+    pub keymap: [u8; 16],
+}
+
+impl RomProfile {
+
+    /// Applies the quirk flags of the profile to the given quirks. Quirks the profile does not specify are left unchanged.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.quirks = profile.quirks(sys.quirks);
+    /// ```
+    pub fn quirks(&self, quirks: Quirks) -> Quirks {
+        match &self.quirk_flags {
+            Some(flags) => quirks.with_flags(flags).unwrap_or(quirks),
+            None => quirks,
+        }
+    }
+
+    /// Applies all settings of the profile to the given configuration: the variant, the quirks, the speed and the key remappings.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut config = Config::new();
+    /// if let Some(found) = program.profile() {
+    ///     found.profile.apply(&mut config);
+    /// }
+    /// ```
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(variant) = self.variant {
+            config.variant = variant;
+        }
+        config.quirks = self.quirks(config.quirks);
+        if let Some(instructions) = self.instructions_per_frame {
+            config.loop_frequency = instructions.saturating_mul(60);
+        }
+        config.keymap = self.keymap;
+    }
+
+    /// Creates a profile from an entry of the database file, checking its settings.
+    fn from_entry(entry: &Entry) -> Result<RomProfile, String> {
+        let variant = entry.variant.as_deref().map(str::parse).transpose()?;
+        if let Some(flags) = &entry.quirks {
+            Quirks::new().with_flags(flags)?;
+        }
+        if entry.ipf == Some(0) || entry.ipf.is_some_and(|ipf| ipf > u16::MAX / 60) {
+            return Err(format!("invalid number of instructions per frame, expected a number between 1 and {}", u16::MAX / 60));
+        }
+        let mut keymap = DEFAULT_KEYMAP;
+        for (from, to) in &entry.keys {
+            let key = |digit: &str| u8::from_str_radix(digit, 16).ok().filter(|_| digit.len() == 1)
+                .ok_or_else(|| format!("invalid key '{}', expected a hexadecimal digit", digit));
+            keymap[key(from)? as usize] = key(to)?;
+        }
+        Ok(RomProfile {
+            title: entry.title.clone(),
+            variant,
+            quirk_flags: entry.quirks.clone(),
+            instructions_per_frame: entry.ipf,
+            keymap,
+        })
+    }
+}
+
+impl std::fmt::Display for RomProfile {
+
+    /// Formats the `RomProfile` as a list of `key: value` lines, omitting settings that are not set.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Title:    {}", self.title)?;
+        if let Some(variant) = self.variant {
+            let name = match variant {
+                Variant::Chip8 => "chip8",
+                Variant::SuperChip => "schip",
+                Variant::XoChip => "xochip",
+            };
+            write!(f, "\nVariant:  {}", name)?;
+        }
+        if let Some(flags) = &self.quirk_flags {
+            write!(f, "\nQuirks:   {}", flags)?;
+        }
+        if let Some(instructions) = self.instructions_per_frame {
+            write!(f, "\nSpeed:    {} instructions per frame", instructions)?;
+        }
+        let remapped: Vec<String> = self.keymap.iter().enumerate().filter(|(from, to)| *from != **to as usize)
+            .map(|(from, to)| format!("{:X}->{:X}", from, to)).collect();
+        if !remapped.is_empty() {
+            write!(f, "\nKeys:     {}", remapped.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// How a ROM was recognized by `RomDatabase::lookup(...)`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MatchKind {
+    Sha1,
+    Crc32,
+    /// Only the file name matched, so the ROM may be a different version or a different program with the same name.
+    FileName,
+}
+
+impl std::fmt::Display for MatchKind {
+
+    /// Formats the `MatchKind` as `SHA-1`, `CRC-32` or `file name`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatchKind::Sha1 => write!(f, "SHA-1"),
+            MatchKind::Crc32 => write!(f, "CRC-32"),
+            MatchKind::FileName => write!(f, "file name"),
+        }
+    }
+}
+
+/// A profile found for a ROM by `RomDatabase::lookup(...)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RomMatch {
+    pub profile: RomProfile,
+    pub matched_by: MatchKind,
+}
+
+impl RomMatch {
+
+    /// Checks whether the ROM was identified by its contents (its SHA-1 hash or CRC-32 checksum), so the profile can be applied safely. Profiles
+    /// that only matched by file name should merely be suggested.
+    ///
+    /// # Example
+    /// ```ignore
+    /// if let Some(found) = program.profile().filter(|found| found.is_exact()) {
+    ///     found.profile.apply(&mut config);
+    /// }
+    /// ```
+    pub fn is_exact(&self) -> bool {
+        self.matched_by != MatchKind::FileName
+    }
+}
+
+/// The contents of a database file.
+#[derive(Deserialize)]
+struct DatabaseFile {
+    #[serde(default)]
+    rom: Vec<Entry>,
+}
+
+/// A `[[rom]]` table of a database file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    title: String,
+    #[serde(default)]
+    sha1: Vec<String>,
+    #[serde(default)]
+    crc32: Vec<String>,
+    #[serde(default)]
+    files: Vec<String>,
+    variant: Option<String>,
+    quirks: Option<String>,
+    ipf: Option<u16>,
+    #[serde(default)]
+    keys: HashMap<String, String>,
+}
+
+/// A profile together with the fingerprints of the ROMs it applies to.
+struct Record {
+    sha1: Vec<String>,
+    crc32: Vec<u32>,
+    files: Vec<String>,
+    profile: RomProfile,
+}
+
+/// A collection of `RomProfile`s, identified by the fingerprints of the ROMs they apply to.
+///
+/// # Example
+/// ```ignore
+/// let database = RomDatabase::load("my-roms.toml")?;
+/// if let Some(found) = database.lookup(&program).or_else(|| romdb::lookup(&program)) {
+///     println!("{} (matched by {})", found.profile.title, found.matched_by);
+/// }
+/// ```
+pub struct RomDatabase {
+    records: Vec<Record>,
+}
+
+impl RomDatabase {
+
+    /// Gets the database that is compiled into the emulator. It is parsed on first use.
+    pub fn bundled() -> &'static RomDatabase {
+        static DATABASE: OnceLock<RomDatabase> = OnceLock::new();
+        DATABASE.get_or_init(|| BUNDLED.parse().expect("the bundled ROM database is valid"))
+    }
+
+    /// Loads a database from the TOML file at the given path.
+    ///
+    /// # Errors
+    /// Returns an error of kind `InvalidData` if the file is not a valid database.
+    pub fn load<P>(path: P) -> io::Result<RomDatabase>
+        where P: AsRef<Path>, {
+            fs::read_to_string(path)?.parse()
+    }
+
+    /// Gets the number of profiles in the database.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Checks whether the database contains no profiles.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Looks up the profile of the given program. Profiles matching its SHA-1 hash take precedence over those matching its CRC-32 checksum, which
+    /// take precedence over those matching its file name.
+    pub fn lookup(&self, program: &Program) -> Option<RomMatch> {
+        let info = program.info();
+        let stem = info.file_name.as_deref()
+            .and_then(|name| Path::new(name).file_stem())
+            .map(|stem| stem.to_string_lossy().to_lowercase());
+        let found = |matches: &dyn Fn(&Record) -> bool, matched_by| self.records.iter().find(|record| matches(record))
+            .map(|record| RomMatch { profile: record.profile.clone(), matched_by });
+        found(&|record| record.sha1.contains(&info.sha1), MatchKind::Sha1)
+            .or_else(|| found(&|record| record.crc32.contains(&info.crc32), MatchKind::Crc32))
+            .or_else(|| found(&|record| stem.as_ref().is_some_and(|stem| record.files.contains(stem)), MatchKind::FileName))
+    }
+}
+
+impl std::str::FromStr for RomDatabase {
+    type Err = io::Error;
+
+    /// Parses a database from its TOML representation.
+    fn from_str(text: &str) -> Result<RomDatabase, io::Error> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let file: DatabaseFile = toml::from_str(text).map_err(|e| invalid(e.to_string().trim_end().to_string()))?;
+        let mut records = Vec::with_capacity(file.rom.len());
+        for entry in &file.rom {
+            let profile = RomProfile::from_entry(entry).map_err(|e| invalid(format!("{}: {}", entry.title, e)))?;
+            let sha1 = entry.sha1.iter().map(|hash| hash.to_lowercase()).collect::<Vec<_>>();
+            if let Some(hash) = sha1.iter().find(|hash| hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit())) {
+                return Err(invalid(format!("{}: invalid SHA-1 hash '{}'", entry.title, hash)));
+            }
+            let crc32 = entry.crc32.iter()
+                .map(|crc| u32::from_str_radix(crc, 16).ok().filter(|_| crc.len() == 8).ok_or(crc))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|crc| invalid(format!("{}: invalid CRC-32 checksum '{}'", entry.title, crc)))?;
+            let files = entry.files.iter().map(|name| name.to_lowercase()).collect();
+            records.push(Record { sha1, crc32, files, profile });
+        }
+        Ok(RomDatabase { records })
+    }
+}
+
+/// Looks up the profile of the given program in the bundled database, see `RomDatabase::lookup(...)`.
+///
+/// # Example
+/// ```ignore
+/// if let Some(found) = romdb::lookup(&Program::from_bytes(bytes)) {
+///     println!("{}", found.profile);
+/// }
+/// ```
+pub fn lookup(program: &Program) -> Option<RomMatch> {
+    RomDatabase::bundled().lookup(program)
+}
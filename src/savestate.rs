@@ -0,0 +1,94 @@
+//! Contains the `SaveState` struct, a snapshot of the emulated machine that can be written to a file and restored later, e.g. to retry a hard part
+//! of a game. States are created with `System::snapshot()` and stored as JSON.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use crate::audio::DEFAULT_PITCH;
+use crate::system::{Memory, Registers, Stack, Timer, Variant};
+
+/// The version of the file format written by `SaveState::save(...)`. Files of other versions are rejected when loading.
+pub const SAVE_STATE_VERSION: u32 = 1;
+
+/// The complete state of the emulated machine, see `System::snapshot()`.
+///
+/// # Example
+/// ```ignore
+/// let state = sys.snapshot();
+/// state.save("pong.state")?;
+/// sys.restore(SaveState::load("pong.state")?);
+/// ```
+#[derive(Serialize, Deserialize)]
+pub struct SaveState {
+    /// The version of the file format, `SAVE_STATE_VERSION` for new states.
+    pub version: u32,
+    pub variant: Variant,
+    pub memory: Memory,
+    pub registers: Registers,
+    pub stack: Stack,
+    pub delay_timer: Timer,
+    pub sound_timer: Timer,
+    pub rpl_flags: [u8; 8],
+    pub pc: u16,
+    /// The state of the random number generator, so the restored machine draws the same numbers.
+    pub rng: ChaCha8Rng,
+    /// The number of instructions executed so far.
+    pub cycles: u64,
+    /// The time that has passed on the emulated machine, which the timers are synchronized to.
+    pub emulated_time: Duration,
+    /// Whether the program exited with `00FD`.
+    pub exited: bool,
+    /// The audio pattern of the XO-CHIP, see `System::set_audio_pattern(...)`.
+    #[serde(default)]
+    pub audio_pattern: Option<[u8; 16]>,
+    /// The pitch of the audio pattern, see `System::set_pitch(...)`.
+    #[serde(default = "default_pitch")]
+    pub pitch: u8,
+}
+
+/// The pitch of states saved before the XO-CHIP was supported.
+fn default_pitch() -> u8 {
+    DEFAULT_PITCH
+}
+
+impl SaveState {
+
+    /// Writes the state to the given file.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written.
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+        where P: AsRef<Path>, {
+            let json = serde_json::to_vec(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            fs::write(path, json)
+    }
+
+    /// Reads a state from the given file.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read, or an error of kind `InvalidData` if it does not contain a state of the current version.
+    pub fn load<P>(path: P) -> io::Result<SaveState>
+        where P: AsRef<Path>, {
+            let state: SaveState = serde_json::from_slice(&fs::read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            if state.version != SAVE_STATE_VERSION {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                    format!("save state version {} is not supported, expected {}", state.version, SAVE_STATE_VERSION)));
+            }
+            Ok(state)
+    }
+
+    /// Gets the file that stores the state of the given ROM: the ROM's path with the extension `.state`.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8::savestate::SaveState;
+    /// # use std::path::{Path, PathBuf};
+    /// assert_eq!(SaveState::file_for(Path::new("roms/pong.ch8")), PathBuf::from("roms/pong.state"));
+    /// ```
+    pub fn file_for(rom: &Path) -> PathBuf {
+        rom.with_extension("state")
+    }
+}
@@ -0,0 +1,130 @@
+//! Contains the `Scheduler` struct, which paces the fetch/decode/execute loop of the CHIP-8 system to a given target frequency, in frames of 1/60 s.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The number of frames per second. In each frame, the `Scheduler` executes the instructions due in it, and the loop ticks the timers and renders
+/// the display once.
+pub const FRAMES_PER_SECOND: u32 = 60;
+
+/// The maximum amount of time the emulation may fall behind its target timeline before the scheduler stops trying to catch up.
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// The final stretch of a wait that is spent spinning instead of sleeping, since the OS scheduler cannot reliably wake a thread up with sub-millisecond accuracy.
+const SPIN_THRESHOLD: Duration = Duration::from_micros(500);
+
+/// Blocks the current thread until the given point in time.
+///
+/// Waiting is performed as a hybrid of sleeping and spinning: the thread sleeps until shortly before the `deadline`, and then spin-waits for the remaining
+/// fraction of a millisecond. This avoids the coarse granularity of `thread::sleep` (up to 15 ms on some platforms) while still leaving the CPU idle for most
+/// of the waiting time.
+///
+/// # Example
+/// ```ignore
+/// let deadline = Instant::now() + Duration::from_millis(16);
+/// sleep_until(deadline);
+/// ```
+pub fn sleep_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline <= now {
+        return;
+    }
+    if deadline - now > SPIN_THRESHOLD {
+        thread::sleep(deadline - now - SPIN_THRESHOLD);
+    }
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+/// Paces the execution of instructions in frames of 1/60 s.
+///
+/// Each frame, the loop asks for the number of instructions due in it with `begin_frame()`, executes them, ticks the timers and renders, and then
+/// calls `end_frame()`, which sleeps until the frame's time slot on the target timeline has passed. The instructions per frame are
+/// `frequency / FRAMES_PER_SECOND`, and the remainder is carried over to the following frames, so exactly `frequency` instructions are executed per
+/// second. If the operating system oversleeps, the following frames are executed without delay until the emulation is back on the target timeline.
+/// If it falls behind by more than `MAX_LAG` (e.g. because of a slow display update), the timeline is re-aligned instead of executing a long burst
+/// of frames.
+pub struct Scheduler {
+    frequency: u16,
+    start: Instant,
+    frames: u64,
+    owed: u32,
+}
+
+impl Scheduler {
+
+    /// Creates a new instance of the `Scheduler` struct that paces execution to `frequency` instructions per second.
+    ///
+    /// The target timeline starts at the moment of creation.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let scheduler = Scheduler::new(700);
+    /// ```
+    pub fn new(frequency: u16) -> Scheduler {
+        Scheduler {
+            frequency: frequency.max(1),
+            start: Instant::now(),
+            frames: 0,
+            owed: 0,
+        }
+    }
+
+    /// Changes the target frequency. The target timeline is restarted at the current point in time, so the frames executed at the old frequency
+    /// are neither caught up on nor waited for.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut scheduler = Scheduler::new(700);
+    /// scheduler.set_frequency(1400);
+    /// ```
+    pub fn set_frequency(&mut self, frequency: u16) {
+        *self = Scheduler::new(frequency);
+    }
+
+    /// Gets the target frequency in instructions per second.
+    pub fn frequency(&self) -> u16 {
+        self.frequency
+    }
+
+    /// Starts the next frame and returns the number of instructions to execute in it.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut scheduler = Scheduler::new(700);
+    /// loop {
+    ///     for _ in 0..scheduler.begin_frame() {
+    ///         // execute an instruction
+    ///     }
+    ///     // tick the timers and render
+    ///     scheduler.end_frame();
+    /// }
+    /// ```
+    pub fn begin_frame(&mut self) -> u32 {
+        self.owed += self.frequency as u32;
+        let instructions = self.owed / FRAMES_PER_SECOND;
+        self.owed %= FRAMES_PER_SECOND;
+        instructions
+    }
+
+    /// Finishes the current frame and sleeps until the point in time at which it should have finished, or re-aligns the timeline if the emulation
+    /// has fallen too far behind.
+    pub fn end_frame(&mut self) {
+        self.frames += 1;
+        let target = self.start + self.target_offset();
+        let now = Instant::now();
+        if target > now {
+            sleep_until(target);
+        }
+        else if now - target > MAX_LAG {
+            log::debug!("Emulation fell behind by {:.2?}, re-aligning the timeline", now - target);
+            self.start = now - self.target_offset();
+        }
+    }
+
+    /// Computes the time that should have passed since `start` for the number of frames finished so far.
+    fn target_offset(&self) -> Duration {
+        Duration::from_nanos((self.frames as u128 * 1_000_000_000 / FRAMES_PER_SECOND as u128) as u64)
+    }
+}
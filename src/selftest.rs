@@ -0,0 +1,91 @@
+//! Checks the active quirks by running one tiny test program per quirk, generated with the `ProgramBuilder`, and observing which of the two
+//! possible behaviors the interpreter shows. Used by `chip8 selftest` to let users verify that their configuration matches what a ROM expects.
+
+use crate::builder::ProgramBuilder;
+use crate::quirks::Quirks;
+use crate::system::{Event, System};
+
+/// The result of checking a single quirk with `run_checks(...)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuirkCheck {
+    /// The name of the quirk, as used by `Quirks::with_flags(...)`.
+    pub name: &'static str,
+    /// Whether the quirk is enabled in the checked configuration.
+    pub configured: bool,
+    /// Whether the test program showed the behavior of the enabled quirk.
+    pub observed: bool,
+    /// A description of the observed behavior.
+    pub behavior: &'static str,
+}
+
+impl QuirkCheck {
+
+    /// Whether the observed behavior matches the configuration.
+    pub fn passed(&self) -> bool {
+        self.configured == self.observed
+    }
+}
+
+/// Runs one test program per quirk with the given quirks and reports which behavior each of them showed.
+///
+/// # Example
+/// ```
+/// # use chip8::selftest::run_checks;
+/// # use chip8::quirks::Quirks;
+/// for check in run_checks(Quirks::new()) {
+///     assert!(check.passed(), "{} does not behave as configured", check.name);
+/// }
+/// ```
+pub fn run_checks(quirks: Quirks) -> Vec<QuirkCheck> {
+    let mut checks = Vec::new();
+
+    // V1 = 0x01, V2 = 0x04, V1 = V? >> 1
+    let sys = run(quirks, ProgramBuilder::new().op(0x6101).op(0x6204).op(0x8126).op(0x0000));
+    let observed = sys.registers.get(0x1) == 0x02;
+    checks.push(check("shift_uses_vy", quirks.shift_uses_vy, observed, "8XY6/8XYE shift VY into VX", "8XY6/8XYE shift VX in place"));
+
+    // I = 0x300, store V0
+    let sys = run(quirks, ProgramBuilder::new().op(0xA300).op(0xF055).op(0x0000));
+    let observed = sys.registers.i() == 0x301;
+    checks.push(check("load_store_increments_i", quirks.load_store_increments_i, observed, "FX55/FX65 increment I", "FX55/FX65 leave I unchanged"));
+
+    // VF = 0x05, V0 |= V1
+    let sys = run(quirks, ProgramBuilder::new().op(0x6F05).op(0x6001).op(0x6102).op(0x8011).op(0x0000));
+    let observed = sys.registers.get(0xF) == 0x00;
+    checks.push(check("vf_reset", quirks.vf_reset, observed, "8XY1/8XY2/8XY3 reset VF", "8XY1/8XY2/8XY3 leave VF unchanged"));
+
+    // V0 = 2, V2 = 6, jump to table + V0 (lands on VA = 1) or table + V2 (lands on VA = 2); the table lies in 0x2XX
+    let sys = run(quirks, ProgramBuilder::new()
+        .op(0x6002)
+        .op(0x6206)
+        .jump_v0("table")
+        .label("table")
+        .op(0x0000)
+        .op(0x6A01)
+        .op(0x0000)
+        .op(0x6A02)
+        .op(0x0000));
+    let observed = sys.registers.get(0xA) == 0x02;
+    checks.push(check("jump_uses_vx", quirks.jump_uses_vx, observed, "BXNN jumps to XNN + VX", "BNNN jumps to NNN + V0"));
+
+    // Draw a row of 8 pixels at x = 60, so 4 of them cross the right edge
+    let sys = run(quirks, ProgramBuilder::new().load_i("row").op(0x603C).op(0x6100).op(0xD011).op(0x0000).label("row").sprite(&[0xFF]));
+    let observed = sys.memory.display_byte(0, 0) == 0x00;
+    checks.push(check("clip_sprites", quirks.clip_sprites, observed, "sprites are clipped at the edges", "sprites wrap around the edges"));
+
+    checks
+}
+
+/// Builds and runs a test program until it halts.
+fn run(quirks: Quirks, builder: &mut ProgramBuilder) -> System {
+    let mut sys = System::new();
+    sys.quirks = quirks;
+    sys.load(builder.build().expect("test programs only refer to defined labels")).expect("test programs are small");
+    sys.run_until(Event::Halt);
+    sys
+}
+
+/// Creates a `QuirkCheck`, describing the behavior that was observed.
+fn check(name: &'static str, configured: bool, observed: bool, enabled: &'static str, disabled: &'static str) -> QuirkCheck {
+    QuirkCheck { name, configured, observed, behavior: if observed { enabled } else { disabled } }
+}
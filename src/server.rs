@@ -0,0 +1,243 @@
+//! Serves the emulator over telnet: each client that connects gets its own `System` running the served ROM, rendered with the terminal `Display`
+//! and controlled with the keys typed into the telnet session.
+//!
+//! Since a telnet client only sends characters (and no key releases), a key is held for `KEY_HOLD_TIME` after its character was received. Holding
+//! a key down makes the client's terminal repeat the character, which keeps the key pressed.
+//!
+//! `accept_second_player(...)` uses the same protocol to let a remote client join a local session as a second player: the client sees a mirror of
+//! the local display and controls a subset of the keys.
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::input::{ChannelInput, InputEvent, KEYBOARD_KEYS};
+use crate::program::Program;
+use crate::quirks::Quirks;
+use crate::system::{Display, System};
+
+/// The time a key stays pressed after its character was received.
+const KEY_HOLD_TIME: Duration = Duration::from_millis(150);
+
+/// The interval in which held keys are checked for release while no data is received.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The telnet "Interpret As Command" byte, which starts a command sequence.
+const IAC: u8 = 255;
+
+/// The telnet commands sent to a client when it connects: `WILL ECHO` and `WILL SUPPRESS-GO-AHEAD` make the client send each character as soon as
+/// it is typed, without echoing it locally.
+const NEGOTIATION: [u8; 6] = [IAC, 251, 1, IAC, 251, 3];
+
+/// Accepts telnet connections on the given port and runs the program for each client on its own thread. This function only returns if the port
+/// cannot be bound.
+///
+/// # Example
+/// ```ignore
+/// serve(Program::load("pong.ch8")?, Quirks::new(), 2323)?;
+/// ```
+pub fn serve(program: Program, quirks: Quirks, port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    log::warn!("Serving '{}' on port {}", program.info().title, port);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("Could not accept connection: {}", e);
+                continue;
+            },
+        };
+        let program = program.clone();
+        thread::spawn(move || {
+            let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_else(|_| "unknown".to_string());
+            log::info!("Client {} connected", peer);
+            if let Err(e) = handle_client(stream, program, quirks) {
+                log::warn!("Connection to {} failed: {}", peer, e);
+            }
+            log::info!("Client {} disconnected", peer);
+        });
+    }
+    Ok(())
+}
+
+/// Runs the program for a single client until it halts or the client disconnects.
+fn handle_client(mut stream: TcpStream, program: Program, quirks: Quirks) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    stream.write_all(&NEGOTIATION)?;
+    write!(stream, "{}[?1049h{}[?25l", 27 as char, 27 as char)?;
+
+    let (tx, mut input) = ChannelInput::new();
+    let reader = stream.try_clone()?;
+    thread::spawn(move || read_keys(reader, tx, [true; 16], true));
+
+    let title = program.info().title;
+    let mut sys = System::new();
+    sys.quirks = quirks;
+    sys.load(program).map_err(io::Error::other)?;
+    let mut display = Display::with_output(Box::new(stream.try_clone()?));
+    display.set_title(&format!("CHIP-8 - {}", title));
+    sys.run_with(&mut display, &mut input);
+
+    write!(stream, "{}[0m{}[?25h{}[?1049l", 27 as char, 27 as char, 27 as char)?;
+    write!(stream, "CHIP-8 Finished after {:.2?} of emulated time!\r\n", sys.emulated_time())?;
+    stream.shutdown(Shutdown::Both)
+}
+
+/// Waits for a second player to connect to the given port with a telnet client, and then forwards the keys they type to `events`.
+///
+/// Only the CHIP-8 keys enabled in `keys` are forwarded; the controls of the emulator (speed, quitting) remain with the local player. Since the
+/// display is rendered incrementally, the second player has to join before the session starts, so the returned `Mirror` should be used as the
+/// output of the `Display` right away. If the second player disconnects, the session continues without them.
+///
+/// # Errors
+/// Returns an error if the port cannot be bound or the connection fails.
+///
+/// # Example
+/// ```ignore
+/// let mut input = keyboard();
+/// let mut keys = [false; 16];
+/// keys[0xC] = true;
+/// keys[0xD] = true;
+/// let mirror = accept_second_player(2324, keys, input.sender())?;
+/// let mut display = Display::with_output(Box::new(mirror));
+/// sys.run_with(&mut display, &mut input);
+/// ```
+pub fn accept_second_player(port: u16, keys: [bool; 16], events: Sender<InputEvent>) -> io::Result<Mirror> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    let (mut stream, peer) = listener.accept()?;
+    log::info!("Second player {} connected", peer);
+    stream.set_nodelay(true)?;
+    stream.write_all(&NEGOTIATION)?;
+    write!(stream, "{}[?1049h{}[?25l", 27 as char, 27 as char)?;
+
+    let reader = stream.try_clone()?;
+    thread::spawn(move || {
+        read_keys(reader, events, keys, false);
+        log::info!("Second player {} disconnected", peer);
+    });
+    Ok(Mirror { remote: Arc::new(Mutex::new(Some(stream))) })
+}
+
+/// An output that writes everything to stdout and mirrors it to a second player's connection, see `accept_second_player(...)`.
+///
+/// Errors of the connection are not reported; the connection is dropped instead. Clones share the same connection.
+#[derive(Clone)]
+pub struct Mirror {
+    remote: Arc<Mutex<Option<TcpStream>>>,
+}
+
+impl Mirror {
+
+    /// Restores the terminal of the second player and closes the connection. Called when the session ends.
+    ///
+    /// # Example
+    /// ```ignore
+    /// sys.run_with(&mut display, &mut input);
+    /// mirror.close("CHIP-8 Finished!");
+    /// ```
+    pub fn close(&self, message: &str) {
+        if let Some(mut stream) = self.remote.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            let _res = write!(stream, "{}[0m{}[?25h{}[?1049l{}\r\n", 27 as char, 27 as char, 27 as char, message);
+            let _res = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Applies `f` to the connection, dropping the connection if it fails.
+    fn with_remote<F>(&self, f: F)
+        where F: FnOnce(&mut TcpStream) -> io::Result<()>, {
+            let mut remote = self.remote.lock().unwrap_or_else(|e| e.into_inner());
+            if remote.as_mut().is_some_and(|stream| f(stream).is_err()) {
+                *remote = None;
+            }
+    }
+}
+
+impl Write for Mirror {
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.with_remote(|stream| stream.write_all(buf));
+        io::stdout().write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.with_remote(|stream| stream.flush());
+        io::stdout().flush()
+    }
+}
+
+/// Reads the characters typed by a client and translates them into `InputEvent`s, until the client disconnects or presses Ctrl-C.
+///
+/// Characters are mapped to CHIP-8 keys like on the local keyboard (see `KEYBOARD_KEYS`), but only keys enabled in `keys` are forwarded. If
+/// `controls` is set, `+`/`-` change the emulation speed, and Ctrl-C or disconnecting quits the emulation. Telnet command negotiation sequences
+/// (`IAC <command> <option>`) are skipped.
+fn read_keys(mut stream: TcpStream, events: Sender<InputEvent>, keys: [bool; 16], controls: bool) {
+    let mut releases: [Option<Instant>; 16] = [None; 16];
+    let mut buffer = [0u8; 64];
+    let mut skip = 0;
+    if stream.set_read_timeout(Some(POLL_INTERVAL)).is_err() {
+        if controls {
+            let _res = events.send(InputEvent::Quit);
+        }
+        return;
+    }
+
+    'read: loop {
+        let received = match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => 0,
+            Err(_) => break,
+        };
+
+        let now = Instant::now();
+        for byte in &buffer[..received] {
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            let event = match byte {
+                &IAC => {
+                    skip = 2;
+                    continue;
+                },
+                3 if controls => InputEvent::Quit,
+                3 => break 'read,
+                b'+' if controls => InputEvent::Faster,
+                b'-' if controls => InputEvent::Slower,
+                c => match KEYBOARD_KEYS.iter().position(|k| *k == c.to_ascii_uppercase()) {
+                    Some(key) if keys[key] => {
+                        let held = releases[key].replace(now + KEY_HOLD_TIME).is_some();
+                        if held {
+                            continue;
+                        }
+                        InputEvent::Key { key: key as u8, pressed: true }
+                    },
+                    _ => continue,
+                },
+            };
+            if events.send(event).is_err() || event == InputEvent::Quit {
+                return;
+            }
+        }
+
+        for (key, release) in releases.iter_mut().enumerate() {
+            if release.is_some_and(|deadline| deadline <= now) {
+                *release = None;
+                if events.send(InputEvent::Key { key: key as u8, pressed: false }).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+    if controls {
+        let _res = events.send(InputEvent::Quit);
+    }
+    else {
+        for key in (0..16).filter(|key| releases[*key as usize].is_some()) {
+            let _res = events.send(InputEvent::Key { key, pressed: false });
+        }
+    }
+}
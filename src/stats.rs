@@ -0,0 +1,34 @@
+//! Contains the `Stats` struct, a set of counters describing the work done by a `System`.
+
+use std::time::Duration;
+
+/// Counters collected while a `System` is running, as returned by `System::stats()`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of instructions executed.
+    pub instructions: u64,
+    /// The number of times the display was rendered because at least one pixel changed.
+    pub frames: u64,
+    /// The number of executed `DXYN` instructions.
+    pub draw_calls: u64,
+    /// The number of ticks of the delay and sound timers.
+    pub timer_ticks: u64,
+    /// The number of CHIP-8 key presses (releases are not counted).
+    pub key_presses: u64,
+    /// The wall-clock time spent in `System::run(...)`.
+    pub runtime: Duration,
+}
+
+impl std::fmt::Display for Stats {
+
+    /// Formats the `Stats` struct as a list of `name: value` lines, including the average instruction and frame rates.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rate = |count: u64| if self.runtime.is_zero() { 0.0 } else { count as f64 / self.runtime.as_secs_f64() };
+        writeln!(f, "Runtime:      {:.2?}", self.runtime)?;
+        writeln!(f, "Instructions: {} ({:.0}/s)", self.instructions, rate(self.instructions))?;
+        writeln!(f, "Frames:       {} ({:.1}/s)", self.frames, rate(self.frames))?;
+        writeln!(f, "Draw calls:   {}", self.draw_calls)?;
+        writeln!(f, "Timer ticks:  {}", self.timer_ticks)?;
+        write!(f, "Key presses:  {}", self.key_presses)
+    }
+}
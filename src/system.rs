@@ -1,35 +1,236 @@
 //! A collection of structs and functions used to represent the state of a CHIP-8 system.
 
-extern crate user32;
-use rand::rngs::ThreadRng as ThreadRng;
-use std::sync::mpsc::{self, Sender};
-use std::{thread};
-use std::time::{SystemTime, Duration, UNIX_EPOCH};
-use crate::program::{self, Instruction};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::VecDeque;
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::audio::{Audio, Silent, DEFAULT_PITCH};
+use crate::config::Config;
+use crate::debugger::Debugger;
+use crate::frames::{Frame, Frames};
+use crate::hexdump::HexDump;
+use crate::hexedit::EditorKey;
+use crate::input::{self, InputEvent, InputSource};
+use crate::persistence::PersistentRegion;
+use crate::program::{self, Instruction, SideEffects, PROGRAM_START};
+use crate::quirks::Quirks;
+use crate::replay::{InputChange, InputLog, InputRecorder, InputReplay};
+use crate::savestate::{SaveState, SAVE_STATE_VERSION};
+use crate::renderer::Renderer;
+use crate::stats::Stats;
+use crate::scheduler::{sleep_until, Scheduler, FRAMES_PER_SECOND};
+use crate::trace::{TraceEvent, Tracer};
 use crate::utils::{big_endian_8_2};
 
-#[deny(missing_docs)]
+/// The size of the main memory in bytes.
+pub const MEMORY_SIZE: usize = 4096;
+
+/// The size of the main memory of the XO-CHIP in bytes, the whole 16-bit address space.
+pub const XO_MEMORY_SIZE: usize = 0x10000;
+
+/// The size of the video memory of each display plane in bytes, which follows the main memory and holds the display buffer of variants with
+/// larger displays (see `Variant::SuperChip`), so it does not take away memory from the program.
+pub const VIDEO_MEMORY_SIZE: usize = 0x400;
+
+/// The address at which the video memory starts, right after the 12-bit address space of the main memory. A display buffer at or behind this
+/// address always lies in the video memory, which the XO-CHIP moves behind its larger main memory.
+pub const VIDEO_MEMORY_START: u16 = MEMORY_SIZE as u16;
+
+/// The address at which the sprites of the built-in hexadecimal font are stored.
+pub const FONT_START: u16 = 0x50;
+
+/// The address at which the 8x10 sprites of the big hexadecimal font of the SUPER-CHIP (see `FX30`) are stored, right after the small font.
+pub const BIG_FONT_START: u16 = 0xA0;
+
+/// The first address after the built-in fonts.
+pub const FONT_END: u16 = 0x140;
+
+/// The number of terminal columns taken up by a `Display` of 64x32 pixels, including its border.
+pub const DISPLAY_COLUMNS: u16 = 130;
+
+/// The address at which the display buffer starts by default.
+pub const DISPLAY_START: u16 = 0xF00;
+
+/// The location and size of the display buffer in memory, set with `Memory::set_display_layout(...)`.
+/// 
+/// Each row of pixels occupies `width / 8` consecutive bytes, with the most significant bit of each byte being the leftmost pixel.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayLayout {
+    /// The address of the first byte of the display buffer.
+    pub start: u16,
+    /// The width of the display in pixels, a multiple of 8.
+    pub width: u8,
+    /// The height of the display in pixels, at most 64.
+    pub height: u8,
+}
+
+impl DisplayLayout {
+
+    /// The layout of the original CHIP-8: 64x32 pixels in the last 0x100 bytes of the memory, starting at `DISPLAY_START`.
+    pub const DEFAULT: DisplayLayout = DisplayLayout { start: DISPLAY_START, width: 64, height: 32 };
+
+    /// The low-resolution layout of the SUPER-CHIP: 64x32 pixels in the video memory.
+    pub const LORES: DisplayLayout = DisplayLayout { start: VIDEO_MEMORY_START, width: 64, height: 32 };
+
+    /// The high-resolution layout of the SUPER-CHIP, selected with `00FF`: 128x64 pixels in the video memory.
+    pub const HIRES: DisplayLayout = DisplayLayout { start: VIDEO_MEMORY_START, width: 128, height: 64 };
+
+    /// Gets the number of bytes per row of pixels.
+    pub fn bytes_per_row(&self) -> u16 {
+        self.width as u16 / 8
+    }
+
+    /// Gets the size of the display buffer in bytes.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::DisplayLayout;
+    /// assert_eq!(DisplayLayout::DEFAULT.len(), 0x100);
+    /// ```
+    pub fn len(&self) -> u16 {
+        self.bytes_per_row() * self.height as u16
+    }
+
+    /// Checks whether the display buffer has no bytes, which `Memory::set_display_layout(...)` never accepts.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the first address after the display buffer.
+    pub fn end(&self) -> u16 {
+        self.start + self.len()
+    }
+
+    /// Checks whether the given address lies within the display buffer.
+    pub fn contains(&self, address: u16) -> bool {
+        (self.start..self.end()).contains(&address)
+    }
+}
+
+/// The variant of the CHIP-8 that a `System` emulates, set with `System::set_variant(...)`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Variant {
+    /// The original CHIP-8 of the COSMAC VIP, with a 64x32 display buffer in the main memory.
+    Chip8,
+    /// The SUPER-CHIP 1.1 of the HP48 calculators, which adds a 128x64 high-resolution mode (`00FF`/`00FE`), scrolling (`00CN`, `00FB`, `00FC`),
+    /// 16x16 sprites (`DXY0`), a big font (`FX30`), the RPL user flags (`FX75`/`FX85`) and an exit instruction (`00FD`). The display buffer lies in
+    /// the video memory.
+    SuperChip,
+    /// The XO-CHIP of Octo, which extends the SUPER-CHIP with 64 KiB of memory (`F000 NNNN` loads a 16-bit address into `I`), a second display
+    /// plane for four colors (selected with `FN01`), scrolling up (`00DN`), saving and loading register ranges (`5XY2`/`5XY3`) and an audio pattern
+    /// buffer (`F002`, `FX3A`).
+    XoChip,
+}
+
+impl Variant {
+
+    /// Creates the memory of the variant: `XO_MEMORY_SIZE` bytes with two display planes for the XO-CHIP, the default `Memory` otherwise.
+    pub fn memory(self) -> Memory {
+        match self {
+            Variant::XoChip => Memory::with_size(XO_MEMORY_SIZE, 2),
+            Variant::Chip8 | Variant::SuperChip => Memory::new(),
+        }
+    }
+
+    /// Checks whether the variant supports the instructions of the SUPER-CHIP, which the XO-CHIP includes.
+    pub fn superchip(self) -> bool {
+        self != Variant::Chip8
+    }
+}
 
-/// Represents the main memory of a CHIP-8 system. In our implementation, it contains 4096 bytes that can be accessed and modified using the `get(...)` and `store(...)` methods.
+impl std::str::FromStr for Variant {
+    type Err = String;
+
+    /// Parses a variant from its name, `chip8`, `schip` or `xochip`.
+    fn from_str(s: &str) -> Result<Variant, String> {
+        match s.to_lowercase().as_str() {
+            "chip8" | "chip-8" => Ok(Variant::Chip8),
+            "schip" | "superchip" | "super-chip" => Ok(Variant::SuperChip),
+            "xochip" | "xo-chip" => Ok(Variant::XoChip),
+            _ => Err(format!("Unknown variant '{}', expected chip8, schip or xochip", s)),
+        }
+    }
+}
+
+#[deny(missing_docs)]
+/// Represents the main memory of a CHIP-8 system. By default, it contains 4096 bytes that can be accessed and modified using the `get(...)` and
+/// `store(...)` methods; the XO-CHIP uses 64 KiB instead (see `with_size(...)`).
 /// 
-/// Also provides functionality for the access of the display buffer, which is stored in the last 0x100 bytes of the memory.
+/// Also provides functionality for the access of the display buffer, which is stored in the last 0x100 bytes of the memory by default. Its location
+/// and size can be changed with `set_display_layout(...)`, e.g. for variants with a different memory map or larger displays. The main memory is
+/// followed by `VIDEO_MEMORY_SIZE` bytes of video memory per display plane, which can only hold a display buffer. With more than one plane, the
+/// display buffer is repeated for each plane in the video memory, and the pixels of all planes are composited into one color per pixel.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Memory {
-    memory: [u8; 4096],
+    memory: Vec<u8>,
+    #[serde(default = "default_size")]
+    size: usize,
+    #[serde(default = "one")]
+    planes: u8,
+    #[serde(default = "one")]
+    selected_planes: u8,
+    display: DisplayLayout,
+    #[serde(skip, default = "all_rows")]
+    dirty_rows: u64,
+}
+
+/// The size of the main memory of states saved before the memory size became configurable.
+fn default_size() -> usize {
+    MEMORY_SIZE
+}
+
+/// The number of display planes (and the plane mask) of states saved before the XO-CHIP was supported.
+fn one() -> u8 {
+    1
+}
+
+/// Marks all rows of a deserialized `Memory` as changed, so the restored display is rendered completely.
+fn all_rows() -> u64 {
+    u64::MAX
 }
 
 impl Memory {
 
-    /// Creates a new `Memory` object.
+    /// Creates a new `Memory` object with `MEMORY_SIZE` bytes of main memory and a single display plane.
     /// 
-    /// Font data for the sprites of all 16 hexadecimal digits is immediately loaded into the address space 0x50-0x9F.
+    /// Font data for the sprites of all 16 hexadecimal digits is immediately loaded into the address space 0x50-0x9F, followed by the 8x10 sprites of
+    /// the big font in 0xA0-0x13F.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let mem = Memory::new();
     /// ```
     /// 
     pub fn new() -> Memory {
-        let mut mem = Memory { memory: [0u8; 4096] };
+        Memory::with_size(MEMORY_SIZE, 1)
+    }
+
+    /// Creates a new `Memory` object with the given size of the main memory in bytes and the given number of display planes, e.g.
+    /// `XO_MEMORY_SIZE` and 2 for the XO-CHIP. The fonts are loaded like by `new()`, and only the first plane is selected.
+    /// 
+    /// # Panics
+    /// If the size is smaller than `MEMORY_SIZE` or larger than `XO_MEMORY_SIZE`, or the number of planes is not between 1 and 8.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::{Memory, XO_MEMORY_SIZE};
+    /// let mem = Memory::with_size(XO_MEMORY_SIZE, 2);
+    /// assert_eq!(mem.size(), 0x10000);
+    /// ```
+    /// 
+    pub fn with_size(size: usize, planes: u8) -> Memory {
+        assert!((MEMORY_SIZE..=XO_MEMORY_SIZE).contains(&size), "memory size {} is not between {} and {}", size, MEMORY_SIZE, XO_MEMORY_SIZE);
+        assert!((1..=8).contains(&planes), "number of display planes {} is not between 1 and 8", planes);
+        let memory = vec![0u8; size + VIDEO_MEMORY_SIZE * planes as usize];
+        let mut mem = Memory { memory, size, planes, selected_planes: 1, display: DisplayLayout::DEFAULT, dirty_rows: 0 };
         let font_sprites = [
             0xF0, 0x90, 0x90, 0x90, 0xF0,
             0x20, 0x60, 0x20, 0x20, 0x70,
@@ -49,18 +250,40 @@ impl Memory {
             0xF0, 0x80, 0xF0, 0x80, 0x80
         ];
         for (idx, byte) in font_sprites.into_iter().enumerate() {
-            mem.store(0x50 + idx as u16, byte);
+            mem.store(FONT_START + idx as u16, byte);
+        }
+        let big_font_sprites = [
+            0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF,
+            0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF,
+            0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,
+            0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,
+            0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03,
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,
+            0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18,
+            0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,
+            0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,
+            0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3,
+            0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC,
+            0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C,
+            0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC,
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0
+        ];
+        for (idx, byte) in big_font_sprites.into_iter().enumerate() {
+            mem.store(BIG_FONT_START + idx as u16, byte);
         }
 
         mem
     }
 
-    /// Fetches the value of the byte at a given 12-bit address.
+    /// Fetches the value of the byte at a given address.
     /// 
-    /// The address is represented as a `u16` in Rust, but the address space only has a size of 12 bits. Accessing a higher address will return `0`.
+    /// The address space has a size of 12 bits, or 16 bits for the XO-CHIP. With 12 bits, addresses from `VIDEO_MEMORY_START` access the video
+    /// memory, and accessing a higher address will return `0`.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let mem = Memory::new();
     /// let x = mem.get(0x50);
     /// ```
@@ -72,75 +295,336 @@ impl Memory {
         self.memory[address as usize]
     }
 
-    /// Stores a given 8-bit value to a 12-bit address.
+    /// Stores a given 8-bit value to an address.
     /// 
-    /// The address is represented as a `u16` in Rust, but the address space only has a size of 12 bits. Accessing a higher address will result in a panic.
+    /// The address space has a size of 12 bits, or 16 bits for the XO-CHIP. With 12 bits, addresses from `VIDEO_MEMORY_START` access the video
+    /// memory, and storing to a higher address has no effect.
     /// 
     /// # Example
     /// ```
+    /// # use chip8::system::Memory;
     /// let mut mem = Memory::new();
     /// mem.store(0x300, 42);
+    /// mem.store(0xFFFF, 42);
+    /// assert_eq!((mem.get(0x300), mem.get(0xFFFF)), (42, 0));
     /// ```
     /// 
     pub fn store(&mut self, address: u16, value: u8) {
+        if address as usize >= self.memory.len() {
+            return;
+        }
         self.memory[address as usize] = value;
     }
 
-    /// Sets or resets the pixel at the given coordinates.
+    /// Gets the size of the main memory in bytes, `MEMORY_SIZE` or `XO_MEMORY_SIZE`.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Gets the number of display planes.
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    /// Gets the bit mask of the display planes that `draw_plane_byte(...)`, `clear_display()` and `scroll(...)` operate on, as selected by the
+    /// XO-CHIP instruction `FN01`.
+    pub fn selected_planes(&self) -> u8 {
+        self.selected_planes
+    }
+
+    /// Selects the display planes that drawing, clearing and scrolling operate on. Bits of planes that do not exist are ignored.
     /// 
-    /// If the pixel was already set, it will be reset and `true` will be returned. Otherwise, it will be set and `false` is returned.
+    /// # Example
+    /// ```ignore
+    /// let mut mem = Memory::with_size(XO_MEMORY_SIZE, 2);
+    /// mem.select_planes(0b11);
+    /// ```
+    /// 
+    pub fn select_planes(&mut self, mask: u8) {
+        self.selected_planes = mask & ((1u16 << self.planes) - 1) as u8;
+    }
+
+    /// Gets the index into the backing store of the byte at the given offset of the display buffer of a plane. Display buffers at or behind
+    /// `VIDEO_MEMORY_START` lie in the video memory behind the main memory, and the buffers of the planes follow each other.
+    fn buffer_index(&self, plane: u8, offset: u16) -> usize {
+        let start = match self.display.start.checked_sub(VIDEO_MEMORY_START) {
+            Some(video_offset) => self.size + video_offset as usize,
+            None => self.display.start as usize,
+        };
+        start + plane as usize * self.display.len() as usize + offset as usize
+    }
+
+    /// Gets the byte at the given offset of the display buffer of a plane, where offset `0` is the top-left corner of the display.
     /// 
     /// # Example
     /// ```
+    /// # use chip8::system::Memory;
+    /// let mut mem = Memory::new();
+    /// mem.draw_byte(0, 0, 0xF0, true);
+    /// assert_eq!(mem.display_byte(0, 0), 0xF0);
+    /// ```
+    /// 
+    pub fn display_byte(&self, plane: u8, offset: u16) -> u8 {
+        self.memory.get(self.buffer_index(plane, offset)).copied().unwrap_or(0)
+    }
+
+    /// Gets the byte at the given offset of the display buffer with the planes composited, i.e. a pixel is on if it is on in any plane.
+    pub fn composite_byte(&self, offset: u16) -> u8 {
+        (0..self.planes).fold(0, |byte, plane| byte | self.display_byte(plane, offset))
+    }
+
+    /// Draws a single byte of sprite data at the given coordinates by XOR-ing it into the display buffer of the first plane. See
+    /// `draw_plane_byte(...)`.
+    /// 
+    /// # Example
+    /// ```ignore
     /// let mut mem = Memory::new();
-    /// let was_set = mem.flip_pixel(42, 24);
+    /// let collision = mem.draw_byte(42, 24, 0xF0, true);
     /// ```
     /// 
-    pub fn flip_pixel(&mut self, x: u8, y: u8) -> bool {
-        let idx = 0xF00 + (x as u16 + y as u16 * 64u16) / 8;
-        let value = 1 << (7 - x % 8);
-        let current = self.get(idx);
-        let reset = (current & value) > 0;
-        self.store(idx, current ^ value);
-        reset
+    pub fn draw_byte(&mut self, x: u8, y: u8, byte: u8, clip: bool) -> bool {
+        self.draw_plane_byte(0, x, y, byte, clip)
     }
 
-    /// Clears the display buffer
+    /// Draws a single byte of sprite data at the given coordinates by XOR-ing it into the display buffer of the given plane.
     /// 
-    /// The display buffer occupies address space 0xF00-0xFFF. This method resets all bytes in this space to 0.
+    /// The 8 pixels of the byte are written with whole-byte operations: if `x` is not a multiple of 8, the byte is split across two adjacent bytes of
+    /// the display buffer using shifts. Pixels that would end up beyond the right edge of the display are clipped if `clip` is set, otherwise they wrap
+    /// around to the left edge of the same row.
+    /// Returns `true` if any pixel that was already set has been reset by this operation (i.e. a collision occurred).
     /// 
     /// # Example
+    /// ```ignore
+    /// let mut mem = Memory::with_size(XO_MEMORY_SIZE, 2);
+    /// mem.set_display_layout(DisplayLayout::LORES);
+    /// let collision = mem.draw_plane_byte(1, 42, 24, 0xF0, true);
     /// ```
+    /// 
+    pub fn draw_plane_byte(&mut self, plane: u8, x: u8, y: u8, byte: u8, clip: bool) -> bool {
+        let bytes_per_row = self.display.bytes_per_row();
+        let row = y as u16 * bytes_per_row;
+        let column = (x / 8) as u16;
+        let offset = x % 8;
+
+        let left = byte >> offset;
+        let index = self.buffer_index(plane, row + column);
+        let mut collision = self.memory[index] & left != 0;
+        self.memory[index] ^= left;
+
+        if offset > 0 && (column < bytes_per_row - 1 || !clip) {
+            let right = byte << (8 - offset);
+            let index = self.buffer_index(plane, row + (column + 1) % bytes_per_row);
+            collision |= self.memory[index] & right != 0;
+            self.memory[index] ^= right;
+        }
+
+        self.dirty_rows |= 1 << y;
+        collision
+    }
+
+    /// Clears the display buffer of the selected planes (see `select_planes(...)`).
+    /// 
+    /// The display buffer occupies address space 0xF00-0xFFF by default (see `display_layout()`). This method resets all bytes in this space to 0.
+    /// 
+    /// # Example
+    /// ```ignore
     /// let mut mem = Memory::new();
     /// mem.clear_display();
     /// ```
     /// 
     pub fn clear_display(&mut self) {
-        for i in 0xF00..=0xFFF {
-            self.store(i, 0);
+        self.clear_planes(self.selected_planes);
+    }
+
+    /// Clears the display buffers of the planes in the given bit mask.
+    fn clear_planes(&mut self, mask: u8) {
+        for plane in (0..self.planes).filter(|plane| mask & (1 << plane) != 0) {
+            let start = self.buffer_index(plane, 0);
+            self.memory[start..start + self.display.len() as usize].fill(0);
+        }
+        self.dirty_rows = u64::MAX;
+    }
+
+    /// Gets the location and size of the display buffer.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::{Memory, DISPLAY_START};
+    /// let mem = Memory::new();
+    /// assert_eq!(mem.display_layout().start, DISPLAY_START);
+    /// ```
+    /// 
+    pub fn display_layout(&self) -> DisplayLayout {
+        self.display
+    }
+
+    /// Moves and/or resizes the display buffer, and clears it in all planes. The bytes of the previous buffer are left untouched.
+    /// 
+    /// # Panics
+    /// If the width is not a positive multiple of 8, the height is not between 1 and 64, or the buffers of all planes do not fit into the memory.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut mem = Memory::new();
+    /// mem.set_display_layout(DisplayLayout { start: 0xE00, width: 128, height: 16 });
+    /// ```
+    /// 
+    pub fn set_display_layout(&mut self, layout: DisplayLayout) {
+        assert!(layout.width > 0 && layout.width.is_multiple_of(8), "display width {} is not a positive multiple of 8", layout.width);
+        assert!((1..=64).contains(&layout.height), "display height {} is not between 1 and 64", layout.height);
+        self.display = layout;
+        assert!(self.buffer_index(self.planes, 0) <= self.memory.len(), "display buffer at 0x{:0>3X} does not fit into memory", layout.start);
+        self.clear_planes(u8::MAX);
+    }
+
+    /// Scrolls the contents of the display buffer of the selected planes by the given number of pixels, to the right for positive `dx` and down for
+    /// positive `dy`. Pixels that are scrolled out of the display are lost, and the uncovered pixels are turned off.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::Memory;
+    /// let mut mem = Memory::new();
+    /// mem.draw_byte(0, 0, 0x80, true);
+    /// mem.scroll(4, 1);
+    /// assert!(mem.pixel(4, 1));
+    /// ```
+    /// 
+    pub fn scroll(&mut self, dx: i16, dy: i16) {
+        let (width, height) = (self.display.width as i16, self.display.height as i16);
+        for plane in (0..self.planes).filter(|plane| self.selected_planes & (1 << plane) != 0) {
+            let mut pixels = vec![false; width as usize * height as usize];
+            for y in 0..height {
+                for x in 0..width {
+                    let (from_x, from_y) = (x - dx, y - dy);
+                    if (0..width).contains(&from_x) && (0..height).contains(&from_y) {
+                        pixels[(y * width + x) as usize] = self.plane_pixel(plane, from_x as u8, from_y as u8);
+                    }
+                }
+            }
+            for offset in 0..self.display.len() {
+                let first = offset as usize * 8;
+                let index = self.buffer_index(plane, offset);
+                self.memory[index] = pixels[first..first + 8].iter().fold(0u8, |byte, on| byte << 1 | *on as u8);
+            }
+        }
+        self.dirty_rows = u64::MAX;
+    }
+
+    /// Checks whether the pixel at the given position is on in the display buffer of the given plane.
+    fn plane_pixel(&self, plane: u8, x: u8, y: u8) -> bool {
+        let byte = self.display_byte(plane, y as u16 * self.display.bytes_per_row() + x as u16 / 8);
+        byte & (0x80 >> (x % 8)) != 0
+    }
+
+    /// Checks whether the pixel at the given position of the display buffer is on in any plane. Positions outside of the display are off.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::Memory;
+    /// let mut mem = Memory::new();
+    /// mem.draw_byte(8, 2, 0x80, true);
+    /// assert!(mem.pixel(8, 2));
+    /// ```
+    /// 
+    pub fn pixel(&self, x: u8, y: u8) -> bool {
+        self.pixel_color(x, y) != 0
+    }
+
+    /// Gets the color of the pixel at the given position as a bit mask of the planes it is on in, e.g. `0b10` if it is only on in the second plane.
+    /// Positions outside of the display are `0`.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::Memory;
+    /// let mut mem = Memory::new();
+    /// mem.draw_byte(8, 2, 0x80, true);
+    /// assert_eq!(mem.pixel_color(8, 2), 1);
+    /// ```
+    /// 
+    pub fn pixel_color(&self, x: u8, y: u8) -> u8 {
+        if x >= self.display.width || y >= self.display.height {
+            return 0;
+        }
+        (0..self.planes).filter(|plane| self.plane_pixel(*plane, x, y)).fold(0, |color, plane| color | 1 << plane)
+    }
+
+    /// Gets a bit mask of the display rows that were modified since the last call of `clear_dirty_rows()`.
+    /// 
+    /// Bit `n` of the mask is set if row `n` of the display buffer was touched by `draw_byte(...)` or `clear_display()`.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::Memory;
+    /// let mut mem = Memory::new();
+    /// mem.draw_byte(42, 3, 0xFF, true);
+    /// assert_eq!(mem.dirty_rows(), 0b1000);
+    /// ```
+    /// 
+    pub fn dirty_rows(&self) -> u64 {
+        self.dirty_rows
+    }
+
+    /// Marks all rows of the display buffer as unchanged. Called after the display was updated.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::Memory;
+    /// let mut mem = Memory::new();
+    /// mem.clear_display();
+    /// mem.clear_dirty_rows();
+    /// assert_eq!(mem.dirty_rows(), 0);
+    /// ```
+    /// 
+    pub fn clear_dirty_rows(&mut self) {
+        self.dirty_rows = 0;
+    }
+
+    /// Returns a hex dump view of `length` bytes starting at the given address. The range is truncated at the end of the memory.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mem = Memory::new();
+    /// println!("{}", mem.hexdump(FONT_START, 80));
+    /// ```
+    /// 
+    pub fn hexdump(&self, start: u16, length: usize) -> HexDump<'_> {
+        let start = (start as usize).min(self.size);
+        let end = (start + length).min(self.size);
+        HexDump::new(&self.memory[start..end], start as u16)
+    }
+
+    /// Stores a value like `store(...)`, but marks the affected row of the display buffer as modified if the address lies within it, so the edit is
+    /// shown by the next display update. Used for edits made from outside the program, e.g. by the `HexEditor`.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::{Memory, DISPLAY_START};
+    /// let mut mem = Memory::new();
+    /// mem.edit(DISPLAY_START, 0xFF);
+    /// assert_eq!(mem.dirty_rows(), 0b1);
+    /// ```
+    /// 
+    pub fn edit(&mut self, address: u16, value: u8) {
+        self.store(address, value);
+        let start = self.buffer_index(0, 0);
+        if (start..start + self.display.len() as usize).contains(&(address as usize)) {
+            self.dirty_rows |= 1 << ((address as usize - start) / self.display.bytes_per_row() as usize);
         }
     }
 }
 
+impl Default for Memory {
+
+    fn default() -> Memory {
+        Memory::new()
+    }
+}
+
 impl std::fmt::Display for Memory {
 
-    /// Formats the `Memory` struct as a table of width 32 and height 128, where each cell corresponds to the current value of the byte it represents in storage.  
+    /// Formats the `Memory` struct as a hex dump of the whole address space, with 16 bytes and their ASCII representation per row.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "     ")?;
-        for i in 0..32 {
-            write!(f, "{:0>2X} ", i)?;
-        }
-        writeln!(f)?;
-        write!(f, "     ------------------------------------------------------------------------------------------------")?;
-        writeln!(f)?;
-        for i in 0..128 {
-            write!(f, "{:0>3X}: ", i * 32)?;
-            for j in 0..32 {
-                write!(f, "{:0>2X} ", self.get(i*32+j))?;
-            }
-            writeln!(f)?;
-        }
-        write!(f, "")
+        write!(f, "{}", self.hexdump(0, self.size))
     }
 }
 
@@ -151,6 +635,7 @@ impl std::fmt::Display for Memory {
 /// reset by specific instructions under certain conditions.
 /// The `I` register holds a single 12-bit unsigned integer value that can be used to address the main memory. It is used for certain instructions to load information such
 /// as sprite data from the main memory.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Registers {
     v: [u8; 16],
     i: u16,
@@ -163,7 +648,7 @@ impl Registers {
     /// All 16 `V` registers, as well as the `I` register are initialized as `0`.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let reg = Registers::new();
     /// ```
     /// 
@@ -176,7 +661,7 @@ impl Registers {
     /// The index is represented as a `u8` in Rust, but CHIP-8 only has a total of 16 `V` registers. Accessing a higher index will return 0.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let reg = Registers::new();
     /// let x = reg.get(1);
     /// ```
@@ -193,7 +678,7 @@ impl Registers {
     /// The index is represented as a `u8` in Rust, but CHIP-8 only has a total of 16 `V` registers. Accessing a higher index will result in a panic.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let mut reg = Registers::new();
     /// reg.set(1, 42);
     /// ```
@@ -205,7 +690,7 @@ impl Registers {
     /// Fetches the current value inside the `I` register.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let reg = Registers::new();
     /// let x = reg.i(1);
     /// ```
@@ -219,7 +704,7 @@ impl Registers {
     /// The value is passed as a `u16`. However, the `I` register can only hol up to 12 bits, so values larger than 0xFFF should not be passed.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let mut reg = Registers::new();
     /// reg.set_i(42);
     /// ```
@@ -232,7 +717,7 @@ impl Registers {
     /// Sets the value of the `VF` flag register specifically.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let mut reg = Registers::new();
     /// reg.set_vF(1);
     /// ```
@@ -243,8 +728,16 @@ impl Registers {
 
 }
 
+impl Default for Registers {
+
+    fn default() -> Registers {
+        Registers::new()
+    }
+}
+
 /// Represents the Stack used to store return addresses for `CALL` and `RETURN` instructions in the CHIP-8 instruction set. While the stack was typically located inside
 /// the main memory on real CHIP-8 devices, we store it as a separate data structure with (practically) unlimited storage for our emulation.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Stack {
     stack: Vec<u16>,
 }
@@ -256,7 +749,7 @@ impl Stack {
     /// The stack starts with size `0`.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let stack = Stack::new();
     /// ```
     /// 
@@ -267,7 +760,7 @@ impl Stack {
     /// Pushes a value to the top of the stack.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let mut stack = Stack::new();
     /// stack.push(42);
     /// ```
@@ -281,7 +774,7 @@ impl Stack {
     /// If the stack was empty, returns `None` instead.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let mut stack = Stack::new();
     /// stack.push(42);
     /// let fourty_two = stack.pop();
@@ -291,14 +784,46 @@ impl Stack {
         self.stack.pop()
     }
 
+    /// Gets the return addresses on the stack, from the bottom to the top.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::Stack;
+    /// let mut stack = Stack::new();
+    /// stack.push(42);
+    /// assert_eq!(stack.entries(), &[42]);
+    /// ```
+    /// 
+    pub fn entries(&self) -> &[u16] {
+        &self.stack
+    }
+
+}
+
+impl Default for Stack {
+
+    fn default() -> Stack {
+        Stack::new()
+    }
 }
 
-/// Represents a timer in the CHIP-8 system. Timers can be set to 8-bit values and will then decrement at a rate of 60Hz until they reach `0`.
+/// The number of instructions executed per second by default.
+pub const DEFAULT_LOOP_FREQUENCY: u16 = 700;
+
+/// The rate at which the delay and sound timers are decremented by default, in Hz.
+pub const DEFAULT_TIMER_FREQUENCY: u16 = 60;
+
+/// The `System::keymap` that leaves every key unchanged.
+pub const DEFAULT_KEYMAP: [u8; 16] = [0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA, 0xB, 0xC, 0xD, 0xE, 0xF];
+
+/// Represents a timer in the CHIP-8 system. Timers can be set to 8-bit values and will then decrement at a rate of 60Hz (or the rate set with
+/// `System::set_timer_frequency(...)`) until they reach `0`.
 /// Typically, CHIP-8 has a `Delay Timer` and a `Sound Timer` with similar functionalities. Both of them can be represented
 /// by a Timer struct.
+/// 
+/// The value is stored as an atomic, so it can be decremented by a `TimerTicker` running on a separate thread while the emulation thread reads and sets it.
 pub struct Timer {
-    value: u8,
-    last_update: u128
+    value: Arc<AtomicU8>,
 }
 
 impl Timer {
@@ -306,18 +831,16 @@ impl Timer {
     /// Creates a new instance of the `Timer` struct, starting at value `0`.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let timer = Timer::new();
     /// ```
     pub fn new() -> Timer {
-        Timer { value: 0, last_update: 0 }
+        Timer { value: Arc::new(AtomicU8::new(0)) }
     }
 
     /// Ticks down the timer by `1` if it is larger than `0`.
-    fn tick(&mut self) {
-        if self.value > 0 {
-            self.value -= 1;
-        }
+    fn tick(&self) {
+        let _res = self.value.fetch_update(Ordering::AcqRel, Ordering::Acquire, |v| v.checked_sub(1));
     }
 
     /// Sets the timer to a given 8-bit value.
@@ -325,68 +848,227 @@ impl Timer {
     /// After being set, the timer will decrement at a rate of 60 Hz until it reaches `0`.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let mut timer = Timer::new();
     /// timer.set(42);
     /// ```
     pub fn set(&mut self, value: u8) {
-        self.value = value
+        self.value.store(value, Ordering::Release);
     }
 
     /// Gets the current value of the timer.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let mut timer = Timer::new();
     /// timer.set(42);
     /// let fourty_two = timer.get();
     /// ```
     pub fn get(&self) -> u8 {
-        self.value
+        self.value.load(Ordering::Acquire)
     }
 
-    /// Determines if the last call of the `tick()` method was longer than 16 ms ago. If so, it calls the `tick()` method to decrement the timer value.
-    /// 
-    /// # Example
-    /// ```
-    /// let mut timer = Timer::new();
-    /// timer.set(42);
-    /// loop {
-    ///     if timer.get() == 0 {
-    ///         break;
-    ///     }
-    ///     timer.update();
-    /// }
-    /// ```
-    fn update(&mut self) {
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
-        let dt = current_time - self.last_update;
-        if dt > 16 {
-            self.tick();
-            self.last_update = current_time;
-        }
+    /// Creates a second handle to the same timer value, which can be moved to the `TimerTicker` thread.
+    fn share(&self) -> Timer {
+        Timer { value: Arc::clone(&self.value) }
+    }
+
+    /// Creates an independent timer starting at the current value, e.g. for a `SaveState`.
+    fn detach(&self) -> Timer {
+        Timer { value: Arc::new(AtomicU8::new(self.get())) }
     }
 }
 
-/// Represents the state of the 16-key CHIP-8 keyboard.
-pub struct Keyboad {
-    keys: [bool; 16],
-    latest: u8,
+impl Default for Timer {
+
+    fn default() -> Timer {
+        Timer::new()
+    }
 }
 
+impl Serialize for Timer {
 
-impl Keyboad {
+    /// Serializes the current value of the timer.
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer, {
+            serializer.serialize_u8(self.get())
+    }
+}
 
-    /// Creates a new instance of the `Keyboard` struct.
-    /// 
-    /// All keys are initialized as "not pressed".
+impl<'de> Deserialize<'de> for Timer {
+
+    /// Deserializes a new, unshared timer starting at the serialized value.
+    fn deserialize<D>(deserializer: D) -> Result<Timer, D::Error>
+        where D: Deserializer<'de>, {
+            let mut timer = Timer::new();
+            timer.set(u8::deserialize(deserializer)?);
+            Ok(timer)
+    }
+}
+
+/// Selects the regions of memory that programs may not write to. A write into a protected region is reported as an error, which catches programs
+/// that corrupt themselves or the font by accident (e.g. with a wrong `I` before `FX55`).
+/// 
+/// Both regions are writable by default, since some programs legitimately modify their own code.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WriteProtection {
+    /// Protects the built-in font sprites (`FONT_START` to `FONT_END`).
+    pub font: bool,
+    /// Protects the bytes of the loaded program.
+    pub program: bool,
+}
+
+/// Selects how the delay and sound timers are decremented while `System::run_with(...)` is executing.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TimerMode {
+    /// The timers are ticked once per emulated timer period (1/60 s by default), derived from the number of executed instructions and the `loop_frequency` (see
+    /// `System::emulated_time()`). Timer behavior stays consistent with the emulated timeline, even if the host cannot keep up or the emulation is
+    /// sped up or slowed down.
+    Frames,
+    /// The timers are ticked at the timer frequency (60 Hz by default) of wall-clock time by a `TimerTicker` thread, independently of the emulation
+    /// thread.
+    WallClock,
+}
+
+/// Decrements a set of timers at a precise rate (usually 60 Hz) on a dedicated thread.
+/// 
+/// Running the timers on their own thread makes their accuracy independent of the `loop_frequency` and of slow display updates in the emulation thread.
+/// Ticks are scheduled on a fixed timeline using the hybrid `sleep_until(...)` function, so they keep an exact average rate.
+/// The thread is stopped when the `TimerTicker` is dropped.
+pub struct TimerTicker {
+    running: Arc<AtomicBool>,
+    ticks: Arc<AtomicU64>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl TimerTicker {
+
+    /// Starts a new ticker thread decrementing the given timers at the given frequency in Hz (at least 1).
     /// 
     /// # Example
+    /// ```ignore
+    /// let sys = System::new();
+    /// let ticker = TimerTicker::start(&[&sys.delay_timer, &sys.sound_timer], DEFAULT_TIMER_FREQUENCY);
     /// ```
-    /// let kb = Keyboard::new();
-    /// ```
-    pub fn new() -> Keyboad {
-        Keyboad { keys: [false; 16], latest: 16 }
+    pub fn start(timers: &[&Timer], frequency: u16) -> TimerTicker {
+        let period = Duration::from_secs(1) / frequency.max(1) as u32;
+        let timers: Vec<Timer> = timers.iter().map(|t| t.share()).collect();
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = Arc::clone(&running);
+        let ticks = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&ticks);
+        let handle = thread::spawn(move || {
+            let mut next_tick = Instant::now() + period;
+            while flag.load(Ordering::Acquire) {
+                sleep_until(next_tick);
+                for timer in &timers {
+                    timer.tick();
+                }
+                counter.fetch_add(1, Ordering::Relaxed);
+                next_tick += period;
+                let now = Instant::now();
+                if now > next_tick {
+                    log::warn!("Timer thread stalled for {:.2?}, skipping the missed ticks", now - next_tick);
+                    next_tick = now + period;
+                }
+            }
+        });
+        TimerTicker { running, ticks, handle: Some(handle) }
+    }
+
+    /// Gets the number of ticks performed since the ticker was started.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let ticker = TimerTicker::start(&[], DEFAULT_TIMER_FREQUENCY);
+    /// let ticks = ticker.ticks();
+    /// ```
+    pub fn ticks(&self) -> u64 {
+        self.ticks.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for TimerTicker {
+
+    /// Stops the ticker thread and waits for it to finish.
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            let _res = handle.join();
+        }
+    }
+}
+
+/// The number of recently executed PCs kept by a `System`, e.g. for crash reports.
+const PC_HISTORY_LENGTH: usize = 256;
+
+/// The number of frames between two updates of the debugger's live monitor while it is shown, i.e. 10 updates per second.
+const MONITOR_INTERVAL: u64 = 6;
+
+/// The maximum number of events buffered by a `Keyboard`. When the queue is full, the oldest event is discarded.
+const KEY_QUEUE_CAPACITY: usize = 64;
+
+/// A key being pressed or released, as recorded by a `Keyboard`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KeyboardEvent {
+    /// The index of the key (`0x0`-`0xF`).
+    pub key: u8,
+    /// `true` if the key was pressed, `false` if it was released.
+    pub pressed: bool,
+    /// The value of `System::cycles()` at the time of the event.
+    pub cycle: u64,
+}
+
+/// An event at which `System::run_until(...)` stops.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// An instruction changed the display buffer (`00E0` or `DXYN`).
+    NextDraw,
+    /// `FX18` started the sound timer.
+    SoundStart,
+    /// `FX0A` found no key and waits for one.
+    KeyWait,
+    /// A subroutine returned with `00EE`.
+    Return,
+    /// The program halted at the opcode `0000`.
+    Halt,
+}
+
+/// Selects how `FX0A` (wait for a key) observes the keyboard.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyMode {
+    /// Key presses and releases are latched into the event queue of the `Keyboard` while `FX0A` waits, so even a tap that is shorter than the
+    /// interval between two executions of `FX0A` (e.g. at a low `loop_frequency`) is recognized. Releases from before the wait are ignored. See
+    /// `Keyboard::next_release(...)`.
+    Queue,
+    /// Only the current state of the keys is sampled whenever `FX0A` is executed, like on the original hardware: a key has to be seen pressed and
+    /// then released by two executions of `FX0A`. See `Keyboard::sample_release()`.
+    Sample,
+}
+
+/// Represents the state of the 16-key CHIP-8 keyboard.
+/// 
+/// Besides the current state of each key, the keyboard records every change in a FIFO queue of `KeyboardEvent`s. Since the queue is only drained by
+/// `FX0A` (see `next_release(...)`) or by a frontend (see `pop_event()`), key taps that are shorter than the interval between two polls are not lost.
+pub struct Keyboard {
+    keys: [bool; 16],
+    events: VecDeque<KeyboardEvent>,
+    sampled: [bool; 16],
+    wait_start: Option<u64>,
+}
+
+impl Keyboard {
+
+    /// Creates a new instance of the `Keyboard` struct.
+    /// 
+    /// All keys are initialized as "not pressed" and the event queue is empty.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let kb = Keyboard::new();
+    /// ```
+    pub fn new() -> Keyboard {
+        Keyboard { keys: [false; 16], events: VecDeque::with_capacity(KEY_QUEUE_CAPACITY), sampled: [false; 16], wait_start: None }
     }
 
     /// Gets the current state of the key with the given index.
@@ -394,7 +1076,7 @@ impl Keyboad {
     /// `true` indicates that the key is currently pressed, `false` indicates it is not pressed.
     /// 
     /// # Example
-    /// ```
+    /// ```ignore
     /// let kb = Keyboard::new();
     /// let a_pressed = kb.get(0xA);
     /// ```
@@ -405,337 +1087,2358 @@ impl Keyboad {
         self.keys[key as usize]
     }
 
-    /// Updates the state of the keyboard.
+    /// Gets the state of all keys as a bit mask, with bit N set while the key with index N is pressed.
     /// 
-    /// Invokes the `user32::GetAsyncKeyState(...)` function for each possible key to get its current state and stores it. It also updates the value of the
-    /// `latest` field, indicating the latest key that was pressed.
-    pub fn update(&mut self, idx: u8) {
-        if idx < 16 {
-            if self.keys[idx as usize] {
-                self.latest = idx;
-            }
-            self.keys[idx as usize] = !self.keys[idx as usize];
-        }
+    /// # Example
+    /// ```
+    /// # use chip8::system::Keyboard;
+    /// let mut kb = Keyboard::new();
+    /// kb.set(0x5, true, 0);
+    /// assert_eq!(kb.mask(), 0x0020);
+    /// ```
+    pub fn mask(&self) -> u16 {
+        self.keys.iter().enumerate().filter(|(_, pressed)| **pressed).fold(0, |mask, (key, _)| mask | 1 << key)
     }
 
-    /// Gets the index of the latest key that was pressed (or 0x10 if no key was pressed) and then resets the value.
+    /// Sets the state of the key with the given index and records the change in the event queue. Setting a key to the state it already has, or
+    /// passing an index larger than `0xF`, has no effect.
     /// 
     /// # Example
     /// ```
-    /// let kb = Keyboard::new();
-    /// let latest = kb.latest();
+    /// # use chip8::system::Keyboard;
+    /// let mut kb = Keyboard::new();
+    /// kb.set(0xA, true, 0);
+    /// assert!(kb.get(0xA));
     /// ```
-    pub fn latest(&mut self) -> u8 {
-        let x = self.latest;
-        self.latest = 0x10;
-        return x;
+    pub fn set(&mut self, key: u8, pressed: bool, cycle: u64) {
+        if key >= 16 || self.keys[key as usize] == pressed {
+            return;
+        }
+        self.keys[key as usize] = pressed;
+        if self.events.len() == KEY_QUEUE_CAPACITY {
+            self.events.pop_front();
+        }
+        self.events.push_back(KeyboardEvent { key, pressed, cycle });
     }
-}
 
-/// A simulated `Display` for the CHIP-8, using stdout to draw the pixels. 
-pub struct Display {
-    pixels: [[u8;64]; 32],
-}
+    /// Toggles the state of the key with the given index, as reported by the keyboard thread of `System::run(...)`.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut kb = Keyboard::new();
+    /// kb.update(0xA, 0);
+    /// ```
+    pub fn update(&mut self, idx: u8, cycle: u64) {
+        self.set(idx, !self.get(idx), cycle);
+    }
 
-impl Display {
+    /// Removes the oldest event from the queue and returns it, or returns `None` if the queue is empty.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut kb = Keyboard::new();
+    /// while let Some(event) = kb.pop_event() {
+    ///     println!("{:?}", event);
+    /// }
+    /// ```
+    pub fn pop_event(&mut self) -> Option<KeyboardEvent> {
+        self.events.pop_front()
+    }
 
-    /// Creates a new instance of the `Display` struct, initializing its 32x64 pixel matrix as `OFF`.
+    /// Removes events from the queue until a key release is found, and returns the released key. Returns `None` (leaving the queue empty) if the
+    /// queue does not contain a release.
+    /// 
+    /// This implements the behavior of `FX0A`, which waits for a key to be pressed and released again, executed at the given value of
+    /// `System::cycles()`. The wait starts with the first call after the previous wait ended, and releases recorded before that are discarded, so a
+    /// key released long before `FX0A` is reached (e.g. during gameplay with `EX9E`/`EXA1`) does not end the wait immediately.
     /// 
     /// # Example
     /// ```
-    /// let display = Display::new();
+    /// # use chip8::system::Keyboard;
+    /// let mut kb = Keyboard::new();
+    /// kb.set(0x5, true, 0);
+    /// kb.set(0x5, false, 1);
+    /// assert_eq!(kb.next_release(10), None);
+    /// kb.set(0x7, true, 12);
+    /// kb.set(0x7, false, 14);
+    /// assert_eq!(kb.next_release(15), Some(0x7));
     /// ```
-    pub fn new() -> Display {
-        Display { pixels: [[0u8;64]; 32] }
+    pub fn next_release(&mut self, cycle: u64) -> Option<u8> {
+        let since = *self.wait_start.get_or_insert(cycle);
+        while let Some(event) = self.events.pop_front() {
+            if !event.pressed && event.cycle >= since {
+                self.wait_start = None;
+                return Some(event.key);
+            }
+        }
+        None
     }
 
-    /// Updates the current state of the display by using the `Memory` component of the current `System` state.
+    /// Samples the current state of the keys and returns a key that was pressed at an earlier call and is no longer pressed, or `None` if there is
+    /// none. The event queue is discarded, so taps between two calls are missed.
     /// 
-    /// If a pixel is set in the `memory`, its value will be set to `4` in the `pixels` matrix. If it was not set, its value will be decremented by `1` instead.
-    /// Pixels are rendered in the console, as long as their value is larger than `0`.
+    /// This implements the behavior of `FX0A` in `KeyMode::Sample`.
     /// 
     /// # Example
     /// ```
-    /// let mut system = System::new();
-    /// let mut display = Display::new();
-    /// 
-    /// display.update(system);
+    /// # use chip8::system::Keyboard;
+    /// let mut kb = Keyboard::new();
+    /// kb.set(0x5, true, 0);
+    /// assert_eq!(kb.sample_release(), None);
+    /// kb.set(0x5, false, 1);
+    /// assert_eq!(kb.sample_release(), Some(0x5));
     /// ```
-    pub fn update(&mut self, sys: &System) {
-        let mut change_positions = Vec::new();
-        for i in 0xF00..=0xFFF {
-            let byte = sys.memory.get(i);
-            let pos = i - 0xF00;
-            let y = pos / (sys.screen_width as u16 / 8);
-            let x = (pos % (sys.screen_width as u16 / 8))*8;
-            for j in 0..8 {
-                if (byte & (1 << (7-j))) > 0 {
-                    if self.pixels[y as usize][(x + j) as usize] == 0 {
-                        change_positions.push((y, x + j));
-                    }
-                    self.pixels[y as usize][(x + j) as usize] = 4;
-                }
-                else if self.pixels[y as usize][(x + j) as usize] > 0 {
-                    self.pixels[y as usize][(x + j) as usize] -= 1;
-                    if self.pixels[y as usize][(x + j) as usize] == 0 {
-                        change_positions.push((y, x + j));
-                    }
-                }
-            }
+    pub fn sample_release(&mut self) -> Option<u8> {
+        self.events.clear();
+        self.wait_start = None;
+        if let Some(key) = (0..16).find(|key| self.sampled[*key] && !self.keys[*key]) {
+            self.sampled = [false; 16];
+            return Some(key as u8);
         }
+        for (sampled, pressed) in self.sampled.iter_mut().zip(self.keys) {
+            *sampled |= pressed;
+        }
+        None
+    }
+}
 
-        self.render(&change_positions);
-    }
-
-    fn clear_screen(&self) {
-        for y in 0..34 {
-            if y == 0 || y == 33 {
-                print!("{}[{};{}H", 27 as char, y + 1, 1);
-                for x in 0..130 {
-                    let c = match x {
-                        0 => match y {
-                            0 => '╔',
-                            33 => '╚',
-                            _ => 'Y',
-                        },
-                        129 => match y {
-                            0 => '╗',
-                            33 => '╝',
-                            _ => 'X',
-                        },
-                        _ => '═',
-                    };
-                    print!("{}", c);
-                }
-                continue;
-            }
+impl Default for Keyboard {
 
-            print!("{}[{};{}H", 27 as char, y + 1, 1);
+    fn default() -> Keyboard {
+        Keyboard::new()
+    }
+}
 
-            for x in 0..66 {
-                
-                
-                let c = match x {
-                    0 => '║',
-                    65 => '║',
-                    _ => {
-                        ' '
-                    },
-                };
+/// The largest display a `Display` can show, in pixels: the high-resolution mode of the SUPER-CHIP.
+const MAX_DISPLAY_SIZE: (usize, usize) = (128, 64);
+
+/// The part of the pixels (`screen_width` x `screen_height`, usually 64x32) that a `Display` shows, and the number of terminal columns used per
+/// pixel.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Viewport {
+    x: u16,
+    y: u16,
+    width: u16,
+    height: u16,
+    cell_width: u16,
+    screen_width: u16,
+    screen_height: u16,
+}
 
-                if x == 0 || x == 65 {
-                    print!("{}", c);
-                }
-                else {
-                    print!("{}", c);
-                    print!("{}", c);
-                }
-                
-                
-            }
-        }
-        println!("{}[{};{}H", 27 as char, 36, 0);
+impl Viewport {
+
+    /// Creates the viewport showing all pixels of a screen of the given size, with the given number of columns per pixel.
+    fn full(screen_width: u16, screen_height: u16, cell_width: u16) -> Viewport {
+        Viewport { x: 0, y: 0, width: screen_width, height: screen_height, cell_width, screen_width, screen_height }
     }
 
-    /// Renders the current state of the `pixels` matrix to the console. Called by the `update(...)` method.
-    fn render(&self, change_positions: &Vec<(u16, u16)>) {
-        for (y, x) in change_positions {
-            let c = match x {
-                0 => '║',
-                65 => '║',
-                _ => {
-                    let pixel = self.pixels[*y as usize][*x as usize];
-                    match pixel {
-                        0 => ' ',
-                        _ => '█',
-                    }
-                },
-            };
-            print!("{}[{};{}H{}{}", 27 as char, *y + 2, *x * 2, c, c);
-        }
-        println!("{}[{};{}H", 27 as char, 36, 0);
+    /// Creates the largest viewport onto a screen of the given size that fits into a terminal of the given size, including the border and the status
+    /// line. If the pixels do not fit with two columns each, one column per pixel is used instead (unless a fixed `cell_width` is given), and if they
+    /// still do not fit, only a part of them is shown.
+    fn fit(columns: u16, rows: u16, cell_width: Option<u16>, screen_width: u16, screen_height: u16) -> Viewport {
+        let cell_width = cell_width.unwrap_or(if columns >= screen_width * 2 + 2 { 2 } else { 1 });
+        let width = (columns.saturating_sub(2) / cell_width).clamp(1, screen_width);
+        let height = rows.saturating_sub(4).clamp(1, screen_height);
+        Viewport { width, height, ..Viewport::full(screen_width, screen_height, cell_width) }
+    }
+
+    /// Whether only a part of the pixels is shown.
+    fn is_partial(&self) -> bool {
+        self.width < self.screen_width || self.height < self.screen_height
     }
 
+    /// Whether the pixel at the given position is shown.
+    fn contains(&self, y: u16, x: u16) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+
+    /// Moves the viewport to the given position, clamped so it stays within the pixels. Returns `true` if the viewport moved.
+    fn move_to(&mut self, x: i32, y: i32) -> bool {
+        let x = x.clamp(0, (self.screen_width - self.width) as i32) as u16;
+        let y = y.clamp(0, (self.screen_height - self.height) as i32) as u16;
+        let moved = (x, y) != (self.x, self.y);
+        self.x = x;
+        self.y = y;
+        moved
+    }
 }
 
-/// A struct representing the state of a CHIP-8 processor and its peripherals.
-pub struct System {
-    pub memory: Memory,
-    pub registers: Registers,
-    pub stack: Stack,
-    pub delay_timer: Timer,
-    pub sound_timer: Timer,
-    pub keyboard: Keyboad,
+/// The format of a frame returned by `Display::render_to_string(...)`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameStyle {
+    /// Draws a border around the pixels.
+    pub border: bool,
+    /// Includes the color escape sequences used in high-contrast mode (see `DisplayOptions`).
+    pub ansi: bool,
+}
+
+/// Rendering options of a `Display`, e.g. for users with low vision or for terminals with low contrast.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DisplayOptions {
+    /// Pixels that are on are drawn in the background color, and pixels that are off in the foreground color.
+    pub invert: bool,
+    /// Pixels are drawn as solid blocks two columns wide in bright white on black, regardless of the colors of the terminal.
+    pub high_contrast: bool,
+    /// Pixels that are turned off fade out over a few updates, which reduces the flicker of many programs. If disabled, pixels turn off immediately.
+    pub decay: bool,
+}
 
-    pub rng: ThreadRng,
+impl DisplayOptions {
 
-    pub pc: u16,
-    pub screen_width: u8,
-    pub screen_height: u8,
+    /// Creates the default options: no inversion, the colors of the terminal and decaying pixels.
+    pub fn new() -> DisplayOptions {
+        DisplayOptions { invert: false, high_contrast: false, decay: true }
+    }
+}
+
+impl Default for DisplayOptions {
+
+    fn default() -> DisplayOptions {
+        DisplayOptions::new()
+    }
+}
+
+/// A single option of `DisplayOptions`, which can be toggled while the display is running (see `InputEvent::ToggleDisplayOption`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DisplayOption {
+    Invert,
+    HighContrast,
+    Decay,
+}
+
+/// The number of terminal columns reserved for the side panel (see `Display::show_side_panel(...)`), including the gap to the display.
+const SIDE_PANEL_COLUMNS: u16 = 68;
+
+/// The time for which the viewport stops following the draw activity after it was panned manually.
+const FOLLOW_PAUSE: Duration = Duration::from_secs(3);
+
+/// A simulated `Display` for the CHIP-8, using terminal escape sequences to draw the pixels to stdout (or to any other output, e.g. a network
+/// connection, see `with_output(...)`).
+/// 
+/// The display shows as many pixels as the current `DisplayLayout` of the system has, up to 128x64 for the high-resolution mode of the SUPER-CHIP,
+/// and adapts whenever the layout changes.
+/// 
+/// If the terminal is too small for the pixels with their border, a display rendering to stdout shows a zoomed viewport instead (see
+/// `Renderer::begin()`): one column per pixel, and if that is still too large, only the part of the pixels around the latest changes. The viewport can
+/// be panned with the arrow keys, which pauses following the changes for a few seconds.
+/// 
+/// The way pixels are drawn can be changed with `DisplayOptions`, also while the display is running.
+pub struct Display {
+    pixels: [[u8; MAX_DISPLAY_SIZE.0]; MAX_DISPLAY_SIZE.1],
+    fading_rows: u64,
+    sound_active: bool,
     loop_frequency: u16,
+    left: u16,
+    viewport: Viewport,
+    options: DisplayOptions,
+    scale: Option<u16>,
+    fit_terminal: bool,
+    follow_paused_until: Option<Instant>,
+    panel_lines: u16,
+    side_panel: Vec<String>,
+    out: BufWriter<Box<dyn Write + Send>>,
 }
 
-impl System {
+impl Display {
 
-    /// Creates a new instance of the `System` struct.
+    /// Creates a new instance of the `Display` struct, initializing its pixel matrix (64x32, until a larger `DisplayLayout` is shown) as `OFF`.
     /// 
-    /// Sub-structs are initialized as empty, using their individual `new()` methods.  
+    /// # Example
+    /// ```ignore
+    /// let display = Display::new();
+    /// ```
+    pub fn new() -> Display {
+        Display { fit_terminal: true, ..Display::with_output(Box::new(io::stdout())) }
+    }
+
+    /// Creates a new instance of the `Display` struct that renders to the given output instead of stdout. Since the size of the output is unknown,
+    /// all pixels are shown.
     /// 
     /// # Example
+    /// ```ignore
+    /// let stream = TcpStream::connect("localhost:2323")?;
+    /// let display = Display::with_output(Box::new(stream));
     /// ```
-    /// let sys = System::new();
+    pub fn with_output(out: Box<dyn Write + Send>) -> Display {
+        Display {
+            pixels: [[0u8; MAX_DISPLAY_SIZE.0]; MAX_DISPLAY_SIZE.1],
+            fading_rows: 0,
+            sound_active: false,
+            loop_frequency: 0,
+            left: 0,
+            viewport: Viewport::full(64, 32, 2),
+            options: DisplayOptions::new(),
+            scale: None,
+            fit_terminal: false,
+            follow_paused_until: None,
+            panel_lines: 0,
+            side_panel: Vec::new(),
+            out: BufWriter::new(out),
+        }
+    }
+
+    /// Moves the display (including its border and status line) `left` columns to the right, e.g. to render two displays side by side.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let right = Display::new().at_column(DISPLAY_COLUMNS);
     /// ```
+    pub fn at_column(mut self, left: u16) -> Display {
+        self.left = left;
+        self
+    }
+
+    /// Sets the rendering options of the display.
     /// 
-    pub fn new() -> System {
-        System { 
-            memory: Memory::new(),
-            registers: Registers::new(),
-            stack: Stack::new(),
-            delay_timer: Timer::new(),
-            sound_timer: Timer::new(),
-            keyboard: Keyboad::new(),
-            rng: rand::thread_rng(),            
-            pc: 0,
-            screen_width: 64,
-            screen_height: 32,
-            loop_frequency: 700
-        }
+    /// # Example
+    /// ```ignore
+    /// let display = Display::new().with_options(DisplayOptions { invert: true, ..DisplayOptions::new() });
+    /// ```
+    pub fn with_options(mut self, options: DisplayOptions) -> Display {
+        self.options = options;
+        self
     }
 
-    /// Loads a program into the system's main memory.
+    /// Fixes the number of terminal columns used per pixel (`1` or `2`), instead of choosing it based on the size of the terminal.
     /// 
-    /// The loaded program's address space starts at 0x200, and its PC is initialized to 0x200.
+    /// # Panics
+    /// If `scale` is not `1` or `2`.
     /// 
     /// # Example
+    /// ```ignore
+    /// let display = Display::new().with_scale(1);
     /// ```
-    /// let mut sys = System::new();
-    /// let program = Program::load("path");
-    /// sys.load(program);
+    pub fn with_scale(mut self, scale: u16) -> Display {
+        assert!((1..=2).contains(&scale), "the scale must be 1 or 2");
+        self.scale = Some(scale);
+        self.viewport.cell_width = scale;
+        self
+    }
+
+    /// Toggles one of the rendering options and renders the display again with the new setting.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// display.toggle(DisplayOption::Invert);
     /// ```
-    pub fn load(&mut self, program: program::Program) {
-        for (idx, instr) in program.instructions.iter().enumerate() {
-            self.memory.store(0x200 + idx as u16, *instr as u8);
+    pub fn toggle(&mut self, option: DisplayOption) {
+        let flag = match option {
+            DisplayOption::Invert => &mut self.options.invert,
+            DisplayOption::HighContrast => &mut self.options.high_contrast,
+            DisplayOption::Decay => &mut self.options.decay,
+        };
+        *flag = !*flag;
+        log::info!("Display options: {:?}", self.options);
+        if option == DisplayOption::HighContrast {
+            self.fit_to_terminal();
         }
-        self.pc = 0x200;
+        let _res = self.redraw();
     }
 
-    /// Increments the CHIP-8's PC by two.
+    /// Moves a partial viewport by a quarter of its size in the given directions, and stops following the changes for `FOLLOW_PAUSE`. Has no
+    /// effect if all pixels are shown.
     /// 
-    /// ' Example
-    /// ```
-    /// let mut sys = System::new();
-    /// sys.increment_pc();
+    /// # Example
+    /// ```ignore
+    /// display.pan(1, 0);
     /// ```
-    pub fn increment_pc(&mut self) {
-        self.pc += 2;
+    pub fn pan(&mut self, dx: i8, dy: i8) {
+        if !self.viewport.is_partial() {
+            return;
+        }
+        self.follow_paused_until = Some(Instant::now() + FOLLOW_PAUSE);
+        let x = self.viewport.x as i32 + dx as i32 * (self.viewport.width as i32 / 4).max(1);
+        let y = self.viewport.y as i32 + dy as i32 * (self.viewport.height as i32 / 4).max(1);
+        if self.viewport.move_to(x, y) {
+            let _res = self.redraw();
+        }
     }
 
-    /// Starts running the CHIP-8's fetch/decode/execute loop.
+    /// Updates the current state of the display by using the `Memory` component of the current `System` state.
+    /// 
+    /// If a pixel is set in the `memory`, its value will be set to `4` in the `pixels` matrix. If it was not set, its value will be decremented by `1` instead.
+    /// Pixels are rendered in the console, as long as their value is larger than `0`.
+    /// 
+    /// Only rows that were touched by a drawing instruction since the last update (see `Memory::dirty_rows()`), or that still contain fading pixels, are
+    /// processed. If no such rows exist, the update is skipped entirely. Only the pixels that changed are written to the output (see `write_pixels(...)`),
+    /// and the output is flushed once per update. Returns `true` if any pixel changed and the display was rendered.
+    /// 
+    /// The terminal display shows at most 128x64 pixels; the rest of a larger `DisplayLayout` is not rendered. If the size of the layout changed since
+    /// the last update, the display is resized and drawn again.
     /// 
-    /// A mutable reference to a `Display` instance needs to be passed to update the display rendering with each step.
-    /// The loop's refresh rate is defined by the `loop_frequency` field. Each step in the loop consists of the following steps, in order:
-    /// - Update timers
-    /// - Check keyboardinput
-    /// - Fetch next instruction
-    /// - Increment PC
-    /// - Decode & execute instruction
-    /// - Update 
+    /// The status line below the display shows the current `loop_frequency`. Since sound output may be muted or unavailable, it also shows a sound
+    /// indicator while the sound timer is non-zero.
     /// 
     /// # Example
-    /// ```
-    /// let mut sys = System::new();
+    /// ```ignore
+    /// let mut system = System::new();
     /// let mut display = Display::new();
-    /// let program = Program::load("path");
     /// 
-    /// sys.load(program);
-    /// sys.run(&mut display);
+    /// display.update(system);
     /// ```
-    pub fn run(&mut self, display: &mut Display) {
-
-        display.clear_screen();
-
-        let (tx, rx) = mpsc::channel::<u8>();
-        thread::spawn(move || {
-            do_keyboard_check(tx);
-        });
-
-        let delay = 1000000u64/self.loop_frequency as u64;
-        loop {
-            self.delay_timer.update();
-            self.sound_timer.update();
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn update(&mut self, sys: &System) -> bool {
+        let sound_active = sys.sound_timer.get() > 0;
+        let status_changed = sound_active != self.sound_active || sys.loop_frequency != self.loop_frequency;
+        if status_changed {
+            self.sound_active = sound_active;
+            self.loop_frequency = sys.loop_frequency;
+            let _res = self.write_status_line();
+        }
 
-            if let Ok(idx) = rx.try_recv() {
-                self.keyboard.update(idx);
+        let rows = sys.memory.dirty_rows() | self.fading_rows;
+        if rows == 0 {
+            if status_changed {
+                let _res = self.out.flush();
             }
+            return false;
+        }
 
-            //Fetch
-            let op1 = self.memory.get(self.pc);
-            let op2 = self.memory.get(self.pc + 1);
-            self.increment_pc();
-
-            //Decode & Execute
-            if op1 == 0 && op2 == 0 {
-                break;
+        let layout = sys.memory.display_layout();
+        let size = ((layout.width as u16).min(MAX_DISPLAY_SIZE.0 as u16), (layout.height as u16).min(MAX_DISPLAY_SIZE.1 as u16));
+        if size != (self.viewport.screen_width, self.viewport.screen_height) {
+            let _res = self.resize(size.0, size.1);
+        }
+        let bytes_per_row = layout.bytes_per_row().min(self.pixels[0].len() as u16 / 8);
+        let mut change_positions = Vec::new();
+        self.fading_rows = 0;
+        for y in 0..(layout.height as u16).min(self.pixels.len() as u16) {
+            if rows & (1 << y) == 0 {
+                continue;
             }
-            let op: Instruction = big_endian_8_2(op1, op2).into();
-            op.execute(self);
-
-            //Display updates
-            display.update(self);            
-
-            //frequency
-            thread::sleep(Duration::from_micros(delay));
-        }
-
-        println!("CHIP-8 Finished!");
-    }
-}
-
-
-/// Maps 16 QWERTY keyboard keys to the corresponding CHIP-8 key index they should represent.
-const KEYBOARD_KEYS: [u8; 16] = [
-    b'X',
-    b'1',
-    b'2',
-    b'3',
-    b'Q',
-    b'W',
-    b'E',
-    b'A',
-    b'S',
-    b'D',
-    b'Z',
-    b'C',
-    b'4',
-    b'R',
-    b'F',
-    b'V',
-];
-
-/// A worker thread responsible for polling the keyboard state repeatedly, sending updates to the main thread.
-fn do_keyboard_check(tx: Sender<u8>) {
-    let mut states = [0;16];
-
-    'outer: loop {
-        thread::sleep(Duration::from_millis(10));
-        for (idx, key) in KEYBOARD_KEYS.iter().enumerate() {
-            let state = unsafe { user32::GetAsyncKeyState(*key as u8 as i32) } == -32767;
-
-            if state {
-                if states[idx] == 0 {
-                    if tx.send(idx as u8).is_err() {
-                        break 'outer;
+            for b in 0..bytes_per_row {
+                let byte = sys.memory.composite_byte(y * layout.bytes_per_row() + b);
+                let x = b * 8;
+                for j in 0..8 {
+                    let pixel = &mut self.pixels[y as usize][(x + j) as usize];
+                    if (byte & (1 << (7-j))) > 0 {
+                        if *pixel == 0 {
+                            change_positions.push((y, x + j));
+                        }
+                        *pixel = 4;
                     }
-                }
-                states[idx] = 50;
-            } else if states[idx] > 0 {
-                states[idx] -= 1;
-                if states[idx] == 0 {
-                    if tx.send(idx as u8).is_err() {
-                        break 'outer;
+                    else if *pixel > 0 {
+                        *pixel = if self.options.decay { *pixel - 1 } else { 0 };
+                        if *pixel == 0 {
+                            change_positions.push((y, x + j));
+                        }
+                        else {
+                            self.fading_rows |= 1 << y;
+                        }
                     }
                 }
             }
         }
+
+        let rendered = !change_positions.is_empty();
+        if rendered && self.follow(&change_positions) {
+            let _res = self.redraw();
+        }
+        else if rendered || status_changed {
+            let _res = self.write_pixels(&change_positions).and_then(|_| self.out.flush());
+        }
+        rendered
+    }
+
+    /// Centers a partial viewport on the given changes if any of them are outside of it, unless following is paused. Returns `true` if the viewport
+    /// moved.
+    fn follow(&mut self, change_positions: &[(u16, u16)]) -> bool {
+        if !self.viewport.is_partial() || self.follow_paused_until.is_some_and(|until| Instant::now() < until) {
+            return false;
+        }
+        if change_positions.iter().all(|(y, x)| self.viewport.contains(*y, *x)) {
+            return false;
+        }
+        let (min_y, max_y) = change_positions.iter().fold((u16::MAX, 0), |(min, max), (y, _)| (min.min(*y), max.max(*y)));
+        let (min_x, max_x) = change_positions.iter().fold((u16::MAX, 0), |(min, max), (_, x)| (min.min(*x), max.max(*x)));
+        let x = (min_x + max_x) as i32 / 2 - self.viewport.width as i32 / 2;
+        let y = (min_y + max_y) as i32 / 2 - self.viewport.height as i32 / 2;
+        self.viewport.move_to(x, y)
+    }
+
+    /// Sets the title of the terminal window the display is rendered in.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut display = Display::new();
+    /// display.set_title("CHIP-8 - pong");
+    /// ```
+    pub fn set_title(&mut self, title: &str) {
+        let _res = write!(self.out, "{}]0;{}{}", 27 as char, title, 7 as char).and_then(|_| self.out.flush());
+    }
+
+    /// Shows a panel of text below the status line, replacing the previous one. An empty text hides the panel.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// display.show_panel(&sys.debugger.panel(&sys));
+    /// ```
+    pub fn show_panel(&mut self, text: &str) {
+        let _res = self.render_panel(text);
+    }
+
+    /// Shows a panel of text to the right of the display, next to its border, replacing the previous one. An empty text hides the panel.
+    /// 
+    /// Only the lines that differ from the previous panel are rendered, so the panel can be updated frequently. While the panel is shown, a display
+    /// rendering to stdout reserves `SIDE_PANEL_COLUMNS` columns of the terminal for it, and shows a zoomed viewport if the pixels no longer fit.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// display.show_side_panel(&sys.debugger.monitor(&sys));
+    /// ```
+    pub fn show_side_panel(&mut self, text: &str) {
+        let lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+        if lines.is_empty() != self.side_panel.is_empty() {
+            let _res = self.erase_side_panel().and_then(|_| self.relayout(!lines.is_empty()));
+        }
+        let _res = self.render_side_panel(lines);
+    }
+
+    /// Gets the terminal column (starting at 1) of the side panel, to the right of the border of the display.
+    fn side_panel_column(&self) -> u16 {
+        self.left + self.viewport.width * self.viewport.cell_width + 5
+    }
+
+    /// Renders the lines of the side panel that differ from the previous ones, padding them to erase longer previous lines. Called by the
+    /// `show_side_panel(...)` method.
+    fn render_side_panel(&mut self, lines: Vec<String>) -> io::Result<()> {
+        let column = self.side_panel_column();
+        let width = SIDE_PANEL_COLUMNS as usize - 4;
+        for y in 0..lines.len().max(self.side_panel.len()) {
+            let line = lines.get(y).map(|line| line.as_str()).unwrap_or("");
+            if self.side_panel.get(y).map(|previous| previous.as_str()) != Some(line) {
+                let padding = width.saturating_sub(visible_width(line));
+                write!(self.out, "{}[{};{}H{}{}", 27 as char, y + 1, column, line, " ".repeat(padding))?;
+            }
+        }
+        self.side_panel = lines;
+        writeln!(self.out, "{}[{};{}H", 27 as char, self.viewport.height + 4, 0)?;
+        self.out.flush()
     }
-}
\ No newline at end of file
+
+    /// Erases all lines of the side panel from the terminal, so they are rendered again by the next `render_side_panel(...)`. The panel is still
+    /// considered to be shown.
+    fn erase_side_panel(&mut self) -> io::Result<()> {
+        let column = self.side_panel_column();
+        for (y, line) in self.side_panel.iter_mut().enumerate() {
+            write!(self.out, "{}[{};{}H{}", 27 as char, y + 1, column, " ".repeat(SIDE_PANEL_COLUMNS as usize - 4))?;
+            line.clear();
+        }
+        Ok(())
+    }
+
+    /// Fits the viewport to the terminal again after the side panel was shown or hidden, and draws the display again if the viewport changed.
+    fn relayout(&mut self, side_panel: bool) -> io::Result<()> {
+        let viewport = self.viewport;
+        self.fit_terminal_with(side_panel);
+        if self.viewport == viewport {
+            return Ok(());
+        }
+        let current = std::mem::replace(&mut self.viewport, viewport);
+        self.erase()?;
+        self.viewport = current;
+        self.redraw()
+    }
+
+    /// Erases the previous panel and renders the lines of the new one below the status line. Called by the `show_panel(...)` method.
+    fn render_panel(&mut self, text: &str) -> io::Result<()> {
+        let top = self.viewport.height + 4;
+        for y in 0..self.panel_lines {
+            write!(self.out, "{}[{};{}H{}[K", 27 as char, top + y, self.left + 1, 27 as char)?;
+        }
+        let mut lines = 0;
+        for line in text.lines() {
+            write!(self.out, "{}[{};{}H{}", 27 as char, top + lines, self.left + 1, line)?;
+            lines += 1;
+        }
+        self.panel_lines = lines;
+        writeln!(self.out, "{}[{};{}H", 27 as char, top + lines, 0)?;
+        self.out.flush()
+    }
+
+    /// Changes the number of pixels shown, e.g. when the SUPER-CHIP switches to its high-resolution mode. Erases the display from the terminal, turns
+    /// off all pixels and draws the border of the new size.
+    fn resize(&mut self, screen_width: u16, screen_height: u16) -> io::Result<()> {
+        self.erase()?;
+        self.erase_side_panel()?;
+        log::debug!("Display resized to {}x{} pixels", screen_width, screen_height);
+        self.pixels = [[0u8; MAX_DISPLAY_SIZE.0]; MAX_DISPLAY_SIZE.1];
+        self.fading_rows = 0;
+        self.viewport = Viewport::full(screen_width, screen_height, self.viewport.cell_width);
+        self.fit_to_terminal();
+        self.clear_screen()?;
+        self.render_status_line()
+    }
+
+    /// Erases the display with its border and status line from the terminal.
+    fn erase(&mut self) -> io::Result<()> {
+        let blank = " ".repeat((self.viewport.width * self.viewport.cell_width + 2).max(40) as usize);
+        for y in 0..self.viewport.height + 3 {
+            write!(self.out, "{}[{};{}H{}", 27 as char, y + 1, self.left + 1, blank)?;
+        }
+        Ok(())
+    }
+
+    /// Clears the terminal and draws the border of the display. Called when `System::run_with(...)` starts.
+    fn clear_screen(&mut self) -> io::Result<()> {
+        let inner = (self.viewport.width * self.viewport.cell_width) as usize;
+        let bottom = self.viewport.height + 1;
+        let background = self.glyph(false).repeat(self.viewport.width as usize);
+        for y in 0..=bottom {
+            write!(self.out, "{}[{};{}H", 27 as char, y + 1, self.left + 1)?;
+            match y {
+                0 => write!(self.out, "╔{}╗", "═".repeat(inner))?,
+                y if y == bottom => write!(self.out, "╚{}╝", "═".repeat(inner))?,
+                _ => write!(self.out, "║{}║", background)?,
+            }
+        }
+        writeln!(self.out, "{}[{};{}H", 27 as char, self.viewport.height + 4, 0)?;
+        self.out.flush()
+    }
+
+    /// Clears the display and renders all pixels of the viewport and the status line again, e.g. after the viewport moved.
+    fn redraw(&mut self) -> io::Result<()> {
+        self.clear_screen()?;
+        let mut change_positions = Vec::new();
+        for y in self.viewport.y..self.viewport.y + self.viewport.height {
+            for x in self.viewport.x..self.viewport.x + self.viewport.width {
+                if self.pixels[y as usize][x as usize] > 0 {
+                    change_positions.push((y, x));
+                }
+            }
+        }
+        self.render(&change_positions)?;
+        self.render_status_line()
+    }
+
+    /// Renders the pixels at the given positions to the console, e.g. all pixels that are on after the display was cleared by `redraw()`.
+    fn render(&mut self, change_positions: &[(u16, u16)]) -> io::Result<()> {
+        self.write_pixels(change_positions)?;
+        self.out.flush()
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(changes = change_positions.len())))]
+    /// Writes the current state of the `pixels` at the given positions (in row-major order) to the output, without flushing it. The cursor is only
+    /// moved at the start of each run of adjacent positions in a row, so a changed sprite costs one escape sequence per row.
+    fn write_pixels(&mut self, change_positions: &[(u16, u16)]) -> io::Result<()> {
+        let viewport = self.viewport;
+        let (on, off) = (self.glyph(true), self.glyph(false));
+        let mut cursor = None;
+        for (y, x) in change_positions.iter().filter(|(y, x)| viewport.contains(*y, *x)) {
+            if cursor != Some((*y, *x)) {
+                let row = *y - viewport.y + 2;
+                let column = self.left + 2 + (*x - viewport.x) * viewport.cell_width;
+                write!(self.out, "{}[{};{}H", 27 as char, row, column)?;
+            }
+            let glyph = if self.pixels[*y as usize][*x as usize] > 0 { &on } else { &off };
+            self.out.write_all(glyph.as_bytes())?;
+            cursor = Some((*y, *x + 1));
+        }
+        writeln!(self.out, "{}[{};{}H", 27 as char, viewport.height + 4, 0)
+    }
+
+    /// Gets the characters (including color escape sequences in high-contrast mode) that draw a single pixel in the given state.
+    fn glyph(&self, on: bool) -> String {
+        let cell = self.cell(on);
+        if self.options.high_contrast {
+            format!("{}[97;40m{}{}[0m", 27 as char, cell, 27 as char)
+        }
+        else {
+            cell
+        }
+    }
+
+    /// Gets the characters that draw a single pixel in the given state, without escape sequences.
+    fn cell(&self, on: bool) -> String {
+        let c = if on != self.options.invert { '█' } else { ' ' };
+        c.to_string().repeat(self.viewport.cell_width as usize)
+    }
+
+    /// Renders all pixels as they are currently shown (including fading pixels) into a string, one line per row, e.g. to embed the screen in
+    /// another terminal application or to post it in a chat. Unlike the rendering to the output, the whole display is included, even if the
+    /// viewport only shows a part of it. The `DisplayOptions` are applied; escape sequences are only included if `style.ansi` is set.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut display = Display::with_output(Box::new(io::sink()));
+    /// display.update(&sys);
+    /// let frame = display.render_to_string(FrameStyle { border: true, ansi: false });
+    /// ```
+    pub fn render_to_string(&self, style: FrameStyle) -> String {
+        let glyph = |on: bool| if style.ansi { self.glyph(on) } else { self.cell(on) };
+        let (on, off) = (glyph(true), glyph(false));
+        let (width, height) = (self.viewport.screen_width as usize, self.viewport.screen_height as usize);
+        let inner = "═".repeat(width * self.viewport.cell_width as usize);
+        let mut frame = String::new();
+        if style.border {
+            frame += &format!("╔{}╗\n", inner);
+        }
+        for row in &self.pixels[..height] {
+            if style.border {
+                frame.push('║');
+            }
+            for pixel in &row[..width] {
+                frame += if *pixel > 0 { &on } else { &off };
+            }
+            if style.border {
+                frame.push('║');
+            }
+            frame.push('\n');
+        }
+        if style.border {
+            frame += &format!("╚{}╝\n", inner);
+        }
+        frame
+    }
+
+    /// Fits the viewport to the size of the terminal, if the display renders to stdout. The columns of the side panel are reserved while it is shown.
+    fn fit_to_terminal(&mut self) {
+        self.fit_terminal_with(!self.side_panel.is_empty());
+    }
+
+    /// Fits the viewport to the size of the terminal like `fit_to_terminal()`, reserving the columns of the side panel if `side_panel` is set.
+    fn fit_terminal_with(&mut self, side_panel: bool) {
+        if !self.fit_terminal {
+            return;
+        }
+        if let Some((terminal_size::Width(columns), terminal_size::Height(rows))) = terminal_size::terminal_size() {
+            let cell_width = self.scale.or(self.options.high_contrast.then_some(2));
+            let (screen_width, screen_height) = (self.viewport.screen_width, self.viewport.screen_height);
+            let reserved = if side_panel { SIDE_PANEL_COLUMNS } else { 0 };
+            self.viewport = Viewport::fit(columns.saturating_sub(self.left + reserved), rows, cell_width, screen_width, screen_height);
+            if self.viewport.is_partial() || self.viewport.cell_width < 2 {
+                log::info!("Terminal is {}x{}, showing {}x{} pixels", columns, rows, self.viewport.width, self.viewport.height);
+            }
+        }
+    }
+
+    /// Renders the status line below the display, consisting of the sound indicator (if the sound timer is non-zero) and the current emulation speed.
+    /// Called whenever the display is drawn again; `update(...)` uses `write_status_line()` instead.
+    fn render_status_line(&mut self) -> io::Result<()> {
+        self.write_status_line()?;
+        self.out.flush()
+    }
+
+    /// Writes the status line to the output like `render_status_line()`, without flushing it.
+    fn write_status_line(&mut self) -> io::Result<()> {
+        let indicator = if self.sound_active { "🔊 BEEP" } else { "       " };
+        write!(self.out, "{}[{};{}H{}  {:>5} Hz (+/- to change)", 27 as char, self.viewport.height + 3, self.left + 1, indicator, self.loop_frequency)?;
+        writeln!(self.out, "{}[{};{}H", 27 as char, self.viewport.height + 4, 0)
+    }
+
+}
+
+/// Gets the number of terminal columns a line of text takes up, not counting the `ESC [ ... m` sequences that change its colors.
+fn visible_width(line: &str) -> usize {
+    let mut width = 0;
+    let mut in_escape = false;
+    for c in line.chars() {
+        match c {
+            '\u{1b}' => in_escape = true,
+            'm' if in_escape => in_escape = false,
+            _ if in_escape => {},
+            _ => width += 1,
+        }
+    }
+    width
+}
+
+impl Default for Display {
+
+    fn default() -> Display {
+        Display::new()
+    }
+}
+
+impl Renderer for Display {
+
+    /// Clears the terminal and draws the border. A display rendering to stdout first fits its viewport to the size of the terminal.
+    fn begin(&mut self) {
+        self.fit_to_terminal();
+        let _res = self.clear_screen();
+    }
+
+    fn update(&mut self, sys: &System) -> bool {
+        Display::update(self, sys)
+    }
+
+    fn set_title(&mut self, title: &str) {
+        Display::set_title(self, title)
+    }
+
+    fn pan(&mut self, dx: i8, dy: i8) {
+        Display::pan(self, dx, dy)
+    }
+
+    fn toggle(&mut self, option: DisplayOption) {
+        Display::toggle(self, option)
+    }
+
+    fn show_panel(&mut self, text: &str) {
+        Display::show_panel(self, text)
+    }
+
+    fn show_side_panel(&mut self, text: &str) {
+        Display::show_side_panel(self, text)
+    }
+}
+
+/// Describes why a program could not be loaded into memory by `System::load_at(...)`.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The program does not fit into the main memory when loaded at the given address.
+    OutOfMemory { address: u16, length: usize },
+    /// The program would overwrite the built-in font sprites.
+    OverlapsFont { address: u16, length: usize },
+    /// The program would overlap with the display buffer, which starts at the address `display`.
+    OverlapsDisplay { address: u16, length: usize, display: u16 },
+}
+
+impl std::fmt::Display for LoadError {
+
+    /// Formats the `LoadError` as a human-readable message, including the affected address range.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::OutOfMemory { address, length } =>
+                write!(f, "program of {} bytes does not fit into memory at 0x{:0>3X}", length, address),
+            LoadError::OverlapsFont { address, length } =>
+                write!(f, "program of {} bytes at 0x{:0>3X} overlaps the font region 0x{:0>3X}-0x{:0>3X}", length, address, FONT_START, FONT_END - 1),
+            LoadError::OverlapsDisplay { address, length, display } =>
+                write!(f, "program of {} bytes at 0x{:0>3X} overlaps the display buffer at 0x{:0>3X}", length, address, display),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Describes why an instruction could not be executed by `Instruction::execute(...)`. Each variant contains the address of the instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// The opcode does not belong to any instruction of the emulated variant.
+    UnknownOpcode { pc: u16, opcode: u16 },
+    /// `00EE` returned from a subroutine while the stack was empty.
+    StackUnderflow { pc: u16 },
+    /// The instruction wrote to an address outside of the main memory.
+    AddressOutOfRange { pc: u16, address: u16 },
+    /// The instruction wrote to a region protected by the `write_protection` of the system.
+    WriteProtected { pc: u16, address: u16, region: &'static str },
+}
+
+impl Chip8Error {
+
+    /// Gets the address of the instruction that caused the error.
+    pub fn pc(&self) -> u16 {
+        match self {
+            Chip8Error::UnknownOpcode { pc, .. } | Chip8Error::StackUnderflow { pc } | Chip8Error::AddressOutOfRange { pc, .. }
+            | Chip8Error::WriteProtected { pc, .. } => *pc,
+        }
+    }
+}
+
+impl std::fmt::Display for Chip8Error {
+
+    /// Formats the `Chip8Error` as a human-readable message, including the address of the instruction.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Chip8Error::UnknownOpcode { pc, opcode } => write!(f, "unknown opcode {:0>4X} at 0x{:0>3X}", opcode, pc),
+            Chip8Error::StackUnderflow { pc } => write!(f, "return without a subroutine call at 0x{:0>3X}", pc),
+            Chip8Error::AddressOutOfRange { pc, address } =>
+                write!(f, "write to 0x{:0>4X} outside of the memory by the instruction at 0x{:0>3X}", address, pc),
+            Chip8Error::WriteProtected { pc, address, region } =>
+                write!(f, "write to the protected {} at 0x{:0>3X} by the instruction at 0x{:0>3X}", region, address, pc),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+/// Selects what `System::step()` does when an instruction fails with a `Chip8Error`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorMode {
+    /// The system halts, and the error is kept in `System::error()`.
+    Halt,
+    /// The error is logged and the failed instruction is skipped, e.g. to see how far a slightly broken ROM gets.
+    Continue,
+}
+
+/// A struct representing the state of a CHIP-8 processor and its peripherals.
+pub struct System {
+    pub memory: Memory,
+    pub registers: Registers,
+    pub stack: Stack,
+    pub delay_timer: Timer,
+    pub sound_timer: Timer,
+    pub keyboard: Keyboard,
+
+    /// The random number generator of `CXNN`. It is seeded from the entropy of the host, but its state is part of a `SaveState`, so a restored
+    /// state draws the same numbers.
+    pub rng: ChaCha8Rng,
+    pub quirks: Quirks,
+    /// The RPL user flags of the HP48, which SUPER-CHIP programs save and restore with `FX75`/`FX85`. They are kept when the system is reset.
+    pub rpl_flags: [u8; 8],
+
+    pub pc: u16,
+    pub screen_width: u8,
+    pub screen_height: u8,
+    pub timer_mode: TimerMode,
+    pub key_mode: KeyMode,
+    /// Maps the CHIP-8 key of each key event received by `run_with(...)` to the key that is pressed instead, e.g. with the key remappings of a
+    /// `RomProfile`. Keys set directly on the `keyboard` are not remapped.
+    pub keymap: [u8; 16],
+    pub write_protection: WriteProtection,
+    pub error_mode: ErrorMode,
+    /// The breakpoints and the pause state used by `run_with(...)`. They are kept when the system is reset.
+    pub debugger: Debugger,
+    loop_frequency: u16,
+    cycles: u64,
+    emulated_base: Duration,
+    base_cycles: u64,
+    timer_frames: u64,
+    timer_frequency: u16,
+    stats: Stats,
+    tracer: Option<Tracer>,
+    playlist: Vec<PathBuf>,
+    playlist_position: usize,
+    program_updates: Option<Receiver<program::Program>>,
+    loaded: Option<(Vec<u8>, u16)>,
+    side_effects: SideEffects,
+    persistent: Option<PersistentRegion>,
+    state_file: Option<PathBuf>,
+    pc_history: VecDeque<u16>,
+    audio: Box<dyn Audio>,
+    audio_playing: bool,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    variant: Variant,
+    exited: bool,
+    error: Option<Chip8Error>,
+    input_log: Option<InputLog>,
+}
+
+impl System {
+
+    /// Creates a new instance of the `System` struct with the default `Config`.
+    /// 
+    /// Sub-structs are initialized as empty, using their individual `new()` methods.  
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let sys = System::new();
+    /// ```
+    /// 
+    pub fn new() -> System {
+        System::with_config(Config::new())
+    }
+
+    /// Creates a new instance of the `System` struct with the given settings.
+    /// 
+    /// # Panics
+    /// If the `display_layout` of the configuration is invalid, see `Memory::set_display_layout(...)`.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let sys = System::with_config(Config { quirks: Quirks::new().with_flags("vf_reset")?, ..Config::new() });
+    /// ```
+    pub fn with_config(config: Config) -> System {
+        let layout = match config.variant {
+            Variant::Chip8 => config.display_layout,
+            Variant::SuperChip | Variant::XoChip => DisplayLayout::LORES,
+        };
+        let mut memory = config.variant.memory();
+        memory.set_display_layout(layout);
+        System { 
+            memory,
+            registers: Registers::new(),
+            stack: Stack::new(),
+            delay_timer: Timer::new(),
+            sound_timer: Timer::new(),
+            keyboard: Keyboard::new(),
+            rng: ChaCha8Rng::from_entropy(),
+            quirks: config.quirks,
+            rpl_flags: [0; 8],
+            pc: 0,
+            screen_width: layout.width,
+            screen_height: layout.height,
+            timer_mode: config.timer_mode,
+            key_mode: config.key_mode,
+            keymap: config.keymap,
+            write_protection: config.write_protection,
+            error_mode: config.error_mode,
+            debugger: Debugger::new(),
+            loop_frequency: config.loop_frequency.max(1),
+            cycles: 0,
+            emulated_base: Duration::ZERO,
+            base_cycles: 0,
+            timer_frames: 0,
+            timer_frequency: config.timer_frequency.max(1),
+            stats: Stats::default(),
+            tracer: None,
+            playlist: Vec::new(),
+            playlist_position: 0,
+            program_updates: None,
+            loaded: None,
+            side_effects: SideEffects::default(),
+            persistent: None,
+            state_file: None,
+            pc_history: VecDeque::with_capacity(PC_HISTORY_LENGTH),
+            audio: Box::new(Silent),
+            audio_playing: false,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            variant: config.variant,
+            exited: false,
+            error: None,
+            input_log: None,
+        }
+    }
+
+    /// Loads a program into the system's main memory.
+    /// 
+    /// The loaded program's address space starts at 0x200, and its PC is initialized to 0x200.
+    /// 
+    /// # Errors
+    /// See `load_at(...)`.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// let program = Program::load("path")?;
+    /// sys.load(program)?;
+    /// ```
+    pub fn load(&mut self, program: program::Program) -> Result<(), LoadError> {
+        self.load_at(&program, PROGRAM_START)
+    }
+
+    /// Loads a program into the system's main memory at the given base address, and initializes the PC to that address.
+    /// 
+    /// This is needed for ROMs written for systems with a different program start (e.g. 0x600 on the ETI-660), and for injecting helper routines.
+    /// 
+    /// # Errors
+    /// Returns a `LoadError` (and leaves the memory untouched) if the program would not fit into memory, or if it would overwrite the font sprites
+    /// or the display buffer.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// let program = Program::load("path")?;
+    /// sys.load_at(&program, 0x600)?;
+    /// ```
+    pub fn load_at(&mut self, program: &program::Program, address: u16) -> Result<(), LoadError> {
+        let length = program.instructions.len();
+        self.check_bounds(address, length)?;
+        for (idx, instr) in program.instructions.iter().enumerate() {
+            self.memory.store(address + idx as u16, *instr);
+        }
+        self.pc = address;
+        self.loaded = Some((program.instructions.clone(), address));
+        log::info!("Loaded {} bytes at 0x{:0>3X}", length, address);
+        Ok(())
+    }
+
+    /// Restarts the most recently loaded program: resets the memory (reloading the font and the program bytes), registers, stack, timers and keyboard,
+    /// and sets the PC to the program's start address. The quirks and the loop frequency are kept. If no program was loaded yet, only the state is
+    /// reset.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.load(Program::load("pong.ch8")?)?;
+    /// while sys.step() {}
+    /// sys.reset();
+    /// assert_eq!(sys.pc, 0x200);
+    /// ```
+    pub fn reset(&mut self) {
+        self.flush_persistent();
+        self.reset_state();
+        if let Some((bytes, address)) = &self.loaded {
+            for (idx, byte) in bytes.iter().enumerate() {
+                self.memory.store(address + idx as u16, *byte);
+            }
+            self.pc = *address;
+        }
+        self.restore_persistent();
+        log::info!("System reset");
+    }
+
+    /// Checks whether a program of the given length can be loaded at the given address without exceeding the memory or overwriting the font sprites
+    /// or the display buffer.
+    fn check_bounds(&self, address: u16, length: usize) -> Result<(), LoadError> {
+        let start = address as usize;
+        let end = start + length;
+        if end > self.memory.size() {
+            return Err(LoadError::OutOfMemory { address, length });
+        }
+        if length > 0 && start < FONT_END as usize && end > FONT_START as usize {
+            return Err(LoadError::OverlapsFont { address, length });
+        }
+        let display = self.memory.display_layout();
+        if length > 0 && display.start < VIDEO_MEMORY_START && start < display.end() as usize && end > display.start as usize {
+            return Err(LoadError::OverlapsDisplay { address, length, display: display.start });
+        }
+        Ok(())
+    }
+
+    /// Resets the machine and loads a new program at `PROGRAM_START`, e.g. while `run(...)` is executing.
+    /// 
+    /// The memory, registers, stack, timers and keyboard are reset to their initial state and the whole display is redrawn. Settings such as the
+    /// `quirks` and the `loop_frequency`, as well as the collected statistics, are kept.
+    /// 
+    /// # Errors
+    /// Returns a `LoadError` (and leaves the machine untouched) if the program cannot be loaded, see `load_at(...)`.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.load(Program::load("pong.ch8")?)?;
+    /// sys.swap_program(Program::load("tetris.ch8")?)?;
+    /// ```
+    pub fn swap_program(&mut self, program: program::Program) -> Result<(), LoadError> {
+        self.replace_program(program, None)
+    }
+
+    /// Swaps to a new program like `swap_program(...)`. If `save_file` is given, the persistent region is flushed to its current file and then
+    /// backed by `save_file` before it is restored for the new program. If the new program overlaps the persistent region, persistence is turned
+    /// off instead, so the program is not overwritten.
+    fn replace_program(&mut self, program: program::Program, save_file: Option<PathBuf>) -> Result<(), LoadError> {
+        self.check_bounds(PROGRAM_START, program.instructions.len())?;
+        self.flush_persistent();
+        if let Some(file) = save_file {
+            if let Some(region) = &mut self.persistent {
+                region.set_path(file);
+            }
+        }
+        let range = PROGRAM_START..PROGRAM_START.saturating_add(program.instructions.len() as u16);
+        if self.persistent.as_ref().is_some_and(|region| region.overlaps(&range)) {
+            log::warn!("The program overlaps the persistent region, which is no longer saved");
+            self.persistent = None;
+        }
+        self.reset_state();
+        self.load(program)?;
+        self.restore_persistent();
+        Ok(())
+    }
+
+    /// Resets the memory, registers, stack, timers and keyboard to their initial state and marks the whole display as changed.
+    /// 
+    /// The timers are reset in place, since they are shared with the `TimerTicker` thread while `run(...)` is executing.
+    fn reset_state(&mut self) {
+        let layout = match self.variant {
+            Variant::Chip8 => self.memory.display_layout(),
+            Variant::SuperChip | Variant::XoChip => DisplayLayout::LORES,
+        };
+        self.memory = self.variant.memory();
+        self.set_display_layout(layout);
+        self.registers = Registers::new();
+        self.stack = Stack::new();
+        self.delay_timer.set(0);
+        self.sound_timer.set(0);
+        self.keyboard = Keyboard::new();
+        self.pc = 0;
+        self.side_effects = SideEffects::default();
+        self.pc_history.clear();
+        self.exited = false;
+        self.error = None;
+        self.set_audio_pattern(None);
+        self.set_pitch(DEFAULT_PITCH);
+    }
+
+    /// Stores a value to memory on behalf of the running program, enforcing the `write_protection`. The PC is expected to point behind the
+    /// instruction performing the write, as it does while the instruction is executed.
+    /// 
+    /// # Errors
+    /// Returns `Chip8Error::WriteProtected` if the address lies within a protected region, or `Chip8Error::AddressOutOfRange` if it lies outside
+    /// of the main memory. The memory is left untouched in both cases.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.write_protection.font = true;
+    /// sys.store(0x300, 42)?;
+    /// ```
+    pub fn store(&mut self, address: u16, value: u8) -> Result<(), Chip8Error> {
+        let pc = self.pc.wrapping_sub(2);
+        if address as usize >= self.memory.size() {
+            return Err(Chip8Error::AddressOutOfRange { pc, address });
+        }
+        if let Some(region) = self.protected_region(address) {
+            return Err(Chip8Error::WriteProtected { pc, address, region });
+        }
+        self.memory.store(address, value);
+        self.debugger.notify_write(address, pc);
+        Ok(())
+    }
+
+    /// Gets the name of the protected region the address lies in, or `None` if the address may be written to.
+    fn protected_region(&self, address: u16) -> Option<&'static str> {
+        if self.write_protection.font && (FONT_START..FONT_END).contains(&address) {
+            return Some("font");
+        }
+        match &self.loaded {
+            Some((bytes, start)) if self.write_protection.program && (*start as usize..*start as usize + bytes.len()).contains(&(address as usize)) => {
+                Some("program")
+            },
+            _ => None,
+        }
+    }
+
+    /// Sets the list of ROMs that can be cycled through with the `F2` hotkey while `run(...)` is executing. The first entry is assumed to be the
+    /// program that is currently loaded.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.set_playlist(vec![PathBuf::from("pong.ch8"), PathBuf::from("tetris.ch8")]);
+    /// ```
+    pub fn set_playlist(&mut self, paths: Vec<PathBuf>) {
+        self.playlist = paths;
+        self.playlist_position = 0;
+    }
+
+    /// Sets the `Audio` output that plays the tone while the sound timer is active and `run(...)` is executing. By default, the system is `Silent`.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.set_audio(Box::new(Beeper::new()?));
+    /// ```
+    pub fn set_audio(&mut self, audio: Box<dyn Audio>) {
+        self.audio = audio;
+        self.audio_playing = false;
+        self.audio.set_pattern(self.audio_pattern, self.pitch);
+    }
+
+    /// Gets the audio pattern loaded by the XO-CHIP instruction `F002`, or `None` if the plain tone is played.
+    pub fn audio_pattern(&self) -> Option<[u8; 16]> {
+        self.audio_pattern
+    }
+
+    /// Sets the audio pattern of the XO-CHIP: 128 bits that are played in a loop while the sound timer is active, or `None` for the plain tone.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// sys.set_audio_pattern(Some([0xF0; 16]));
+    /// ```
+    pub fn set_audio_pattern(&mut self, pattern: Option<[u8; 16]>) {
+        self.audio_pattern = pattern;
+        self.audio.set_pattern(self.audio_pattern, self.pitch);
+    }
+
+    /// Gets the pitch of the audio pattern, set by the XO-CHIP instruction `FX3A`. The default `DEFAULT_PITCH` plays 4000 bits per second.
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    /// Sets the pitch of the audio pattern. The pattern is played at `4000 * 2^((pitch - 64) / 48)` bits per second.
+    pub fn set_pitch(&mut self, pitch: u8) {
+        self.pitch = pitch;
+        self.audio.set_pattern(self.audio_pattern, self.pitch);
+    }
+
+    /// Sets a channel through which new programs are received while `run(...)` is executing. Each received program replaces the running one, see
+    /// `swap_program(...)`. This is used by the `--watch` option to reload a ROM whenever its file changes.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let (tx, rx) = mpsc::channel();
+    /// sys.set_program_updates(rx);
+    /// tx.send(Program::load("game.ch8")?)?;
+    /// ```
+    pub fn set_program_updates(&mut self, programs: Receiver<program::Program>) {
+        self.program_updates = Some(programs);
+    }
+
+    /// Swaps to the next program of the playlist, wrapping around at its end. Returns the title of the new program, or `None` if the playlist is
+    /// empty or the program could not be loaded.
+    fn load_next_program(&mut self) -> Option<String> {
+        if self.playlist.is_empty() {
+            log::warn!("No other programs to swap to");
+            return None;
+        }
+        self.playlist_position = (self.playlist_position + 1) % self.playlist.len();
+        self.load_current_program()
+    }
+
+    /// Loads the current program of the playlist from its file again, e.g. after it was rebuilt. Returns the title of the program, or `None` if
+    /// there is no playlist or the program could not be loaded.
+    fn reload_program(&mut self) -> Option<String> {
+        if self.playlist.is_empty() {
+            log::warn!("No file to reload the program from");
+            return None;
+        }
+        let title = self.load_current_program()?;
+        log::info!("Program reloaded from '{}'", self.playlist[self.playlist_position].display());
+        Some(title)
+    }
+
+    /// Swaps to the program of the playlist at the current position, updating the files of the persistent region and of the save state. Returns
+    /// the title of the program, or `None` if it could not be loaded.
+    fn load_current_program(&mut self) -> Option<String> {
+        let path = &self.playlist[self.playlist_position];
+        let program = match program::Program::load(path) {
+            Ok(program) => program,
+            Err(e) => {
+                log::error!("Could not load '{}': {}", path.display(), e);
+                return None;
+            },
+        };
+        let title = program.info().title;
+        let save_file = PersistentRegion::file_for(path);
+        let state_file = SaveState::file_for(path);
+        match self.replace_program(program, Some(save_file)) {
+            Ok(()) => {
+                if let Some(file) = &mut self.state_file {
+                    *file = state_file;
+                }
+                Some(title)
+            },
+            Err(e) => {
+                log::error!("Could not load '{}': {}", title, e);
+                None
+            },
+        }
+    }
+
+    /// Increments the CHIP-8's PC by two.
+    /// 
+    /// ' Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.increment_pc();
+    /// ```
+    pub fn increment_pc(&mut self) {
+        self.pc = self.pc.wrapping_add(2);
+    }
+
+    /// Skips the next instruction, as the conditional skip instructions do. On the XO-CHIP, this skips both words of `F000 NNNN`.
+    pub fn skip(&mut self) {
+        if self.variant == Variant::XoChip && self.fetch() == 0xF000 {
+            self.increment_pc();
+        }
+        self.increment_pc();
+    }
+
+    /// Gets the number of instructions executed since the system was created.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::System;
+    /// let sys = System::new();
+    /// assert_eq!(sys.cycles(), 0);
+    /// ```
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Gets the statistics collected while running, such as the number of executed instructions, rendered frames and key presses.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.run(&mut display);
+    /// println!("{}", sys.stats());
+    /// ```
+    pub fn stats(&self) -> Stats {
+        Stats { instructions: self.cycles, ..self.stats }
+    }
+
+    /// Gets the amount of time that has passed on the emulated machine, derived from the number of executed instructions and the `loop_frequency`.
+    /// 
+    /// Unlike the wall-clock time, this value does not depend on how long the host took to execute and render the instructions.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let sys = System::new();
+    /// let elapsed = sys.emulated_time();
+    /// ```
+    pub fn emulated_time(&self) -> Duration {
+        let cycles = self.cycles - self.base_cycles;
+        self.emulated_base + Duration::from_nanos((cycles as u128 * 1_000_000_000 / self.loop_frequency as u128) as u64)
+    }
+
+    /// Gets the number of instructions executed per second.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::System;
+    /// let sys = System::new();
+    /// assert_eq!(sys.loop_frequency(), 700);
+    /// ```
+    pub fn loop_frequency(&self) -> u16 {
+        self.loop_frequency
+    }
+
+    /// Sets the number of instructions executed per second. The new value is picked up by a running `run(...)` loop immediately.
+    /// 
+    /// A frequency of `0` is treated as `1`.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.set_loop_frequency(1000);
+    /// ```
+    pub fn set_loop_frequency(&mut self, frequency: u16) {
+        self.emulated_base = self.emulated_time();
+        self.base_cycles = self.cycles;
+        self.loop_frequency = frequency.max(1);
+        log::info!("Loop frequency set to {} Hz", self.loop_frequency);
+    }
+
+    /// Gets the number of instructions executed per frame of 1/60 s by `run(...)`, rounded down if the `loop_frequency` is not a multiple of 60.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::System;
+    /// let sys = System::new();
+    /// assert_eq!(sys.instructions_per_frame(), 11);
+    /// ```
+    pub fn instructions_per_frame(&self) -> u16 {
+        self.loop_frequency / FRAMES_PER_SECOND as u16
+    }
+
+    /// Sets the number of instructions executed per frame of 1/60 s, i.e. sets the `loop_frequency` to `60 * instructions`, capped at the largest
+    /// frequency that can be represented.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::System;
+    /// let mut sys = System::new();
+    /// sys.set_instructions_per_frame(15);
+    /// assert_eq!(sys.loop_frequency(), 900);
+    /// ```
+    pub fn set_instructions_per_frame(&mut self, instructions: u16) {
+        self.set_loop_frequency(instructions.saturating_mul(FRAMES_PER_SECOND as u16));
+    }
+
+    /// Gets the rate at which the delay and sound timers are decremented, in Hz.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::{System, DEFAULT_TIMER_FREQUENCY};
+    /// let sys = System::new();
+    /// assert_eq!(sys.timer_frequency(), DEFAULT_TIMER_FREQUENCY);
+    /// ```
+    pub fn timer_frequency(&self) -> u16 {
+        self.timer_frequency
+    }
+
+    /// Sets the rate at which the delay and sound timers are decremented, e.g. 50 Hz for systems derived from PAL televisions. Applies to both
+    /// `TimerMode`s; a running `TimerTicker` keeps its rate until `run(...)` is called again.
+    /// 
+    /// A frequency of `0` is treated as `1`.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.set_timer_frequency(50);
+    /// ```
+    pub fn set_timer_frequency(&mut self, frequency: u16) {
+        self.timer_frequency = frequency.max(1);
+        self.timer_frames = self.emulated_frames();
+        log::info!("Timer frequency set to {} Hz", self.timer_frequency);
+    }
+
+    /// Moves and/or resizes the display buffer (see `Memory::set_display_layout(...)`) and sets `screen_width` and `screen_height` accordingly.
+    /// The layout is kept when the system is reset or swaps programs.
+    /// 
+    /// # Panics
+    /// If the layout is invalid, see `Memory::set_display_layout(...)`.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.set_display_layout(DisplayLayout { start: 0xE00, ..DisplayLayout::DEFAULT });
+    /// sys.load(program)?;
+    /// ```
+    pub fn set_display_layout(&mut self, layout: DisplayLayout) {
+        self.memory.set_display_layout(layout);
+        self.screen_width = layout.width;
+        self.screen_height = layout.height;
+        log::info!("Display buffer at 0x{:0>3X}-0x{:0>3X} ({}x{} pixels)", layout.start, layout.end() - 1, layout.width, layout.height);
+    }
+
+    /// Gets the variant of the CHIP-8 that is emulated.
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Switches the variant of the CHIP-8 that is emulated. The SUPER-CHIP and XO-CHIP start in their low-resolution mode (`DisplayLayout::LORES`),
+    /// and switching back to the CHIP-8 restores `DisplayLayout::DEFAULT`. All of them clear the display; the variant is kept when the system is
+    /// reset or swaps programs. Switching to or from the XO-CHIP replaces the memory with one of the right size, so the program must be loaded
+    /// afterwards.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.set_variant(Variant::SuperChip);
+    /// sys.load(program)?;
+    /// ```
+    pub fn set_variant(&mut self, variant: Variant) {
+        if (variant == Variant::XoChip) != (self.variant == Variant::XoChip) {
+            self.memory = variant.memory();
+        }
+        self.variant = variant;
+        match variant {
+            Variant::Chip8 => self.set_display_layout(DisplayLayout::DEFAULT),
+            Variant::SuperChip | Variant::XoChip => self.set_display_layout(DisplayLayout::LORES),
+        }
+    }
+
+    /// Checks whether the SUPER-CHIP or XO-CHIP is in its high-resolution mode.
+    pub fn hires(&self) -> bool {
+        self.variant.superchip() && self.memory.display_layout() == DisplayLayout::HIRES
+    }
+
+    /// Switches the SUPER-CHIP or XO-CHIP between its low- and high-resolution modes (`00FE`/`00FF`), which clears the display. Has no effect for
+    /// the CHIP-8.
+    pub fn set_hires(&mut self, hires: bool) {
+        if self.variant.superchip() && hires != self.hires() {
+            self.set_display_layout(if hires { DisplayLayout::HIRES } else { DisplayLayout::LORES });
+        }
+    }
+
+    /// Stops the program, so the next `step()` halts as if it had reached the opcode `0000`. Used by the SUPER-CHIP instruction `00FD`.
+    pub fn exit(&mut self) {
+        self.exited = true;
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(pc = self.pc)))]
+    /// Fetches the two bytes of the instruction at the current PC and combines them into a single opcode. The PC itself is not modified.
+    fn fetch(&self) -> u16 {
+        big_endian_8_2(self.memory.get(self.pc), self.memory.get(self.pc.wrapping_add(1)))
+    }
+
+    /// Enables tracing of all executed instructions to the file at the given path.
+    /// 
+    /// Events are recorded into a preallocated ring buffer and written to the file by a background thread (see `Tracer`), so tracing has only
+    /// a small impact on the emulation speed. The trace is completed when the system is dropped.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.enable_trace("trace.log")?;
+    /// ```
+    pub fn enable_trace<P>(&mut self, path: P) -> io::Result<()>
+        where P: AsRef<Path>, {
+            self.tracer = Some(Tracer::create(path)?);
+            Ok(())
+    }
+
+    /// Seeds the random number generator of `CXNN`, so the program draws the same random numbers in every run.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// sys.set_seed(42);
+    /// ```
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
+
+    /// Records the input of the session to an input log at the given path (see the `replay` module), so it can be reproduced later with
+    /// `replay_input(...)`.
+    /// 
+    /// The random number generator is seeded with a new random seed, which is stored in the log. Every key event (after the `keymap` is applied)
+    /// and every change of the `loop_frequency` is written with the cycle it was applied at. The timers are synchronized with the emulated time
+    /// before every instruction (see `sync_timers()`), so the session does not depend on how the instructions were split into frames.
+    /// 
+    /// Input that would change the machine in ways the log cannot reproduce is ignored while recording: the hotkeys that reset the program, load a
+    /// state, or load the next or a rebuilt program, edits in the memory editor of the `debugger`, and programs received through
+    /// `set_program_updates(...)`.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// sys.load(Program::load("pong.ch8")?)?;
+    /// sys.record_input("run.log")?;
+    /// sys.run(&mut display);
+    /// ```
+    pub fn record_input<P>(&mut self, path: P) -> io::Result<()>
+        where P: AsRef<Path>, {
+            let seed = rand::random();
+            self.input_log = Some(InputLog::Recording(InputRecorder::create(path, seed, self.loop_frequency)?));
+            self.set_seed(seed);
+            self.timer_mode = TimerMode::Frames;
+            Ok(())
+    }
+
+    /// Replays the input log at the given path, written by `record_input(...)`: seeds the random number generator and sets the `loop_frequency`
+    /// as recorded, and applies each recorded key event and change of the speed at the cycle it was recorded at, in the recorded order.
+    /// 
+    /// While the log is replayed, the CHIP-8 keys and the `+`/`-` hotkeys of the input are ignored, as well as the input that is ignored while
+    /// recording (see `record_input(...)`); afterwards, the input is used again.
+    /// 
+    /// # Errors
+    /// Returns an error if the file cannot be read or is not a valid input log.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// sys.load(Program::load("pong.ch8")?)?;
+    /// sys.replay_input("run.log")?;
+    /// sys.run(&mut display);
+    /// ```
+    pub fn replay_input<P>(&mut self, path: P) -> io::Result<()>
+        where P: AsRef<Path>, {
+            let replay = InputReplay::load(path)?;
+            self.set_seed(replay.seed());
+            self.set_loop_frequency(replay.frequency());
+            self.timer_mode = TimerMode::Frames;
+            self.input_log = Some(InputLog::Replaying(replay));
+            Ok(())
+    }
+
+    /// Checks whether an input log is being replayed, see `replay_input(...)`.
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.input_log, Some(InputLog::Replaying(_)))
+    }
+
+    /// Records the current input to the input log, or applies the changes of the replayed log that are due at the current cycle, and synchronizes
+    /// the timers. Called by `step()` before each instruction while there is an input log.
+    fn sync_input_log(&mut self) {
+        match &mut self.input_log {
+            Some(InputLog::Recording(recorder)) => if let Err(e) = recorder.record_frequency(self.cycles, self.loop_frequency) {
+                log::error!("Could not record the input, stopping the recording: {}", e);
+                self.input_log = None;
+            },
+            Some(InputLog::Replaying(_)) => {
+                while let Some(change) = match &mut self.input_log {
+                    Some(InputLog::Replaying(replay)) => replay.next_due(self.cycles),
+                    _ => None,
+                } {
+                    match change {
+                        InputChange::Key { key, pressed } => self.keyboard.set(key, pressed, self.cycles),
+                        InputChange::Frequency(frequency) => self.set_loop_frequency(frequency),
+                    }
+                }
+                if matches!(&self.input_log, Some(InputLog::Replaying(replay)) if replay.is_finished()) {
+                    log::info!("Replay finished at cycle {}, using the live input again", self.cycles);
+                    self.input_log = None;
+                }
+            },
+            None => {},
+        }
+        if self.timer_mode == TimerMode::Frames {
+            self.sync_timers();
+        }
+    }
+
+    /// Writes the recorded input, if any, to the input log. Errors are logged.
+    fn flush_input_log(&mut self) {
+        if let Some(InputLog::Recording(recorder)) = &mut self.input_log {
+            if let Err(e) = recorder.flush() {
+                log::error!("Could not write the input log: {}", e);
+            }
+        }
+    }
+
+    /// Backs the given address range with a host file, so the program can keep data such as high scores between sessions: the range is loaded from
+    /// the file now (after loading the program) and whenever the program is restarted, and written back when the program is restarted or swapped,
+    /// when `run(...)` returns and when the system is dropped. When the playlist swaps to another ROM, its own file (see
+    /// `PersistentRegion::file_for(...)`) is used instead.
+    /// 
+    /// # Errors
+    /// Returns an error if the range is invalid or overlaps the display buffer or the loaded program (see `PersistentRegion::new(...)`), or the
+    /// file cannot be read.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.load(Program::load("game.ch8")?)?;
+    /// sys.enable_persistence("game.sav", 0xE00..0xE10)?;
+    /// ```
+    pub fn enable_persistence<P>(&mut self, path: P, range: Range<u16>) -> io::Result<()>
+        where P: AsRef<Path>, {
+            let program = match &self.loaded {
+                Some((bytes, address)) => *address..address.saturating_add(bytes.len() as u16),
+                None => PROGRAM_START..PROGRAM_START,
+            };
+            let mut region = PersistentRegion::new(path, range, program, &self.memory)?;
+            region.restore(&mut self.memory)?;
+            self.persistent = Some(region);
+            Ok(())
+    }
+
+    /// Writes the persistent region (see `enable_persistence(...)`) to its file, if it changed. Errors are logged.
+    pub fn flush_persistent(&mut self) {
+        if let Some(region) = &mut self.persistent {
+            if let Err(e) = region.flush(&self.memory) {
+                log::error!("Could not save '{}': {}", region.path().display(), e);
+            }
+        }
+    }
+
+    /// Loads the persistent region from its file after the memory was reset. Errors are logged.
+    fn restore_persistent(&mut self) {
+        if let Some(region) = &mut self.persistent {
+            if let Err(e) = region.restore(&mut self.memory) {
+                log::error!("Could not load '{}': {}", region.path().display(), e);
+            }
+        }
+    }
+
+    /// Captures the complete state of the emulated machine: the memory (including the display buffer and its layout), the registers, the stack,
+    /// the timers, the PC, the RPL user flags, the state of the random number generator and the emulated time.
+    /// 
+    /// The settings of the system (such as the quirks or the loop frequency) and the state of the keyboard are not included.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let state = sys.snapshot();
+    /// sys.step();
+    /// sys.restore(state);
+    /// ```
+    pub fn snapshot(&self) -> SaveState {
+        SaveState {
+            version: SAVE_STATE_VERSION,
+            variant: self.variant,
+            memory: self.memory.clone(),
+            registers: self.registers.clone(),
+            stack: self.stack.clone(),
+            delay_timer: self.delay_timer.detach(),
+            sound_timer: self.sound_timer.detach(),
+            rpl_flags: self.rpl_flags,
+            pc: self.pc,
+            rng: self.rng.clone(),
+            cycles: self.cycles,
+            emulated_time: self.emulated_time(),
+            exited: self.exited,
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+        }
+    }
+
+    /// Restores a state captured with `snapshot()`. The machine continues exactly where the state was captured, drawing the same random numbers.
+    /// All keys are released, and the timers are set in place, since they are shared with the `TimerTicker` thread while `run(...)` is executing.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let state = sys.snapshot();
+    /// sys.step();
+    /// sys.restore(state);
+    /// ```
+    pub fn restore(&mut self, state: SaveState) {
+        let layout = state.memory.display_layout();
+        self.variant = state.variant;
+        self.memory = state.memory;
+        self.screen_width = layout.width;
+        self.screen_height = layout.height;
+        self.registers = state.registers;
+        self.stack = state.stack;
+        self.delay_timer.set(state.delay_timer.get());
+        self.sound_timer.set(state.sound_timer.get());
+        self.rpl_flags = state.rpl_flags;
+        self.pc = state.pc;
+        self.rng = state.rng;
+        self.cycles = state.cycles;
+        self.emulated_base = state.emulated_time;
+        self.base_cycles = state.cycles;
+        self.timer_frames = self.emulated_frames();
+        self.exited = state.exited;
+        self.error = None;
+        self.set_audio_pattern(state.audio_pattern);
+        self.set_pitch(state.pitch);
+        self.keyboard = Keyboard::new();
+        self.side_effects = SideEffects::default();
+        self.pc_history.clear();
+    }
+
+    /// Writes the state of the machine (see `snapshot()`) to the given file.
+    /// 
+    /// # Errors
+    /// Returns an error if the file cannot be written.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// sys.save_state("pong.state")?;
+    /// ```
+    pub fn save_state<P>(&self, path: P) -> io::Result<()>
+        where P: AsRef<Path>, {
+            self.snapshot().save(path)
+    }
+
+    /// Restores the state of the machine (see `restore(...)`) from a file written by `save_state(...)`.
+    /// 
+    /// # Errors
+    /// Returns an error if the file cannot be read or does not contain a valid state, in which case the machine is left untouched.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// sys.load_state("pong.state")?;
+    /// ```
+    pub fn load_state<P>(&mut self, path: P) -> io::Result<()>
+        where P: AsRef<Path>, {
+            self.restore(SaveState::load(path)?);
+            Ok(())
+    }
+
+    /// Sets the file used by the `F3` (save state) and `F4` (load state) hotkeys of `run(...)`. When the playlist swaps to another ROM, its own file
+    /// (see `SaveState::file_for(...)`) is used instead.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// sys.set_state_file(SaveState::file_for(Path::new("pong.ch8")));
+    /// ```
+    pub fn set_state_file(&mut self, path: PathBuf) {
+        self.state_file = Some(path);
+    }
+
+    /// Executes a single instruction: fetches the opcode at the PC, increments the PC and executes the instruction.
+    /// 
+    /// Unlike `run(...)`, this neither paces the execution nor decrements the timers or updates a display, so it can be used to run the system
+    /// headlessly at maximum speed (see `tick_timers()`). Returns `false` without doing anything if the opcode at the PC is `0000`, which halts the
+    /// system, if the program exited with `00FD`, or if an instruction failed before (see `error()`).
+    /// 
+    /// If the instruction fails with a `Chip8Error`, the error is logged. With `ErrorMode::Halt`, the system then halts and `false` is returned;
+    /// with `ErrorMode::Continue`, the instruction is skipped.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.load(program)?;
+    /// while sys.step() {}
+    /// ```
+    pub fn step(&mut self) -> bool {
+        if self.input_log.is_some() {
+            self.sync_input_log();
+        }
+        let opcode = self.fetch();
+        if let Some(tracer) = &self.tracer {
+            tracer.record(TraceEvent { cycle: self.cycles, pc: self.pc, opcode });
+        }
+        if opcode == 0 || self.exited || self.error.is_some() {
+            return false;
+        }
+        if self.pc_history.len() == PC_HISTORY_LENGTH {
+            self.pc_history.pop_front();
+        }
+        self.pc_history.push_back(self.pc);
+        self.increment_pc();
+
+        let op: Instruction = opcode.into();
+        if op.0 == 0xD {
+            self.stats.draw_calls += 1;
+        }
+        let result = op.execute(self);
+        self.cycles += 1;
+        match result {
+            Ok(effects) => self.side_effects = effects,
+            Err(e) => {
+                self.side_effects = SideEffects::default();
+                if self.error_mode == ErrorMode::Halt {
+                    log::error!("Halting: {}", e);
+                    self.error = Some(e);
+                    return false;
+                }
+                log::warn!("Skipping: {}", e);
+            },
+        }
+        true
+    }
+
+    /// Gets the error that halted the system, if an instruction failed with `ErrorMode::Halt`. The error is cleared when the system is reset or
+    /// a state is restored.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// while sys.step() {}
+    /// if let Some(e) = sys.error() {
+    ///     eprintln!("Halted: {}", e);
+    /// }
+    /// ```
+    pub fn error(&self) -> Option<&Chip8Error> {
+        self.error.as_ref()
+    }
+
+    /// Executes instructions as fast as possible until the given event occurs, e.g. to fast-forward to the next drawn frame or to the next key prompt
+    /// in a tool. The timers are ticked for the emulated time that passes (see `sync_timers()`). The instruction that caused the event has been
+    /// executed when this method returns; its `side_effects()` are still available.
+    /// 
+    /// Returns `true` if the event occurred, or `false` if the program halted before. Note that this does not return if the program never
+    /// reaches the event or halts.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.load(program)?;
+    /// while sys.run_until(Event::NextDraw) {
+    ///     println!("{}", Frame::capture(&sys));
+    /// }
+    /// ```
+    pub fn run_until(&mut self, event: Event) -> bool {
+        loop {
+            let opcode = self.fetch();
+            if !self.step() {
+                return event == Event::Halt;
+            }
+            self.sync_timers();
+            let effects = self.side_effects;
+            let occurred = match event {
+                Event::NextDraw => effects.screen_dirty,
+                Event::SoundStart => effects.sound_started,
+                Event::KeyWait => effects.waiting_for_key,
+                Event::Return => opcode == 0x00EE,
+                Event::Halt => false,
+            };
+            if occurred {
+                return true;
+            }
+        }
+    }
+
+    /// Runs the system frame by frame, yielding a snapshot of the display after each emulated 60 Hz frame (see `Frames`). The iterator ends when the
+    /// program halts.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.load(program)?;
+    /// let hashes: Vec<u64> = sys.frames().take(600).map(|frame| hash(&frame)).collect();
+    /// ```
+    pub fn frames(&mut self) -> Frames<'_> {
+        Frames::new(self)
+    }
+
+    /// Gets a snapshot of the display buffer, e.g. to draw the screen in a frontend that drives the system with `step()`. The snapshot is not
+    /// updated by later instructions.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// sys.load(program)?;
+    /// while sys.step() && !sys.side_effects().screen_dirty {}
+    /// let lit = (0..64).filter(|x| sys.framebuffer().pixel(*x, 0)).count();
+    /// ```
+    pub fn framebuffer(&self) -> Frame {
+        Frame::capture(self)
+    }
+
+    /// Gets the addresses of the last (up to 256) instructions executed by `step()`, oldest first.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// sys.step();
+    /// assert_eq!(sys.pc_history(), vec![0x200]);
+    /// ```
+    pub fn pc_history(&self) -> Vec<u16> {
+        self.pc_history.iter().copied().collect()
+    }
+
+    /// Gets the bytes of the most recently loaded program, or `None` if no program was loaded yet.
+    pub fn loaded_program(&self) -> Option<&[u8]> {
+        self.loaded.as_ref().map(|(bytes, _)| bytes.as_slice())
+    }
+
+    /// Gets the `SideEffects` of the last instruction executed by `step()`, e.g. to only render after instructions that changed the display.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// while sys.step() {
+    ///     if sys.side_effects().sound_started {
+    ///         beep();
+    ///     }
+    /// }
+    /// ```
+    pub fn side_effects(&self) -> SideEffects {
+        self.side_effects
+    }
+
+    /// Decrements the delay and sound timers by one tick, as if one timer period (1/60 s by default) had passed. Used to drive the timers manually together with `step()`.
+    /// 
+    /// # Example
+    /// ```
+    /// # use chip8::system::System;
+    /// let mut sys = System::new();
+    /// sys.delay_timer.set(2);
+    /// sys.tick_timers();
+    /// assert_eq!(sys.delay_timer.get(), 1);
+    /// ```
+    pub fn tick_timers(&mut self) {
+        self.delay_timer.tick();
+        self.sound_timer.tick();
+        self.stats.timer_ticks += 1;
+    }
+
+    /// Gets the number of complete timer periods that have passed on the emulated machine, see `emulated_time()`.
+    fn emulated_frames(&self) -> u64 {
+        (self.emulated_time().as_nanos() * self.timer_frequency as u128 / 1_000_000_000) as u64
+    }
+
+    /// Ticks the timers once for every emulated timer period that has passed since they were last synchronized. Used by `run_with(...)` in
+    /// `TimerMode::Frames`, and by frontends that drive the system with `step()` themselves.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// while sys.step() {
+    ///     sys.sync_timers();
+    /// }
+    /// ```
+    pub fn sync_timers(&mut self) {
+        let frames = self.emulated_frames();
+        while self.timer_frames < frames {
+            self.tick_timers();
+            self.timer_frames += 1;
+        }
+    }
+
+    /// Starts running the CHIP-8's fetch/decode/execute loop.
+    /// 
+    /// A mutable reference to a `Display` instance needs to be passed to update the display rendering once per frame.
+    /// The loop runs in frames of 1/60 s, paced by a `Scheduler`: each frame executes `loop_frequency / 60` instructions (see
+    /// `set_instructions_per_frame(...)`), so the speed does not depend on how long rendering takes. The delay and sound timers are decremented
+    /// according to the `timer_mode`: by default once per emulated timer period (see `set_timer_frequency(...)`), which is exactly once per frame at
+    /// the default 60 Hz, or independently by a `TimerTicker` thread for as long as the loop is running. Each frame consists of the following steps,
+    /// in order:
+    /// - Check keyboard input, including the hotkeys listed below, and check for programs received through `set_program_updates(...)`
+    /// - Fetch, decode & execute the instructions of the frame (see `step()`), then tick the timers for each emulated timer period that has passed
+    ///   (in `TimerMode::Frames`). While the `debugger` is paused, instructions are only executed when stepping, and the frame otherwise just polls
+    ///   the input; the debugger panel is shown on the display (see `Renderer::show_panel(...)`), and the live monitor is updated every
+    ///   `MONITOR_INTERVAL` frames while it is shown (see `Renderer::show_side_panel(...)`)
+    /// - Update the display, which can be any `Renderer` (e.g. a `Tee` of several renderers)
+    /// - Let the scheduler sleep until the end of the frame
+    /// 
+    /// The hotkeys control the emulator rather than the program:
+    /// - `+`/`-`: change the `loop_frequency` by 25%
+    /// - `F2`: swap to the next program of the playlist set with `set_playlist(...)`
+    /// - `F3`/`F4`: save and load the state to and from the file set with `set_state_file(...)`
+    /// - Arrow keys: pan the display if it only shows a part of the pixels
+    /// - `F5`-`F7`: toggle the `DisplayOptions`
+    /// - `F8` or `P`: pause or resume the execution in the `debugger`
+    /// - `F9`: toggle a breakpoint at the PC
+    /// - `F10`: execute a single instruction
+    /// - `Backspace`: restart the program (see `reset()`)
+    /// - `L`: reload the current program of the playlist from its file
+    /// - `M`: show or hide the live monitor of the `debugger`
+    /// - `PageUp`/`PageDown`: scroll the memory view of the monitor
+    /// - `Tab`: edit the memory in the monitor: the arrow keys and `PageUp`/`PageDown` move the cursor, and typed hex digits overwrite the memory
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let mut sys = System::new();
+    /// let mut display = Display::new();
+    /// let program = Program::load("path");
+    /// 
+    /// sys.load(program);
+    /// sys.run(&mut display);
+    /// ```
+    pub fn run(&mut self, display: &mut dyn Renderer) {
+        self.run_with(display, &mut input::keyboard());
+    }
+
+    /// Starts running the CHIP-8's fetch/decode/execute loop like `run(...)`, but receives key presses and control events from the given
+    /// `InputSource` instead of the local keyboard.
+    /// 
+    /// In addition to halting at the opcode `0000`, the loop stops when the input source reports `InputEvent::Quit`.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let (tx, mut input) = ChannelInput::new();
+    /// sys.run_with(&mut display, &mut input);
+    /// ```
+    pub fn run_with(&mut self, display: &mut dyn Renderer, input: &mut dyn InputSource) {
+
+        display.begin();
+
+        let started = Instant::now();
+        let ticker = match self.timer_mode {
+            TimerMode::Frames => None,
+            TimerMode::WallClock => Some(TimerTicker::start(&[&self.delay_timer, &self.sound_timer], self.timer_frequency)),
+        };
+        self.timer_frames = self.emulated_frames();
+        let mut scheduler = Scheduler::new(self.loop_frequency);
+        let mut frames = 0u64;
+        'run: loop {
+            while let Some(event) = input.poll() {
+                if !self.handle_event(event, display) {
+                    break 'run;
+                }
+            }
+            self.check_program_updates(display);
+            if scheduler.frequency() != self.loop_frequency {
+                scheduler.set_frequency(self.loop_frequency);
+            }
+
+            //Fetch, Decode & Execute the instructions of this frame
+            for _ in 0..scheduler.begin_frame() {
+                if !self.debugger.should_execute(self.pc) {
+                    break;
+                }
+                if !self.step() {
+                    break 'run;
+                }
+            }
+            if self.timer_mode == TimerMode::Frames {
+                self.sync_timers();
+            }
+            self.update_audio();
+            if self.debugger.take_changed() {
+                self.show_debugger(display);
+            }
+            frames += 1;
+            self.update_monitor(display, frames);
+
+            //Display updates
+            if display.update(self) {
+                self.stats.frames += 1;
+            }
+            self.memory.clear_dirty_rows();
+
+            scheduler.end_frame();
+        }
+
+        if let Some(ticker) = ticker {
+            self.stats.timer_ticks += ticker.ticks();
+        }
+        self.set_audio_playing(false);
+        self.stats.runtime += started.elapsed();
+        self.flush_persistent();
+        self.flush_input_log();
+    }
+
+    /// Runs the CHIP-8 like `run_with(...)`, but as a future, so the emulator can live inside an async application such as a bot or a web server.
+    /// Requires the `tokio` feature.
+    /// 
+    /// Instead of sleeping, the loop waits on three intervals: one executes the instructions due in each 60 Hz frame (according to the
+    /// `loop_frequency`) and then yields to other tasks, one ticks the timers at the timer frequency (in `TimerMode::WallClock`) and one updates the
+    /// display at 60 Hz. Events are
+    /// received from the channel as they arrive. The future completes when the program halts or `InputEvent::Quit` is received; closing the channel
+    /// does not stop it.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let (tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+    /// tokio::spawn(async move { bot(tx).await });
+    /// sys.run_async(&mut display, &mut events).await;
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn run_async(&mut self, display: &mut dyn Renderer, input: &mut tokio::sync::mpsc::UnboundedReceiver<InputEvent>) {
+        use tokio::time::{interval, MissedTickBehavior};
+
+        display.begin();
+
+        let started = Instant::now();
+        let frame = Duration::from_secs(1) / 60;
+        let (mut cpu, mut timers, mut render) = (interval(frame), interval(Duration::from_secs(1) / self.timer_frequency as u32), interval(frame));
+        cpu.set_missed_tick_behavior(MissedTickBehavior::Burst);
+        timers.set_missed_tick_behavior(MissedTickBehavior::Burst);
+        render.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        self.timer_frames = self.emulated_frames();
+        let mut owed_cycles = 0u32;
+        let mut rendered_frames = 0u64;
+        let mut input_open = true;
+        'run: loop {
+            tokio::select! {
+                event = input.recv(), if input_open => match event {
+                    Some(event) => if !self.handle_event(event, display) {
+                        break 'run;
+                    },
+                    None => input_open = false,
+                },
+                _ = cpu.tick() => {
+                    self.check_program_updates(display);
+                    owed_cycles += self.loop_frequency as u32;
+                    for _ in 0..owed_cycles / 60 {
+                        if !self.debugger.should_execute(self.pc) {
+                            break;
+                        }
+                        if !self.step() {
+                            break 'run;
+                        }
+                        if self.timer_mode == TimerMode::Frames {
+                            self.sync_timers();
+                        }
+                    }
+                    owed_cycles %= 60;
+                    tokio::task::yield_now().await;
+                },
+                _ = timers.tick(), if self.timer_mode == TimerMode::WallClock => self.tick_timers(),
+                _ = render.tick() => {
+                    self.update_audio();
+                    if self.debugger.take_changed() {
+                        self.show_debugger(display);
+                    }
+                    rendered_frames += 1;
+                    self.update_monitor(display, rendered_frames);
+                    if display.update(self) {
+                        self.stats.frames += 1;
+                    }
+                    self.memory.clear_dirty_rows();
+                },
+            }
+        }
+
+        self.set_audio_playing(false);
+        self.stats.runtime += started.elapsed();
+        self.flush_persistent();
+        self.flush_input_log();
+    }
+
+    /// Applies an input event received by `run_with(...)` or `run_async(...)`. Returns `false` if the event stops the loop.
+    fn handle_event(&mut self, event: InputEvent, display: &mut dyn Renderer) -> bool {
+        match event {
+            InputEvent::Key { .. } | InputEvent::Faster | InputEvent::Slower if self.is_replaying() => {},
+            InputEvent::Key { pressed: true, .. } if self.debugger.is_editing() => {},
+            InputEvent::Reset | InputEvent::LoadState | InputEvent::NextProgram | InputEvent::Reload if self.input_log.is_some() => {
+                log::warn!("Ignoring {:?} while the input is recorded or replayed", event);
+            },
+            InputEvent::HexDigit(_) if self.input_log.is_some() && self.debugger.is_editing() => {
+                log::warn!("The memory cannot be edited while the input is recorded or replayed");
+            },
+            InputEvent::Key { key, pressed } => {
+                let key = self.keymap[key as usize % 16];
+                #[cfg(feature = "tracing")]
+                let _span = tracing::trace_span!("input", key, pressed).entered();
+                if pressed && !self.keyboard.get(key) {
+                    self.stats.key_presses += 1;
+                }
+                self.keyboard.set(key, pressed, self.cycles);
+                if let Some(InputLog::Recording(recorder)) = &mut self.input_log {
+                    if let Err(e) = recorder.record_key(self.cycles, key, pressed) {
+                        log::error!("Could not record the input, stopping the recording: {}", e);
+                        self.input_log = None;
+                    }
+                }
+            },
+            InputEvent::Faster => self.set_loop_frequency(self.loop_frequency.saturating_add((self.loop_frequency / 4).max(1))),
+            InputEvent::Slower => self.set_loop_frequency(self.loop_frequency - self.loop_frequency / 5),
+            InputEvent::NextProgram => if let Some(title) = self.load_next_program() {
+                display.set_title(&format!("CHIP-8 - {}", title));
+            },
+            InputEvent::Reset => {
+                self.reset();
+                if self.debugger.is_paused() {
+                    self.show_debugger(display);
+                }
+            },
+            InputEvent::Reload => if let Some(title) = self.reload_program() {
+                display.set_title(&format!("CHIP-8 - {}", title));
+                if self.debugger.is_paused() {
+                    self.show_debugger(display);
+                }
+            },
+            InputEvent::Pan { dx, dy } if self.debugger.is_editing() => {
+                let key = match (dx, dy) {
+                    (-1, _) => EditorKey::Left,
+                    (1, _) => EditorKey::Right,
+                    (_, -1) => EditorKey::Up,
+                    _ => EditorKey::Down,
+                };
+                self.edit_memory(key, display);
+            },
+            InputEvent::Pan { dx, dy } => display.pan(dx, dy),
+            InputEvent::ToggleDisplayOption(option) => display.toggle(option),
+            InputEvent::TogglePause => if self.debugger.is_paused() {
+                self.debugger.resume(self.pc);
+            } else {
+                self.debugger.pause();
+            },
+            InputEvent::ToggleBreakpoint => self.debugger.toggle_breakpoint(self.pc),
+            InputEvent::Step => self.debugger.step(),
+            InputEvent::ToggleMonitor => {
+                self.debugger.toggle_monitor();
+                if self.debugger.is_monitor_shown() {
+                    display.show_side_panel(&self.debugger.monitor(self));
+                } else {
+                    display.show_side_panel("");
+                }
+            },
+            InputEvent::ScrollMonitor(rows) if self.debugger.is_editing() => {
+                self.edit_memory(if rows < 0 { EditorKey::PageUp } else { EditorKey::PageDown }, display);
+            },
+            InputEvent::ScrollMonitor(rows) => if self.debugger.is_monitor_shown() {
+                self.debugger.scroll_monitor(rows);
+                display.show_side_panel(&self.debugger.monitor(self));
+            },
+            InputEvent::ToggleEditor => {
+                self.debugger.toggle_editor(self.registers.i(), &self.memory);
+                display.show_side_panel(&self.debugger.monitor(self));
+            },
+            InputEvent::HexDigit(digit) => if self.debugger.is_editing() {
+                self.edit_memory(EditorKey::Digit(digit), display);
+            },
+            InputEvent::SaveState => match &self.state_file {
+                Some(file) => match self.save_state(file) {
+                    Ok(()) => log::info!("State saved to '{}'", file.display()),
+                    Err(e) => log::error!("Could not save the state to '{}': {}", file.display(), e),
+                },
+                None => log::warn!("No file to save the state to"),
+            },
+            InputEvent::LoadState => match self.state_file.clone() {
+                Some(file) => match self.load_state(&file) {
+                    Ok(()) => {
+                        log::info!("State loaded from '{}'", file.display());
+                        if self.debugger.is_paused() {
+                            self.show_debugger(display);
+                        }
+                    },
+                    Err(e) => log::error!("Could not load the state from '{}': {}", file.display(), e),
+                },
+                None => log::warn!("No file to load the state from"),
+            },
+            InputEvent::Quit => return false,
+        }
+        true
+    }
+
+    /// Shows the debugger panel on the display while the debugger is paused, and hides it otherwise.
+    fn show_debugger(&mut self, display: &mut dyn Renderer) {
+        let panel = if self.debugger.is_paused() { self.debugger.panel(self) } else { String::new() };
+        display.show_panel(&panel);
+    }
+
+    /// Passes a key to the memory editor of the debugger, and shows the result on the monitor, and on the debugger panel while it is paused.
+    fn edit_memory(&mut self, key: EditorKey, display: &mut dyn Renderer) {
+        if self.debugger.edit(key, &mut self.memory) && self.debugger.is_paused() {
+            self.show_debugger(display);
+        }
+        display.show_side_panel(&self.debugger.monitor(self));
+    }
+
+    /// Shows the live monitor of the debugger next to the display every `MONITOR_INTERVAL` frames while it is enabled, so it follows the program
+    /// without slowing down the emulation.
+    fn update_monitor(&mut self, display: &mut dyn Renderer, frame: u64) {
+        if self.debugger.is_monitor_shown() && frame.is_multiple_of(MONITOR_INTERVAL) {
+            display.show_side_panel(&self.debugger.monitor(self));
+        }
+    }
+
+    /// Starts or stops the tone of the `Audio` output, depending on whether the sound timer is active.
+    fn update_audio(&mut self) {
+        self.set_audio_playing(self.sound_timer.get() > 0);
+    }
+
+    /// Starts or stops the tone of the `Audio` output, if its state changed.
+    fn set_audio_playing(&mut self, playing: bool) {
+        if playing != self.audio_playing {
+            self.audio_playing = playing;
+            self.audio.set_playing(playing);
+        }
+    }
+
+    /// Swaps to the most recent program received through `set_program_updates(...)`, if any.
+    fn check_program_updates(&mut self, display: &mut dyn Renderer) {
+        if let Some(program) = self.program_updates.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            let title = program.info().title;
+            if self.input_log.is_some() {
+                log::warn!("Ignoring the update of '{}' while the input is recorded or replayed", title);
+                return;
+            }
+            match self.swap_program(program) {
+                Ok(()) => display.set_title(&format!("CHIP-8 - {}", title)),
+                Err(e) => log::error!("Could not load '{}': {}", title, e),
+            }
+        }
+    }
+}
+
+impl Default for System {
+
+    fn default() -> System {
+        System::new()
+    }
+}
+
+impl Drop for System {
+
+    /// Writes the persistent region, if any, to its file, and the recorded input to the input log.
+    fn drop(&mut self) {
+        self.flush_persistent();
+        self.flush_input_log();
+    }
+}
@@ -0,0 +1,106 @@
+//! Contains the `TerminalGuard` struct, which puts the terminal into the state needed for rendering and restores it afterwards.
+//!
+//! The terminal is restored when the guard is dropped, but also when the emulator panics or is interrupted with Ctrl-C, so a crash does not leave
+//! the user's terminal with a hidden cursor, changed colors or stuck in the alternate screen.
+
+use std::io::{self, Write};
+#[cfg(not(target_family = "wasm"))]
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+
+/// The exit code used when the emulator is interrupted with Ctrl-C (`128 + SIGINT`).
+#[cfg(not(target_family = "wasm"))]
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Ensures that the panic hook and the Ctrl-C handler are only installed once.
+static INSTALL_HANDLERS: Once = Once::new();
+
+/// Whether the terminal was asked to report key releases, which has to be undone by `restore()`.
+static KEY_RELEASES: AtomicBool = AtomicBool::new(false);
+
+/// Switches the terminal to the alternate screen, hides the cursor and enables raw mode while it is alive, and restores the terminal when it is
+/// dropped.
+///
+/// # Example
+/// ```ignore
+/// let mut display = Display::new();
+/// {
+///     let _terminal = TerminalGuard::enter();
+///     sys.run(&mut display);
+/// }
+/// ```
+pub struct TerminalGuard {
+    _private: (),
+}
+
+impl TerminalGuard {
+
+    /// Switches to the alternate screen, hides the cursor and enables raw mode, so the keyboard input (see `input::keyboard()`) receives keys as
+    /// they are typed. If the terminal supports it, it is also asked to report key releases.
+    ///
+    /// On first use, this also installs a panic hook and a Ctrl-C handler (except on WebAssembly, which has no signals) that restore the terminal before the
+    /// process terminates.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let terminal = TerminalGuard::enter();
+    /// ```
+    pub fn enter() -> TerminalGuard {
+        INSTALL_HANDLERS.call_once(|| {
+            let default_hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                restore();
+                default_hook(info);
+            }));
+            #[cfg(not(target_family = "wasm"))]
+            let _res = ctrlc::set_handler(|| {
+                restore();
+                process::exit(INTERRUPTED_EXIT_CODE);
+            });
+        });
+
+        print!("{}[?1049h{}[?25l", 27 as char, 27 as char);
+        let _res = io::stdout().flush();
+        #[cfg(not(target_family = "wasm"))]
+        if crossterm::terminal::enable_raw_mode().is_ok() && crossterm::terminal::supports_keyboard_enhancement().unwrap_or(false) {
+            let flags = crossterm::event::KeyboardEnhancementFlags::REPORT_EVENT_TYPES;
+            let _res = crossterm::execute!(io::stdout(), crossterm::event::PushKeyboardEnhancementFlags(flags));
+            KEY_RELEASES.store(true, Ordering::SeqCst);
+        }
+        TerminalGuard { _private: () }
+    }
+}
+
+impl Drop for TerminalGuard {
+
+    /// Restores the terminal.
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Resets all colors and text attributes, shows the cursor, leaves the alternate screen and disables raw mode.
+///
+/// Calling this function while the terminal is already in its normal state has no effect, so it is safe to call it from several cleanup paths.
+///
+/// # Example
+/// ```ignore
+/// restore();
+/// ```
+pub fn restore() {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        if KEY_RELEASES.swap(false, Ordering::SeqCst) {
+            let _res = crossterm::execute!(io::stdout(), crossterm::event::PopKeyboardEnhancementFlags);
+        }
+        let _res = crossterm::terminal::disable_raw_mode();
+    }
+    print!("{}[0m{}[?25h{}[?1049l", 27 as char, 27 as char, 27 as char);
+    let _res = io::stdout().flush();
+}
+
+/// Whether the terminal reports key releases, because a `TerminalGuard` asked it to.
+pub fn reports_key_releases() -> bool {
+    KEY_RELEASES.load(Ordering::SeqCst)
+}
@@ -0,0 +1,157 @@
+//! Provides an allocation-free execution trace. Events are recorded by the emulation thread into a preallocated ring buffer and written to a file
+//! by a separate writer thread, so enabling a trace does not slow down the fetch/decode/execute loop with per-instruction allocations or I/O.
+//!
+//! Each line of the trace file has the form `<cycle> <PC>: <opcode>  <mnemonic>`, e.g. `        42 20A: D015  DRW V0, V1, 5`. Opcodes that cannot
+//! be decoded are shown as `???`, which makes it easy to see how a crashing ROM got there.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crate::disasm;
+
+/// The number of events the ring buffer can hold before new events are dropped.
+const TRACE_CAPACITY: usize = 1 << 14;
+
+/// A single executed instruction, as recorded in the trace.
+#[derive(Copy, Clone)]
+pub struct TraceEvent {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+}
+
+/// The state shared between the emulation thread (producer) and the writer thread (consumer).
+///
+/// Each slot consists of two atomics: the cycle count and the PC and opcode packed into a single word. The producer only ever advances `head` and the
+/// consumer only ever advances `tail`, so no locking is required.
+struct RingBuffer {
+    cycles: Box<[AtomicU64]>,
+    instructions: Box<[AtomicU64]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+}
+
+impl RingBuffer {
+
+    /// Creates a new ring buffer with `TRACE_CAPACITY` slots. This is the only allocation performed by the trace.
+    fn new() -> RingBuffer {
+        RingBuffer {
+            cycles: (0..TRACE_CAPACITY).map(|_| AtomicU64::new(0)).collect(),
+            instructions: (0..TRACE_CAPACITY).map(|_| AtomicU64::new(0)).collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Appends an event to the buffer, or counts it as dropped if the buffer is full.
+    fn push(&self, event: TraceEvent) {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head.wrapping_sub(tail) >= TRACE_CAPACITY {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let slot = head % TRACE_CAPACITY;
+        self.cycles[slot].store(event.cycle, Ordering::Relaxed);
+        self.instructions[slot].store(((event.pc as u64) << 16) | event.opcode as u64, Ordering::Relaxed);
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Removes the oldest event from the buffer, if there is one.
+    fn pop(&self) -> Option<TraceEvent> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if tail == head {
+            return None;
+        }
+        let slot = tail % TRACE_CAPACITY;
+        let cycle = self.cycles[slot].load(Ordering::Relaxed);
+        let instruction = self.instructions[slot].load(Ordering::Relaxed);
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Some(TraceEvent { cycle, pc: (instruction >> 16) as u16, opcode: instruction as u16 })
+    }
+}
+
+/// Records executed instructions and hands them to a background thread that writes them to a file.
+///
+/// Recording an event only consists of a few atomic stores into a preallocated buffer. If the writer thread cannot keep up, events are dropped instead
+/// of blocking the emulation; the number of dropped events is reported at the end of the trace file.
+pub struct Tracer {
+    buffer: Arc<RingBuffer>,
+    writer: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Tracer {
+
+    /// Creates a new `Tracer` writing to the file at the given path and starts its writer thread.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let tracer = Tracer::create("trace.log")?;
+    /// ```
+    pub fn create<P>(path: P) -> io::Result<Tracer>
+        where P: AsRef<Path>, {
+            let file = BufWriter::new(File::create(path)?);
+            let buffer = Arc::new(RingBuffer::new());
+            let shared = Arc::clone(&buffer);
+            let writer = thread::spawn(move || write_events(&shared, file));
+            Ok(Tracer { buffer, writer: Some(writer) })
+    }
+
+    /// Records a single event. Never allocates or blocks.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let tracer = Tracer::create("trace.log")?;
+    /// tracer.record(TraceEvent { cycle: 0, pc: 0x200, opcode: 0x00E0 });
+    /// ```
+    pub fn record(&self, event: TraceEvent) {
+        self.buffer.push(event);
+    }
+}
+
+impl Drop for Tracer {
+
+    /// Signals the writer thread to write all remaining events and waits for it to finish.
+    fn drop(&mut self) {
+        self.buffer.closed.store(true, Ordering::Release);
+        if let Some(writer) = self.writer.take() {
+            let _res = writer.join();
+        }
+    }
+}
+
+/// The body of the writer thread. Drains the ring buffer into the output until the `Tracer` is dropped. The mnemonics are only computed here, so
+/// the emulation thread never has to format them.
+fn write_events<W: Write>(buffer: &RingBuffer, mut out: W) -> io::Result<()> {
+    loop {
+        let closed = buffer.closed.load(Ordering::Acquire);
+        let mut written = false;
+        while let Some(event) = buffer.pop() {
+            let text = disasm::mnemonic(event.opcode.into()).unwrap_or_else(|| "???".to_string());
+            writeln!(out, "{:>10} {:0>3X}: {:0>4X}  {}", event.cycle, event.pc, event.opcode, text)?;
+            written = true;
+        }
+        if closed {
+            break;
+        }
+        if !written {
+            out.flush()?;
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    let dropped = buffer.dropped.load(Ordering::Relaxed);
+    if dropped > 0 {
+        writeln!(out, "{} events dropped", dropped)?;
+    }
+    out.flush()
+}
@@ -0,0 +1,148 @@
+//! Exposes the emulator to JavaScript through `wasm-bindgen`, so it can be embedded in a web page. Only available with the `wasm` feature.
+//!
+//! In the browser, there is no terminal, no threads and no way to sleep, so the JavaScript host drives the `Emulator` itself: it calls
+//! `run_frame()` once per animation frame, draws the bytes of `framebuffer()` onto a canvas and forwards key events with `key_down(...)` and
+//! `key_up(...)`. The main loop of `System::run_with(...)` is not used.
+//!
+//! Build with `cargo build --lib --target wasm32-unknown-unknown --features wasm`, then generate the JavaScript bindings with
+//! `wasm-bindgen --target web target/wasm32-unknown-unknown/debug/chip8.wasm --out-dir pkg`.
+//!
+//! ```text
+//! import init, { Emulator } from "./pkg/chip8.js";
+//! await init();
+//! const emulator = new Emulator(new Uint8Array(await (await fetch("pong.ch8")).arrayBuffer()));
+//! function frame() {
+//!     emulator.run_frame();
+//!     const pixels = emulator.framebuffer();
+//!     // draw `pixels` (emulator.width() x emulator.height(), one byte per pixel) onto the canvas
+//!     requestAnimationFrame(frame);
+//! }
+//! requestAnimationFrame(frame);
+//! ```
+
+use wasm_bindgen::prelude::*;
+use crate::program::Program;
+use crate::quirks::Quirks;
+use crate::renderer;
+use crate::system::{System, Variant};
+
+/// A CHIP-8 system that is driven frame by frame from JavaScript.
+#[wasm_bindgen]
+pub struct Emulator {
+    system: System,
+    halted: bool,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+
+    /// Creates a CHIP-8 system and loads the given ROM bytes into it.
+    ///
+    /// # Errors
+    /// Returns an error if the ROM does not fit into the memory.
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8]) -> Result<Emulator, JsError> {
+        Emulator::with_variant(rom, "chip8")
+    }
+
+    /// Creates a system of the given variant (`chip8`, `schip` or `xochip`) and loads the given ROM bytes into it.
+    ///
+    /// # Errors
+    /// Returns an error if the variant is unknown or the ROM does not fit into the memory.
+    pub fn with_variant(rom: &[u8], variant: &str) -> Result<Emulator, JsError> {
+        let variant: Variant = variant.parse().map_err(|e: String| JsError::new(&e))?;
+        let mut system = System::new();
+        system.set_variant(variant);
+        system.load(Program::from_bytes(rom.to_vec())).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(Emulator { system, halted: false })
+    }
+
+    /// Sets the quirks, given as a preset (`chip8`, `schip`) or a comma-separated list of flags such as `shift_uses_vy,clip_sprites=off`.
+    ///
+    /// # Errors
+    /// Returns an error if the flags cannot be parsed.
+    pub fn set_quirks(&mut self, flags: &str) -> Result<(), JsError> {
+        self.system.quirks = Quirks::new().with_flags(flags).map_err(|e| JsError::new(&e))?;
+        Ok(())
+    }
+
+    /// Sets the number of instructions executed by each call to `run_frame()`.
+    pub fn set_instructions_per_frame(&mut self, instructions: u16) {
+        self.system.set_instructions_per_frame(instructions);
+    }
+
+    /// Executes a single instruction. Returns `false` once the program halted, e.g. with `00FD` or an error.
+    pub fn step(&mut self) -> bool {
+        if !self.halted && !self.system.step() {
+            self.halted = true;
+        }
+        !self.halted
+    }
+
+    /// Executes the instructions of one 60 Hz frame and ticks the timers once. Returns `false` once the program halted.
+    pub fn run_frame(&mut self) -> bool {
+        for _ in 0..self.system.instructions_per_frame() {
+            if !self.step() {
+                break;
+            }
+        }
+        if !self.halted {
+            self.system.tick_timers();
+        }
+        !self.halted
+    }
+
+    /// Gets the pixels of the display, one byte per pixel in rows of `width()` pixels. Each byte is the bit mask of the display planes the pixel is
+    /// on in, i.e. `0` for off and `1` for on unless the XO-CHIP draws in its second plane.
+    pub fn framebuffer(&mut self) -> Vec<u8> {
+        self.system.memory.clear_dirty_rows();
+        renderer::capture(&self.system, 1)
+    }
+
+    /// Checks whether the display changed since the last call to `framebuffer()`, so the canvas only needs to be drawn again if it did.
+    pub fn display_changed(&self) -> bool {
+        self.system.memory.dirty_rows() != 0
+    }
+
+    /// Gets the width of the display in pixels, which changes when the SUPER-CHIP switches to its high-resolution mode.
+    pub fn width(&self) -> u32 {
+        self.system.screen_width as u32
+    }
+
+    /// Gets the height of the display in pixels.
+    pub fn height(&self) -> u32 {
+        self.system.screen_height as u32
+    }
+
+    /// Presses the CHIP-8 key with the given index (`0x0`-`0xF`).
+    pub fn key_down(&mut self, key: u8) {
+        self.set_key(key, true);
+    }
+
+    /// Releases the CHIP-8 key with the given index (`0x0`-`0xF`).
+    pub fn key_up(&mut self, key: u8) {
+        self.set_key(key, false);
+    }
+
+    /// Checks whether the sound timer is active, so the host should play a tone.
+    pub fn sound_active(&self) -> bool {
+        self.system.sound_timer.get() > 0
+    }
+
+    /// Restarts the program from the state right after it was loaded.
+    pub fn reset(&mut self) {
+        self.system.reset();
+        self.halted = false;
+    }
+}
+
+impl Emulator {
+
+    /// Sets the state of a CHIP-8 key, ignoring indices outside of the keypad.
+    fn set_key(&mut self, key: u8, pressed: bool) {
+        if key < 16 {
+            let cycle = self.system.cycles();
+            self.system.keyboard.set(key, pressed, cycle);
+        }
+    }
+}
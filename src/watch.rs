@@ -0,0 +1,75 @@
+//! Contains the `RomWatcher` struct, which reloads a ROM whenever its file changes. Only available with the `watch` feature.
+//!
+//! Together with `System::set_program_updates(...)`, this allows a tight edit-assemble-run loop: as soon as the assembler writes a new version of the
+//! ROM, the running emulator is reset and starts executing it.
+
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+use notify::{EventKind, RecursiveMode, Watcher};
+use crate::program::Program;
+
+/// The time to wait after a change before reloading the ROM, so a file that is written in several steps is only loaded once it is complete.
+const SETTLE_TIME: Duration = Duration::from_millis(50);
+
+/// Watches a ROM file and sends a new `Program` whenever it is modified. Watching stops when the `RomWatcher` is dropped.
+///
+/// The directory containing the ROM is watched rather than the file itself, since many editors and tools replace files instead of writing to them.
+///
+/// # Example
+/// ```ignore
+/// let (tx, rx) = mpsc::channel();
+/// let _watcher = RomWatcher::start("game.ch8", tx)?;
+/// sys.set_program_updates(rx);
+/// sys.run(&mut display);
+/// ```
+pub struct RomWatcher {
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl RomWatcher {
+
+    /// Starts watching the ROM at the given path, sending the reloaded programs through `programs`.
+    ///
+    /// # Errors
+    /// Returns an error if the directory containing the ROM cannot be watched.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let (tx, rx) = mpsc::channel();
+    /// let watcher = RomWatcher::start("game.ch8", tx)?;
+    /// ```
+    pub fn start<P>(path: P, programs: Sender<Program>) -> io::Result<RomWatcher>
+        where P: AsRef<Path>, {
+            let path = path.as_ref().to_path_buf();
+            let directory = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf();
+            let rom = path.canonicalize()?;
+            let mut current = Program::load(&rom).map(|p| p.instructions).unwrap_or_default();
+            let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(e) => {
+                        log::warn!("Error while watching '{}': {}", rom.display(), e);
+                        return;
+                    },
+                };
+                if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) || !event.paths.iter().any(|p| p.canonicalize().is_ok_and(|p| p == rom)) {
+                    return;
+                }
+                thread::sleep(SETTLE_TIME);
+                match Program::load(&rom) {
+                    Ok(program) if program.instructions == current => {},
+                    Ok(program) => {
+                        current = program.instructions.clone();
+                        log::info!("'{}' changed, reloading", rom.display());
+                        let _res = programs.send(program);
+                    },
+                    Err(e) => log::warn!("Could not reload '{}': {}", rom.display(), e),
+                }
+            }).map_err(io::Error::other)?;
+            watcher.watch(&directory, RecursiveMode::NonRecursive).map_err(io::Error::other)?;
+            Ok(RomWatcher { _watcher: watcher })
+    }
+}
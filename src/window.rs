@@ -0,0 +1,236 @@
+//! Contains the `WindowRenderer`, a `Renderer` that shows the display in a window instead of the terminal. Only available with the `window` feature.
+//!
+//! Unlike the terminal `Display`, the window does not depend on the size of the terminal or on the speed of stdout, and it draws each CHIP-8 pixel
+//! as a solid square with configurable colors.
+
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+use crate::input::{InputEvent, KEYBOARD_KEYS};
+use crate::renderer::{self, Renderer, PLANE_COLORS};
+use crate::system::{DisplayLayout, DisplayOption, System};
+
+/// The interval in which the window is redrawn and its key events are read. The window is updated from `Renderer::update(...)`, which is called
+/// after every instruction, so faster updates would only slow down the emulation.
+const FRAME_INTERVAL: Duration = Duration::from_micros(16_667);
+
+/// The default number of window pixels per CHIP-8 pixel in each direction.
+pub const DEFAULT_PIXEL_SCALE: usize = 10;
+
+/// The default color of pixels that are on, as `0xRRGGBB`.
+pub const DEFAULT_ON_COLOR: u32 = 0xFFFFFF;
+
+/// The default color of pixels that are off, as `0xRRGGBB`.
+pub const DEFAULT_OFF_COLOR: u32 = 0x000000;
+
+/// A `Renderer` that shows the display in a window, at most 60 times per second.
+///
+/// The window has the size of the 64x32 display of the CHIP-8, scaled by the pixel scale. Larger displays, such as the high-resolution mode of the
+/// SUPER-CHIP, are stretched to the same window. The second display plane of the XO-CHIP is drawn in the `PLANE_COLORS`. Keys pressed while the
+/// window is focused are sent to the `Sender` given with `with_input(...)`, using the same mapping and hotkeys as the terminal (see
+/// `input::keyboard()`), and closing the window or pressing `Escape` sends `InputEvent::Quit`. The debugger panel is not shown in the window.
+///
+/// # Example
+/// ```ignore
+/// let input = keyboard();
+/// let mut window = WindowRenderer::open("CHIP-8", 10)?.with_colors(0xFFB000, 0x202020).with_input(input.sender());
+/// sys.run_with(&mut window, &mut input);
+/// ```
+pub struct WindowRenderer {
+    window: Window,
+    on: u32,
+    off: u32,
+    inverted: bool,
+    input: Option<Sender<InputEvent>>,
+    pending: bool,
+    last_frame: Instant,
+    closed: bool,
+}
+
+impl WindowRenderer {
+
+    /// Opens a window with the given title, drawing each CHIP-8 pixel as a square of `scale` x `scale` window pixels.
+    ///
+    /// # Errors
+    /// Returns an error message if the window cannot be opened, e.g. because there is no graphical session.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let window = WindowRenderer::open("CHIP-8", DEFAULT_PIXEL_SCALE)?;
+    /// ```
+    pub fn open(title: &str, scale: usize) -> Result<WindowRenderer, String> {
+        let scale = scale.max(1);
+        let (width, height) = (DisplayLayout::DEFAULT.width as usize * scale, DisplayLayout::DEFAULT.height as usize * scale);
+        let mut window = Window::new(title, width, height, WindowOptions::default()).map_err(|e| e.to_string())?;
+        window.set_target_fps(0);
+        Ok(WindowRenderer {
+            window,
+            on: DEFAULT_ON_COLOR,
+            off: DEFAULT_OFF_COLOR,
+            inverted: false,
+            input: None,
+            pending: true,
+            last_frame: Instant::now() - FRAME_INTERVAL,
+            closed: false,
+        })
+    }
+
+    /// Sets the colors of pixels that are on and off, as `0xRRGGBB`.
+    pub fn with_colors(mut self, on: u32, off: u32) -> WindowRenderer {
+        self.on = on;
+        self.off = off;
+        self.pending = true;
+        self
+    }
+
+    /// Sends the key events of the window to the given input, e.g. `ChannelInput::sender()`.
+    pub fn with_input(mut self, input: Sender<InputEvent>) -> WindowRenderer {
+        self.input = Some(input);
+        self
+    }
+
+    /// Sends the keys pressed and released since the last update to the input.
+    fn forward_keys(&mut self) {
+        let Some(input) = &self.input else { return };
+        let mut events = Vec::new();
+        if !self.window.is_open() && !self.closed {
+            self.closed = true;
+            events.push(InputEvent::Quit);
+        }
+        for key in self.window.get_keys_pressed(KeyRepeat::No) {
+            if let Some(event) = hotkey(key) {
+                events.push(event);
+                continue;
+            }
+            if let Some(digit) = hex_digit(key) {
+                events.push(InputEvent::HexDigit(digit));
+            }
+            if let Some(idx) = chip8_key(key) {
+                events.push(InputEvent::Key { key: idx, pressed: true });
+            }
+        }
+        for key in self.window.get_keys_released() {
+            if let Some(idx) = chip8_key(key) {
+                events.push(InputEvent::Key { key: idx, pressed: false });
+            }
+        }
+        for event in events {
+            if input.send(event).is_err() {
+                self.input = None;
+                return;
+            }
+        }
+    }
+}
+
+impl Renderer for WindowRenderer {
+
+    fn update(&mut self, sys: &System) -> bool {
+        self.pending |= sys.memory.dirty_rows() != 0;
+        if self.last_frame.elapsed() < FRAME_INTERVAL {
+            return false;
+        }
+        self.last_frame = Instant::now();
+
+        let rendered = self.pending;
+        if self.pending {
+            self.pending = false;
+            let (on, off) = if self.inverted { (self.off, self.on) } else { (self.on, self.off) };
+            let palette = [off, on, PLANE_COLORS[0], PLANE_COLORS[1]];
+            let buffer: Vec<u32> = renderer::capture(sys, 1).into_iter().map(|pixel| palette[pixel as usize % palette.len()]).collect();
+            if let Err(e) = self.window.update_with_buffer(&buffer, sys.screen_width as usize, sys.screen_height as usize) {
+                log::error!("Could not update the window: {}", e);
+            }
+        } else {
+            self.window.update();
+        }
+        self.forward_keys();
+        rendered
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    /// Only `DisplayOption::Invert` is supported, which swaps the colors of pixels that are on and off.
+    fn toggle(&mut self, option: DisplayOption) {
+        if option == DisplayOption::Invert {
+            self.inverted = !self.inverted;
+            self.pending = true;
+        }
+    }
+}
+
+/// Maps a key of the window to the control event it triggers, if it is one of the hotkeys.
+fn hotkey(key: Key) -> Option<InputEvent> {
+    match key {
+        Key::Equal | Key::NumPadPlus => Some(InputEvent::Faster),
+        Key::Minus | Key::NumPadMinus => Some(InputEvent::Slower),
+        Key::F2 => Some(InputEvent::NextProgram),
+        Key::F3 => Some(InputEvent::SaveState),
+        Key::F4 => Some(InputEvent::LoadState),
+        Key::F5 => Some(InputEvent::ToggleDisplayOption(DisplayOption::Invert)),
+        Key::F6 => Some(InputEvent::ToggleDisplayOption(DisplayOption::HighContrast)),
+        Key::F7 => Some(InputEvent::ToggleDisplayOption(DisplayOption::Decay)),
+        Key::F8 => Some(InputEvent::TogglePause),
+        Key::F9 => Some(InputEvent::ToggleBreakpoint),
+        Key::F10 => Some(InputEvent::Step),
+        Key::P => Some(InputEvent::TogglePause),
+        Key::Backspace => Some(InputEvent::Reset),
+        Key::L => Some(InputEvent::Reload),
+        Key::M => Some(InputEvent::ToggleMonitor),
+        Key::PageUp => Some(InputEvent::ScrollMonitor(-1)),
+        Key::PageDown => Some(InputEvent::ScrollMonitor(1)),
+        Key::Tab => Some(InputEvent::ToggleEditor),
+        Key::Escape => Some(InputEvent::Quit),
+        _ => None,
+    }
+}
+
+/// Maps a key of the window to the index of the CHIP-8 key it represents (see `KEYBOARD_KEYS`).
+fn chip8_key(key: Key) -> Option<u8> {
+    let c = match key {
+        Key::Key1 => b'1',
+        Key::Key2 => b'2',
+        Key::Key3 => b'3',
+        Key::Key4 => b'4',
+        Key::Q => b'Q',
+        Key::W => b'W',
+        Key::E => b'E',
+        Key::R => b'R',
+        Key::A => b'A',
+        Key::S => b'S',
+        Key::D => b'D',
+        Key::F => b'F',
+        Key::Z => b'Z',
+        Key::X => b'X',
+        Key::C => b'C',
+        Key::V => b'V',
+        _ => return None,
+    };
+    KEYBOARD_KEYS.iter().position(|k| *k == c).map(|idx| idx as u8)
+}
+
+/// Maps a key of the window to the hexadecimal digit it types, if it is one (see `InputEvent::HexDigit`).
+fn hex_digit(key: Key) -> Option<u8> {
+    let digit = match key {
+        Key::Key0 | Key::NumPad0 => 0x0,
+        Key::Key1 | Key::NumPad1 => 0x1,
+        Key::Key2 | Key::NumPad2 => 0x2,
+        Key::Key3 | Key::NumPad3 => 0x3,
+        Key::Key4 | Key::NumPad4 => 0x4,
+        Key::Key5 | Key::NumPad5 => 0x5,
+        Key::Key6 | Key::NumPad6 => 0x6,
+        Key::Key7 | Key::NumPad7 => 0x7,
+        Key::Key8 | Key::NumPad8 => 0x8,
+        Key::Key9 | Key::NumPad9 => 0x9,
+        Key::A => 0xA,
+        Key::B => 0xB,
+        Key::C => 0xC,
+        Key::D => 0xD,
+        Key::E => 0xE,
+        Key::F => 0xF,
+        _ => return None,
+    };
+    Some(digit)
+}
@@ -0,0 +1,31 @@
+//! Runs the conformance tests of `chip8::conformance` and fails on any display that differs from its screenshot in `test/expected/`, on any test
+//! ROM missing from `test/roms/` (unless `CHIP8_SKIP_MISSING_ROMS` is set), and on any opcode check that leaves a register with an unexpected
+//! value.
+
+use std::path::Path;
+use chip8::conformance::{self, Verdict};
+
+#[test]
+fn displays_match_screenshots() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut failures = Vec::new();
+    for case in &conformance::CASES {
+        match conformance::check(case, root) {
+            Ok(Verdict::MissingRom(path)) if conformance::skip_missing_roms() => eprintln!("Skipping {}: {} not found", case.name, path.display()),
+            Ok(verdict) if verdict.is_ok() => {},
+            Ok(Verdict::Mismatch { expected, actual }) => failures.push(format!("{}: expected\n{}got\n{}", case.name, expected, actual)),
+            Ok(Verdict::MissingExpected(_)) => failures.push(format!("{}: no screenshot (record it with `chip8 conformance --bless`)", case.name)),
+            Ok(verdict) => failures.push(format!("{}: {}", case.name, verdict)),
+            Err(e) => failures.push(format!("{}: {}", case.name, e)),
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn opcodes_set_registers_and_flags() {
+    let failures: Vec<String> = conformance::OPCODE_CHECKS.iter()
+        .filter_map(|check| conformance::check_opcodes(check).err().map(|e| format!("{}: {}", check.name, e)))
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}